@@ -2,26 +2,20 @@
 set -euo pipefail
 
 DB_PATH="/data/db.sqlite"
-MIG_DIR="/app/migrations"
 
 echo "[entrypoint] usando DB: $DB_PATH"
 
 # Asegura PATH con cargo
 export PATH="/usr/local/cargo/bin:/usr/local/rustup/toolchains/*/bin:$PATH"
 
-# Crea DB si no existe y aplica migraciones
+# Crea la DB si no existe. Las migraciones las aplica la app al arrancar
+# (src/migrations.rs, tabla schema_migrations); `libro_reviews --dry-run` muestra las pendientes.
 if [ ! -f "$DB_PATH" ]; then
-  echo "[entrypoint] creando DB y aplicando migraciones..."
+  echo "[entrypoint] creando DB..."
   mkdir -p "$(dirname "$DB_PATH")"
-  sqlite3 "$DB_PATH" "PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;"
-  shopt -s nullglob
-  for f in "$MIG_DIR"/*.sql; do
-    echo "[entrypoint] aplicando $f"
-    sqlite3 "$DB_PATH" < "$f"
-  done
-  echo "[entrypoint] ✅ migraciones listas"
+  sqlite3 "$DB_PATH" "PRAGMA journal_mode=WAL;"
 else
-  echo "[entrypoint] DB ya existe; saltando inicialización."
+  echo "[entrypoint] DB ya existe; la app aplicará las migraciones pendientes."
 fi
 
 echo "[entrypoint] iniciando app..."
@@ -0,0 +1,27 @@
+-- Mantener books.sales_count = SUM(yearly_sales.sales) del libro
+-- Los triggers recalculan el total (no suman deltas) para que nunca acumulen errores.
+-- Para corregir datos anteriores a esta migración: POST /api/admin/sales/recompute
+
+CREATE TRIGGER IF NOT EXISTS trg_yearly_sales_insert
+AFTER INSERT ON yearly_sales
+BEGIN
+    UPDATE books
+       SET sales_count = (SELECT COALESCE(SUM(sales), 0) FROM yearly_sales WHERE book_id = NEW.book_id)
+     WHERE id = NEW.book_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_yearly_sales_update
+AFTER UPDATE OF sales, book_id ON yearly_sales
+BEGIN
+    UPDATE books
+       SET sales_count = (SELECT COALESCE(SUM(sales), 0) FROM yearly_sales WHERE book_id = books.id)
+     WHERE id IN (OLD.book_id, NEW.book_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_yearly_sales_delete
+AFTER DELETE ON yearly_sales
+BEGIN
+    UPDATE books
+       SET sales_count = (SELECT COALESCE(SUM(sales), 0) FROM yearly_sales WHERE book_id = OLD.book_id)
+     WHERE id = OLD.book_id;
+END;
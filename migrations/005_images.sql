@@ -0,0 +1,4 @@
+-- Imágenes subidas: nombre del archivo en UPLOAD_DIR/<authors|books>/ (hash SHA-256 del contenido + extensión).
+-- NULL = sin imagen. La miniatura se guarda junto al original como <hash>_thumb.<ext>.
+ALTER TABLE authors ADD COLUMN photo TEXT;
+ALTER TABLE books ADD COLUMN cover TEXT;
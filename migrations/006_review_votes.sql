@@ -0,0 +1,28 @@
+-- Votos positivos de reseñas: un voto por votante y reseña.
+-- `voter` es `user:<id>` para usuarios autenticados o `anon:<sha256>` (IP + User-Agent) para anónimos.
+-- reviews.positive_votes lo mantienen los triggers sumando/restando (los valores previos se conservan
+-- como base porque no tienen votos registrados).
+
+CREATE TABLE review_votes (
+    review_id INTEGER NOT NULL,
+    voter TEXT NOT NULL,
+    user_id INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (review_id, voter),
+    FOREIGN KEY (review_id) REFERENCES reviews(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_review_votes_user ON review_votes(user_id);
+
+CREATE TRIGGER IF NOT EXISTS trg_review_votes_insert
+AFTER INSERT ON review_votes
+BEGIN
+    UPDATE reviews SET positive_votes = COALESCE(positive_votes, 0) + 1 WHERE id = NEW.review_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_review_votes_delete
+AFTER DELETE ON review_votes
+BEGIN
+    UPDATE reviews SET positive_votes = MAX(COALESCE(positive_votes, 0) - 1, 0) WHERE id = OLD.review_id;
+END;
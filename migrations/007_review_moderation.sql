@@ -0,0 +1,12 @@
+-- Moderación de reseñas (src/moderation.rs).
+-- Solo las reseñas `approved` son públicas (listado del libro, promedios, dashboard, estadísticas, búsqueda).
+-- Las existentes quedan aprobadas; `moderation_reason` explica por qué una reseña quedó pendiente o rechazada.
+
+ALTER TABLE reviews ADD COLUMN status TEXT NOT NULL DEFAULT 'approved'
+    CHECK (status IN ('pending', 'approved', 'rejected'));
+ALTER TABLE reviews ADD COLUMN moderation_reason TEXT;
+ALTER TABLE reviews ADD COLUMN moderated_at TIMESTAMP;
+ALTER TABLE reviews ADD COLUMN moderated_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_reviews_book_status ON reviews(book_id, status);
+CREATE INDEX idx_reviews_status ON reviews(status, created_at);
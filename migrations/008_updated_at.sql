@@ -0,0 +1,77 @@
+-- Fecha de última modificación de cada entidad (Last-Modified de la API, src/api/conditional.rs).
+-- SQLite no admite ADD COLUMN con un DEFAULT no constante: las filas existentes se rellenan acá
+-- y los triggers la mantienen en cada INSERT/UPDATE, incluidos los que hacen otros triggers
+-- (ventas -> books.sales_count, votos -> reviews.positive_votes).
+-- El WHEN evita que el UPDATE del propio trigger lo vuelva a disparar y respeta un valor explícito.
+
+ALTER TABLE authors ADD COLUMN updated_at TIMESTAMP;
+ALTER TABLE books ADD COLUMN updated_at TIMESTAMP;
+ALTER TABLE reviews ADD COLUMN updated_at TIMESTAMP;
+ALTER TABLE yearly_sales ADD COLUMN updated_at TIMESTAMP;
+ALTER TABLE users ADD COLUMN updated_at TIMESTAMP;
+
+UPDATE authors SET updated_at = CURRENT_TIMESTAMP;
+UPDATE books SET updated_at = CURRENT_TIMESTAMP;
+UPDATE reviews SET updated_at = COALESCE(created_at, CURRENT_TIMESTAMP);
+UPDATE yearly_sales SET updated_at = CURRENT_TIMESTAMP;
+UPDATE users SET updated_at = COALESCE(created_at, CURRENT_TIMESTAMP);
+
+CREATE TRIGGER IF NOT EXISTS trg_authors_updated_at_insert
+AFTER INSERT ON authors WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE authors SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_authors_updated_at_update
+AFTER UPDATE ON authors WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE authors SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_books_updated_at_insert
+AFTER INSERT ON books WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE books SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_books_updated_at_update
+AFTER UPDATE ON books WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE books SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_reviews_updated_at_insert
+AFTER INSERT ON reviews WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE reviews SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_reviews_updated_at_update
+AFTER UPDATE ON reviews WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE reviews SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_yearly_sales_updated_at_insert
+AFTER INSERT ON yearly_sales WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE yearly_sales SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_yearly_sales_updated_at_update
+AFTER UPDATE ON yearly_sales WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE yearly_sales SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_users_updated_at_insert
+AFTER INSERT ON users WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE users SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_users_updated_at_update
+AFTER UPDATE ON users WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE users SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
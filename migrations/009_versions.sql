@@ -0,0 +1,8 @@
+-- Control de concurrencia optimista: cada edición (PUT de la API o formulario HTML) incrementa `version`
+-- y solo se aplica si la versión que leyó el cliente sigue siendo la actual (si no, 412).
+-- Los cambios derivados (ventas -> books.sales_count, votos, moderación) no la tocan: no pisan lo que edita nadie.
+
+ALTER TABLE authors ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE books ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE reviews ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE yearly_sales ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -1,29 +1,260 @@
-use rocket::{get, post, serde::json::Json, State};
-use crate::{Db, models::ApiResponse, search::Search};
+use std::collections::HashMap;
+use std::time::Instant;
 
+use rocket::{get, post, put, serde::json::Json, State};
+use bb8_redis::redis::RedisError;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{Db, cache::Cache, models::{ApiResponse, ModerateReview, ModeratedReview, ReviewStatus, Role, UpdateUserRole, User}, repository, search::{BulkFailure, Search}};
+use crate::indexer::{IndexOp, Indexer};
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::repository::sales::SalesDrift;
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::pagination::{Page, PageRequest, Paginated};
+use crate::auth::Admin;
+
+// Máximo de fallos individuales que se devuelven en la respuesta
+const MAX_REPORTED_FAILURES: usize = 50;
+
+#[derive(Serialize)]
+pub struct ReindexReport {
+    pub books_indexed: usize,
+    pub reviews_indexed: usize,
+    pub failed: usize,
+    pub failures: Vec<BulkFailure>,
+    pub elapsed_ms: u64,
+}
+
+// POST /api/admin/reindex
+// Reconstruye los índices `books` y `reviews` de OpenSearch a partir de SQLite usando _bulk
 #[post("/admin/reindex")]
-pub async fn reindex_all(pool: &State<Db>, search: &State<Search>) -> Json<ApiResponse<String>> {
+pub async fn reindex_all(_admin: Admin, pool: &State<Db>, search: &State<Search>) -> ApiResult<ReindexReport> {
     if !search.enabled() {
-        return Json(ApiResponse::error("Search deshabilitado"));
+        return Err(ApiError::unavailable("Search deshabilitado"));
     }
+    let started = Instant::now();
 
-    // Reindex books
-    let mut books_count = 0usize;
-    if let Ok(books) = crate::repository::get_all_books(&pool.0).await {
-        for b in books {
-            let _ = search.index_book(&b).await;
-            books_count += 1;
-        }
+    if let Err(e) = search.ensure_indices().await {
+        error!(error = %e, "Error preparando índices");
+        return Err(ApiError::unavailable("Error preparando índices de OpenSearch"));
     }
 
+    // Reindex books (con rating promedio desnormalizado)
+    let books = repository::get_all_books(&pool.0)
+        .await
+        .map_err(ApiError::db("Error al obtener libros"))?;
+    let ratings: HashMap<i32, f64> = repository::reviews::get_average_ratings(&pool.0)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let books_outcome = match search.bulk_index_books(&books, &ratings).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            error!(error = %e, "Error indexando libros");
+            return Err(ApiError::unavailable("Error indexando libros en OpenSearch"));
+        }
+    };
+
     // Reindex reviews
-    let mut reviews_count = 0usize;
-    if let Ok(revs) = crate::repository::reviews::get_all_reviews(&pool.0).await {
-        for r in revs {
-            let _ = search.index_review_row(&r).await;
-            reviews_count += 1;
+    let reviews = repository::reviews::get_all_reviews(&pool.0)
+        .await
+        .map_err(ApiError::db("Error al obtener reseñas"))?;
+    let reviews_outcome = match search.bulk_index_reviews(&reviews).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            error!(error = %e, "Error indexando reseñas");
+            return Err(ApiError::unavailable("Error indexando reseñas en OpenSearch"));
         }
+    };
+
+    let mut failures = books_outcome.failures;
+    failures.extend(reviews_outcome.failures);
+    let failed = failures.len();
+    failures.truncate(MAX_REPORTED_FAILURES);
+
+    let report = ReindexReport {
+        books_indexed: books_outcome.indexed,
+        reviews_indexed: reviews_outcome.indexed,
+        failed,
+        failures,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+    info!(
+        books = report.books_indexed,
+        reviews = report.reviews_indexed,
+        failed = report.failed,
+        elapsed_ms = report.elapsed_ms,
+        "Reindexación completa"
+    );
+    Ok(Json(ApiResponse::success(report)))
+}
+
+#[derive(Serialize)]
+pub struct SalesRecomputeReport {
+    pub dry_run: bool,
+    pub books_checked: i64,
+    pub drifted: usize,
+    pub corrected: usize,
+    pub drift: Vec<SalesDrift>,
+}
+
+// POST /api/admin/sales/recompute?dry_run=true
+// Recalcula books.sales_count a partir de yearly_sales y reporta qué libros tenían diferencias.
+// Con dry_run=true solo reporta.
+#[post("/admin/sales/recompute?<dry_run>")]
+pub async fn recompute_sales(
+    dry_run: Option<bool>,
+    _admin: Admin,
+    pool: &State<Db>,
+    cache: &Cache,
+    indexer: &State<Indexer>,
+) -> ApiResult<SalesRecomputeReport> {
+    let dry_run = dry_run.unwrap_or(false);
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::RecomputeSales).await;
+
+    let books_checked = repository::books::count_books(&pool.0)
+        .await
+        .map_err(ApiError::db("Error al contar libros"))?;
+    let drift = repository::sales::recompute_sales_counts(&pool.0, !dry_run)
+        .await
+        .map_err(ApiError::db("Error al recalcular ventas"))?;
+
+    let corrected = if dry_run { 0 } else { drift.len() };
+    if corrected > 0 {
+        plan.purge(cache).await;
+        indexer.enqueue_all(drift.iter().map(|d| IndexOp::Book(d.book_id)));
+    }
+    info!(books_checked, drifted = drift.len(), corrected, "Recálculo de ventas");
+
+    Ok(Json(ApiResponse::success(SalesRecomputeReport {
+        dry_run,
+        books_checked,
+        drifted: drift.len(),
+        corrected,
+        drift,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct CachePurge {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub pattern: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CachePurgeReport {
+    pub deleted: usize,
+}
+
+// POST /api/admin/cache/purge
+// Invalidación manual del caché: por tags (`book:17`, `author:3`, `books:list`, ...) y/o por un
+// patrón glob (`reviews:book:*`), que recorre el keyspace con SCAN
+#[post("/admin/cache/purge", data = "<purge>")]
+pub async fn purge_cache(_admin: Admin, purge: Json<CachePurge>, cache: &Cache) -> ApiResult<CachePurgeReport> {
+    if purge.tags.is_empty() && purge.pattern.is_none() {
+        return Err(ApiError::validation("Indicar `tags` o `pattern`"));
+    }
+    let unavailable = |e: RedisError| {
+        warn!(error = %e, "Error purgando el caché");
+        ApiError::unavailable("Caché no disponible")
+    };
+    let mut deleted = 0;
+    for tag in &purge.tags {
+        deleted += cache.invalidate_tag(tag).await.map_err(unavailable)?;
+    }
+    if let Some(pattern) = &purge.pattern {
+        deleted += cache.delete_pattern(pattern).await.map_err(unavailable)?;
+    }
+    info!(tags = ?purge.tags, pattern = purge.pattern.as_deref(), deleted, "Caché purgado a mano");
+    Ok(Json(ApiResponse::success(CachePurgeReport { deleted })))
+}
+
+// GET /api/admin/users
+#[get("/admin/users")]
+pub async fn list_users(_admin: Admin, pool: &State<Db>) -> ApiResult<Vec<User>> {
+    let users = repository::users::list_users(&pool.0)
+        .await
+        .map_err(ApiError::db("Error al obtener usuarios"))?;
+    Ok(Json(ApiResponse::success(users)))
+}
+
+// PUT /api/admin/users/<id>/role  { "role": "admin" | "editor" | "reader" }
+// No permite quitarle el rol al último admin
+#[put("/admin/users/<id>/role", data = "<update>")]
+pub async fn update_user_role(id: i32, admin: Admin, update: Json<UpdateUserRole>, pool: &State<Db>) -> ApiResult<User> {
+    let current = repository::users::get_user_by_id(&pool.0, id)
+        .await
+        .map_err(ApiError::db("Error al obtener usuario"))?
+        .ok_or_else(|| ApiError::not_found("Usuario no encontrado"))?;
+
+    if current.role == Role::Admin && update.role != Role::Admin {
+        let admins = repository::users::count_admins(&pool.0)
+            .await
+            .map_err(ApiError::db("Error al contar admins"))?;
+        if admins <= 1 {
+            return Err(ApiError::Conflict("No se puede quitar el rol al último admin".to_string()));
+        }
+    }
+
+    let user = repository::users::update_user_role(&pool.0, id, update.role)
+        .await
+        .map_err(ApiError::db("Error al actualizar rol"))?
+        .ok_or_else(|| ApiError::not_found("Usuario no encontrado"))?;
+    info!(username = %user.username, role = user.role.as_str(), by = %admin.0.username, "Rol cambiado");
+    Ok(Json(ApiResponse::success(user)))
+}
+
+// GET /api/admin/reviews?status=pending&page=&per_page=
+// Cola de moderación: reseñas en ese estado (por defecto `pending`), las más antiguas primero
+#[get("/admin/reviews?<status>&<page>&<per_page>")]
+pub async fn moderation_queue(
+    status: Option<&str>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    _admin: Admin,
+    pool: &State<Db>,
+) -> Result<Paginated<ModeratedReview>, ApiError> {
+    let status = match status {
+        Some(value) => ReviewStatus::parse(value)
+            .ok_or_else(|| ApiError::validation("`status` inválido; valores permitidos: pending, approved, rejected"))?,
+        None => ReviewStatus::Pending,
+    };
+    let req = PageRequest::new(page, per_page)?;
+    let total = repository::reviews::count_reviews_by_status(&pool.0, status)
+        .await
+        .map_err(ApiError::db("Error al contar reseñas"))?;
+    let reviews = repository::reviews::list_reviews_by_status(&pool.0, status, req.per_page, req.offset())
+        .await
+        .map_err(ApiError::db("Error al obtener reseñas"))?;
+    let params = vec![("status", status.as_str().to_string())];
+    Ok(Page::new(reviews, total, req, "/api/admin/reviews", &params).into())
+}
+
+// PUT /api/admin/reviews/<id>/moderation  { "status": "approved" | "rejected" | "pending", "reason": "..." }
+// Aprobar publica la reseña (listados, promedios, búsqueda); rechazarla la oculta
+#[put("/admin/reviews/<id>/moderation", data = "<decision>")]
+pub async fn moderate_review(
+    id: i32,
+    admin: Admin,
+    decision: Json<ModerateReview>,
+    pool: &State<Db>,
+    cache: &Cache,
+    indexer: &State<Indexer>,
+) -> ApiResult<ModeratedReview> {
+    if decision.reason.as_deref().is_some_and(|r| r.chars().count() > 500) {
+        return Err(ApiError::validation("`reason` admite como máximo 500 caracteres"));
     }
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateReview(id)).await;
+    let review = repository::reviews::set_review_status(&pool.0, id, decision.status, decision.reason.as_deref(), admin.0.id)
+        .await
+        .map_err(ApiError::db("Error al moderar reseña"))?
+        .ok_or_else(|| ApiError::not_found("Reseña no encontrada"))?;
 
-    Json(ApiResponse::success(format!("Reindexados {books_count} libros y {reviews_count} reseñas")))
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Review(id));
+    info!(review_id = id, status = review.status.as_str(), by = %admin.0.username, "Reseña moderada");
+    Ok(Json(ApiResponse::success(review)))
 }
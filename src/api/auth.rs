@@ -0,0 +1,72 @@
+use rocket::{serde::json::Json, State};
+use crate::Db;
+use crate::auth::{self, Reader};
+use crate::models::{ApiResponse, ApiTokenCreated, CreateApiToken, Credentials, Role, User};
+use crate::repository::users as users_repo;
+use crate::api::error::{ApiError, ApiResult};
+use tracing::{error, info};
+
+// POST /api/auth/register
+// Crea una cuenta con rol Reader
+#[post("/auth/register", data = "<credentials>")]
+pub async fn register(credentials: Json<Credentials>, pool: &State<Db>) -> ApiResult<User> {
+    let username = credentials.username.trim();
+    auth::validate_credentials(username, &credentials.password).map_err(ApiError::Validation)?;
+
+    let hash = auth::hash_password(credentials.password.clone()).await.map_err(|e| {
+        error!(error = %e, "Error generando hash");
+        ApiError::internal("No se pudo crear la cuenta")
+    })?;
+    let id = users_repo::create_user(&pool.0, username, &hash, Role::Reader)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db) if db.is_unique_violation() => ApiError::Conflict("Ese nombre de usuario ya existe".to_string()),
+            _ => ApiError::db("Error al crear usuario")(e),
+        })?;
+    let user = users_repo::get_user_by_id(&pool.0, id)
+        .await
+        .map_err(ApiError::db("Error al obtener usuario"))?
+        .ok_or_else(|| ApiError::internal("Usuario recién creado no encontrado"))?;
+
+    info!(username = %user.username, "Usuario registrado");
+    Ok(Json(ApiResponse::success(user)))
+}
+
+// POST /api/auth/tokens
+// Intercambia usuario y contraseña por un token para `Authorization: Bearer <token>`.
+// El token en claro solo aparece en esta respuesta.
+#[post("/auth/tokens", data = "<request>")]
+pub async fn create_token(request: Json<CreateApiToken>, pool: &State<Db>) -> ApiResult<ApiTokenCreated> {
+    let user = auth::authenticate(&pool.0, &request.username, &request.password)
+        .await
+        .map_err(ApiError::db("Error al verificar credenciales"))?
+        .ok_or_else(|| ApiError::unauthorized("Usuario o contraseña incorrectos"))?;
+
+    let name = request.name.as_deref().map(str::trim).filter(|n| !n.is_empty()).unwrap_or("api").to_string();
+    let token = auth::generate_token();
+    let id = users_repo::create_api_token(&pool.0, user.id, &name, &auth::hash_token(&token))
+        .await
+        .map_err(ApiError::db("Error al crear token"))?;
+
+    info!(token_name = %name, username = %user.username, "Token creado");
+    Ok(Json(ApiResponse::success(ApiTokenCreated { id, name, token, user })))
+}
+
+// DELETE /api/auth/tokens/<id>
+// Revoca un token propio
+#[delete("/auth/tokens/<id>")]
+pub async fn delete_token(id: i32, reader: Reader, pool: &State<Db>) -> ApiResult<()> {
+    let deleted = users_repo::delete_api_token(&pool.0, id, reader.0.id)
+        .await
+        .map_err(ApiError::db("Error al revocar token"))?;
+    if !deleted {
+        return Err(ApiError::not_found("Token no encontrado"));
+    }
+    Ok(Json(ApiResponse::success(())))
+}
+
+// GET /api/auth/me
+#[get("/auth/me")]
+pub async fn me(reader: Reader) -> ApiResult<User> {
+    Ok(Json(ApiResponse::success(reader.0)))
+}
@@ -1,30 +1,82 @@
 use rocket::{serde::json::Json, State};
 use rocket::serde::{Serialize, Deserialize}; 
-use rocket_db_pools::sqlx::{self, Row};
+use rocket_db_pools::sqlx::{self, Row, SqlitePool};
 
 use crate::{models::*, repository, Db, cache::Cache};
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::indexer::{IndexOp, Indexer};
+use crate::api::error::{self, ApiError, ApiResult};
+use crate::auth::Editor;
+use crate::uploads::{self, ImageKind};
+use crate::api::pagination::{self, Listing, Page, PageRequest, Paginated};
+use crate::api::books::non_empty;
+use crate::api::conditional::{self, Conditional, IfMatch, Preconditions};
 
-#[get("/authors")]
+// Query de GET /api/authors: paginación, orden y filtros (todo se aplica en SQL)
+#[derive(Debug, FromForm)]
+pub struct AuthorListQuery {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    sort: Option<String>,
+    order: Option<String>,
+    q: Option<String>,
+    country: Option<String>,
+    min_rating: Option<f64>,
+    min_sales: Option<i64>,
+}
+
+impl AuthorListQuery {
+    pub(crate) fn parse(self) -> Result<Listing<AuthorFilter, AuthorSort>, ApiError> {
+        let page = PageRequest::new(self.page, self.per_page)?;
+        let sort = match self.sort.as_deref() {
+            None => AuthorSort::Name,
+            Some(value) => AuthorSort::parse(value).ok_or_else(|| {
+                let valid: Vec<_> = AuthorSort::ALL.iter().map(AuthorSort::as_str).collect();
+                ApiError::Validation(format!("`sort` inválido; valores permitidos: {}", valid.join(", ")))
+            })?,
+        };
+        let order = match self.order.as_deref() {
+            None => SortOrder::Asc,
+            Some(value) => SortOrder::parse(value).ok_or_else(|| ApiError::validation("`order` debe ser asc o desc"))?,
+        };
+
+        let filter = AuthorFilter {
+            q: non_empty(self.q),
+            country: non_empty(self.country),
+            min_rating: self.min_rating,
+            min_sales: self.min_sales,
+        };
+
+        let mut params = vec![("sort", sort.as_str().to_string()), ("order", order.as_str().to_string())];
+        if let Some(q) = &filter.q { params.push(("q", q.clone())); }
+        if let Some(v) = &filter.country { params.push(("country", v.clone())); }
+        if let Some(v) = filter.min_rating { params.push(("min_rating", v.to_string())); }
+        if let Some(v) = filter.min_sales { params.push(("min_sales", v.to_string())); }
+
+        Ok(Listing { filter, sort, order, page, params })
+    }
+}
+
+// GET /api/authors?page=&per_page=&sort=&order=&q=&country=&min_rating=&min_sales=
+#[get("/authors?<query..>")]
 pub async fn get_authors(
+    query: AuthorListQuery,
     pool: &State<Db>,
     cache: &Cache
-) -> Json<ApiResponse<Vec<Author>>> {
-    println!("🔍 Entrando a get_authors");
-    let cache_key = Cache::KEY_AUTHORS_LIST;
+) -> Result<Paginated<Author>, ApiError> {
+    let Listing { filter, sort, order, page: req, params } = query.parse()?;
+    let cache_key = format!("{}{}", Cache::KEY_AUTHORS_LIST_PREFIX, pagination::cache_suffix(&params, req));
     
-    if let Ok(cached_authors) = cache.get::<Vec<Author>>(cache_key).await {
-        println!("✅ Datos de autores obtenidos del CACHÉ");
-        return Json(ApiResponse::success(cached_authors));
-    }
-    println!("🔄 Obteniendo datos de autores de la BASE DE DATOS");
-    match repository::get_all_authors(&pool.0).await {
-        Ok(authors) => {
-            let _ = cache.set(cache_key, &authors, Some(Cache::TTL_5_MIN)).await;
-            println!("💾 Datos de autores guardados en CACHÉ");
-            Json(ApiResponse::success(authors))
-        },
-        Err(_) => Json(ApiResponse::<Vec<Author>>::error("Error al obtener autores")),
-    }
+    let db = pool.0.clone();
+    let page = cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, |_| Vec::new(), move || async move {
+            let (authors, total) = repository::authors::list_authors(&db, &filter, sort, order, req.per_page, req.offset())
+                .await
+                .map_err(ApiError::db("Error al obtener autores"))?;
+            Ok::<_, ApiError>(Page::new(authors, total, req, "/api/authors", &params))
+        })
+        .await?;
+    Ok(page.into())
 }
 
 
@@ -34,61 +86,84 @@ pub async fn get_authors(
 pub async fn get_author(
     id: i32, 
     pool: &State<Db>,
-    cache: &Cache
-) -> Json<ApiResponse<Author>> {
-    println!("🔍 Entrando a get_author para id: {}", id);
+    cache: &Cache,
+    preconditions: Preconditions
+) -> Result<Conditional, ApiError> {
     let cache_key = format!("{}{}", Cache::KEY_AUTHOR_PREFIX, id);
     
-    if let Ok(cached_author) = cache.get::<Author>(&cache_key).await {
-        println!("✅ Datos del autor {} obtenidos del CACHÉ", id);
-        return Json(ApiResponse::success(cached_author));
-    }
-    println!("🔄 Obteniendo datos del autor {} de la BASE DE DATOS", id);
-    match repository::get_author_by_id(&pool.0, id).await {
-        Ok(Some(author)) => {
-            let _ = cache.set(&cache_key, &author, Some(Cache::TTL_5_MIN)).await;
-            println!("💾 Datos del autor {} guardados en CACHÉ", id);
-            Json(ApiResponse::success(author))
-        },
-        Ok(None) => {
-            println!("❌ Autor {} no encontrado", id);
-            Json(ApiResponse::<Author>::error("Autor no encontrado"))
-        },
-        Err(_) => {
-            println!("❌ Error al obtener autor {}", id);
-            Json(ApiResponse::<Author>::error("Error al obtener autor"))
-        },
-    }
+    let db = pool.0.clone();
+    let author = cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, move |_| vec![Cache::tag_author(id)], move || async move {
+            repository::get_author_by_id(&db, id)
+                .await
+                .map_err(ApiError::db("Error al obtener autor"))?
+                .ok_or_else(|| ApiError::not_found("Autor no encontrado"))
+        })
+        .await?;
+    let modified = conditional::last_modified([author.updated_at.as_deref()]);
+    let version = author.version;
+    preconditions.respond(author, Some(version), modified)
 }
 
 
 #[post("/authors", data = "<author>")]
-pub async fn create_author(author: Json<CreateAuthor>, pool: &State<Db>) -> Json<ApiResponse<i32>> {
-    match repository::create_author(&pool.0, &author).await {
-        Ok(id) => Json(ApiResponse::success(id)),
-        Err(_) => Json(ApiResponse::<i32>::error("Error al crear autor")),
-    }
+pub async fn create_author(_editor: Editor, author: Json<CreateAuthor>, pool: &State<Db>, cache: &Cache) -> ApiResult<i32> {
+    error::validate(&pool.0, &*author).await?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::CreateAuthor).await;
+    let id = repository::create_author(&pool.0, &author)
+        .await
+        .map_err(ApiError::db("Error al crear autor"))?;
+
+    plan.purge(cache).await;
+    Ok(Json(ApiResponse::success(id)))
 }
 
+// 412 si el autor cambió desde que el cliente leyó la versión de `If-Match` / `version`
 #[put("/authors/<id>", data = "<author_update>")]
-pub async fn update_author(id: i32, author_update: Json<UpdateAuthor>, pool: &State<Db>) -> Json<ApiResponse<Author>> {
-    match repository::update_author(&pool.0, id, &author_update).await {
-        Ok(Some(author)) => Json(ApiResponse::success(author)),
-        Ok(None) => Json(ApiResponse::<Author>::error("Autor no encontrado")),
-        Err(_) => Json(ApiResponse::<Author>::error("Error al actualizar autor")),
-    }
+pub async fn update_author(id: i32, _editor: Editor, if_match: IfMatch, author_update: Json<UpdateAuthor>, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> Result<Conditional, ApiError> {
+    let mut author_update = author_update.into_inner();
+    author_update.version = if_match.version(author_update.version, &pool.0, "authors", id).await?;
+    error::validate(&pool.0, &author_update).await?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateAuthor(id)).await;
+    let outcome = repository::update_author(&pool.0, id, &author_update)
+        .await
+        .map_err(ApiError::db("Error al actualizar autor"))?;
+    let author = error::updated(outcome, "Autor no encontrado")?;
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Author(id));
+    let version = author.version;
+    conditional::updated(ApiResponse::success(author), version)
 }
 
 #[delete("/authors/<id>")]
-pub async fn delete_author(id: i32, pool: &State<Db>) -> Json<ApiResponse<()>> {
-    match repository::delete_author(&pool.0, id).await {
-        Ok(_) => Json(ApiResponse::success(())),
-        Err(_) => Json(ApiResponse::<()>::error("Error al eliminar autor")),
+pub async fn delete_author(id: i32, _editor: Editor, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<()> {
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::DeleteAuthor(id)).await;
+    // Los libros se borran en cascada: hay que capturarlos antes para sacarlos del índice
+    // y para borrar sus portadas del disco
+    let book_ids = repository::books::get_book_ids_by_author(&pool.0, id).await.unwrap_or_default();
+    let covers = repository::books::get_covers_by_author(&pool.0, id).await.unwrap_or_default();
+    let photo = repository::authors::get_author_photo(&pool.0, id).await.ok().flatten().flatten();
+    let deleted = repository::delete_author(&pool.0, id)
+        .await
+        .map_err(ApiError::db("Error al eliminar autor"))?;
+    if !deleted {
+        return Err(ApiError::not_found("Autor no encontrado"));
+    }
+
+    plan.purge(cache).await;
+    indexer.enqueue_all(book_ids.into_iter().map(IndexOp::Book));
+    if let Some(photo) = photo {
+        uploads::remove_if_unused(&pool.0, ImageKind::AuthorPhoto, &photo).await;
     }
+    for cover in covers {
+        uploads::remove_if_unused(&pool.0, ImageKind::BookCover, &cover).await;
+    }
+    Ok(Json(ApiResponse::success(())))
 }
 
 /// DTO liviano para la lista de libros del autor
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct BookSummary {
     pub id: i32,
@@ -97,7 +172,7 @@ pub struct BookSummary {
 }
 
 /// Respuesta compuesta para el Show de autor
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct AuthorDetails {
     pub author: Author,
@@ -111,26 +186,35 @@ pub struct AuthorDetails {
 pub async fn get_author_details(
     id: i32, 
     pool: &State<Db>,
-    cache: &Cache
-) -> Json<ApiResponse<AuthorDetails>> {
-    println!("🔍 Entrando a get_author_details para id: {}", id);
+    cache: &Cache,
+    preconditions: Preconditions
+) -> Result<Conditional, ApiError> {
     let cache_key = format!("{}{}", Cache::KEY_AUTHOR_DETAILS_PREFIX, id);
     
-    if let Ok(cached_details) = cache.get::<AuthorDetails>(&cache_key).await {
-        println!("✅ Detalles del autor {} obtenidos del CACHÉ", id);
-        return Json(ApiResponse::success(cached_details));
-    }
-    println!("🔄 Obteniendo detalles del autor {} de la BASE DE DATOS", id);
+    // Lista los títulos de sus libros: se invalida también con los cambios de cada libro
+    let tags = move |details: &AuthorDetails| {
+        std::iter::once(Cache::tag_author(id))
+            .chain(details.books.iter().map(|b| Cache::tag_book(b.id)))
+            .collect()
+    };
+    let author_details = cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, tags, move || load_author_details(pool.0.clone(), id))
+        .await?;
     
+    // Solo ETag: la lista de libros cambia con altas y bajas que no tocan el `updated_at` del autor
+    let version = author_details.author.version;
+    preconditions.respond(author_details, Some(version), None)
+}
+
+// Autor y sus libros desde la BD (recibe el pool por valor para poder refrescar el caché en segundo plano)
+async fn load_author_details(pool: SqlitePool, id: i32) -> Result<AuthorDetails, ApiError> {
     // 1) Autor
-    let author_opt = repository::get_author_by_id(&pool.0, id)
+    let author_opt = repository::get_author_by_id(&pool, id)
         .await
-        .ok()
-        .flatten();
+        .map_err(ApiError::db("Error al obtener autor"))?;
 
     let Some(author) = author_opt else {
-        println!("❌ Autor {} no encontrado", id);
-        return Json(ApiResponse::<AuthorDetails>::error("Autor no encontrado"));
+        return Err(ApiError::not_found("Autor no encontrado"));
     };
 
     // 2) Libros del autor
@@ -146,9 +230,9 @@ pub async fn get_author_details(
         "#,
     )
     .bind(id)
-    .fetch_all(&pool.0)
+    .fetch_all(&pool)
     .await
-    .unwrap_or_default();
+    .map_err(ApiError::db("Error al obtener libros del autor"))?;
 
     let books = rows
         .into_iter()
@@ -159,9 +243,5 @@ pub async fn get_author_details(
         })
         .collect();
     
-    let author_details = AuthorDetails { author, books };
-    let _ = cache.set(&cache_key, &author_details, Some(Cache::TTL_5_MIN)).await;
-    println!("💾 Detalles del autor {} guardados en CACHÉ", id);
-    
-    Json(ApiResponse::success(author_details))
+    Ok(AuthorDetails { author, books })
 }
\ No newline at end of file
@@ -3,35 +3,106 @@ use serde::Serialize;
 
 use crate::{Db, repository};
 use crate::repository::books as books_repo;
-use crate::models::{ApiResponse, BookWithAuthor, CreateBook, UpdateBook};
+use crate::models::{ApiResponse, BookFilter, BookSort, BookWithAuthor, CreateBook, SortOrder, UpdateBook};
 use crate::cache::Cache;
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::indexer::{IndexOp, Indexer};
+use crate::api::error::{self, ApiError, ApiResult};
+use crate::auth::Editor;
+use crate::uploads::{self, ImageKind};
+use crate::api::pagination::{self, Listing, Page, PageRequest};
+use crate::api::conditional::{self, Conditional, IfMatch, Preconditions};
 
 
-// los voy a deja con los logs para despues poder demostrar que ocupa cahce
-#[get("/books")]
+// Query de GET /api/books: paginación, orden y filtros (todo se aplica en SQL)
+#[derive(Debug, FromForm)]
+pub struct BookListQuery {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    sort: Option<String>,
+    order: Option<String>,
+    q: Option<String>,
+    author_id: Option<i32>,
+    country: Option<String>,
+    year_from: Option<i32>,
+    year_to: Option<i32>,
+    min_rating: Option<f64>,
+    min_sales: Option<i64>,
+}
+
+impl BookListQuery {
+    // Valida la query (422 si sort/order/página no son válidos)
+    pub(crate) fn parse(self) -> Result<Listing<BookFilter, BookSort>, ApiError> {
+        let page = PageRequest::new(self.page, self.per_page)?;
+        let sort = match self.sort.as_deref() {
+            None => BookSort::Title,
+            Some(value) => BookSort::parse(value).ok_or_else(|| {
+                let valid: Vec<_> = BookSort::ALL.iter().map(BookSort::as_str).collect();
+                ApiError::Validation(format!("`sort` inválido; valores permitidos: {}", valid.join(", ")))
+            })?,
+        };
+        let order = match self.order.as_deref() {
+            None => SortOrder::Asc,
+            Some(value) => SortOrder::parse(value).ok_or_else(|| ApiError::validation("`order` debe ser asc o desc"))?,
+        };
+        if let (Some(from), Some(to)) = (self.year_from, self.year_to) {
+            if from > to {
+                return Err(ApiError::validation("`year_from` no puede ser mayor que `year_to`"));
+            }
+        }
+
+        let filter = BookFilter {
+            q: non_empty(self.q),
+            author_id: self.author_id,
+            country: non_empty(self.country),
+            year_from: self.year_from,
+            year_to: self.year_to,
+            min_rating: self.min_rating,
+            min_sales: self.min_sales,
+        };
+
+        let mut params = vec![("sort", sort.as_str().to_string()), ("order", order.as_str().to_string())];
+        if let Some(q) = &filter.q { params.push(("q", q.clone())); }
+        if let Some(v) = filter.author_id { params.push(("author_id", v.to_string())); }
+        if let Some(v) = &filter.country { params.push(("country", v.clone())); }
+        if let Some(v) = filter.year_from { params.push(("year_from", v.to_string())); }
+        if let Some(v) = filter.year_to { params.push(("year_to", v.to_string())); }
+        if let Some(v) = filter.min_rating { params.push(("min_rating", v.to_string())); }
+        if let Some(v) = filter.min_sales { params.push(("min_sales", v.to_string())); }
+
+        Ok(Listing { filter, sort, order, page, params })
+    }
+}
+
+pub(crate) fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// GET /api/books?page=&per_page=&sort=&order=&q=&author_id=&country=&year_from=&year_to=&min_rating=&min_sales=
+#[get("/books?<query..>")]
 pub async fn get_books(
+    query: BookListQuery,
     pool: &State<Db>,
-    cache: &Cache
-) -> Json<ApiResponse<Vec<BookWithAuthor>>> {
-    println!("🔍 Entrando a get_books");
-    // Usar constante desde cache.rs
-    let cache_key = Cache::KEY_BOOKS_LIST;
+    cache: &Cache,
+    preconditions: Preconditions
+) -> Result<Conditional, ApiError> {
+    let Listing { filter, sort, order, page: req, params } = query.parse()?;
+    // La clave incluye filtros, orden y página
+    let cache_key = format!("{}{}", Cache::KEY_BOOKS_LIST_PREFIX, pagination::cache_suffix(&params, req));
     
-    // Intentar obtener del caché primero
-    if let Ok(cached_books) = cache.get::<Vec<BookWithAuthor>>(cache_key).await {
-        println!("✅ Datos obtenidos del CACHÉ");  // ← Log de caché
-        return Json(ApiResponse::success(cached_books));
-    }
-    println!("🔄 Obteniendo datos de la BASE DE DATOS");  // ← Log de BD
-    match repository::get_all_books(&pool.0).await {
-        Ok(books) => {
-            // Almacenar en caché por 5 minutos usando constante TTL
-            let _ = cache.set(cache_key, &books, Some(Cache::TTL_5_MIN)).await;
-            println!("💾 Datos guardados en CACHÉ");  // ← Log de guardado
-            Json(ApiResponse::success(books))
-        },
-        Err(_) => Json(ApiResponse::<Vec<BookWithAuthor>>::error("Error al obtener libros")),
-    }
+    // Caché de 5 minutos usando constante TTL (un solo cálculo aunque lleguen muchos requests juntos)
+    let db = pool.0.clone();
+    let page = cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, |_| Vec::new(), move || async move {
+            let (books, total) = books_repo::list_books(&db, &filter, sort, order, req.per_page, req.offset())
+                .await
+                .map_err(ApiError::db("Error al obtener libros"))?;
+            Ok::<_, ApiError>(Page::new(books, total, req, "/api/books", &params))
+        })
+        .await?;
+    // Sin Last-Modified: un libro borrado no cambia el máximo `updated_at` de la página
+    let link = page.link_header();
+    Ok(preconditions.respond(page, None, None)?.with_header(link))
 }
 
 
@@ -39,64 +110,85 @@ pub async fn get_books(
 pub async fn get_book(
     id: i32, 
     pool: &State<Db>,
-    cache: &Cache
-) -> Json<ApiResponse<BookWithAuthor>> {
-    println!("🔍 Entrando a get_book para id: {}", id);
+    cache: &Cache,
+    preconditions: Preconditions
+) -> Result<Conditional, ApiError> {
     let cache_key = format!("{}{}", Cache::KEY_BOOK_PREFIX, id);
     
-    if let Ok(cached_book) = cache.get::<BookWithAuthor>(&cache_key).await {
-        println!("✅ Datos del libro {} obtenidos del CACHÉ", id);
-        return Json(ApiResponse::success(cached_book));
-    }
-    println!("🔄 Obteniendo datos del libro {} de la BASE DE DATOS", id);
-    match repository::get_book_by_id(&pool.0, id).await {
-        Ok(Some(book)) => {
-            let _ = cache.set(&cache_key, &book, Some(Cache::TTL_5_MIN)).await;
-            println!("💾 Datos del libro {} guardados en CACHÉ", id);
-            Json(ApiResponse::success(book))
-        },
-        Ok(None) => {
-            println!("❌ Libro {} no encontrado", id);
-            Json(ApiResponse::<BookWithAuthor>::error("Libro no encontrado"))
-        },
-        Err(_) => {
-            println!("❌ Error al obtener libro {}", id);
-            Json(ApiResponse::<BookWithAuthor>::error("Error al obtener libro"))
-        },
-    }
+    // Embebe al autor: se invalida también con sus cambios
+    let tags = move |book: &BookWithAuthor| {
+        std::iter::once(Cache::tag_book(id)).chain(book.author.id.map(Cache::tag_author)).collect()
+    };
+    let db = pool.0.clone();
+    let book = cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, tags, move || async move {
+            repository::get_book_by_id(&db, id)
+                .await
+                .map_err(ApiError::db("Error al obtener libro"))?
+                .ok_or_else(|| ApiError::not_found("Libro no encontrado"))
+        })
+        .await?;
+    let modified = conditional::last_modified([book.updated_at.as_deref(), book.author.updated_at.as_deref()]);
+    let version = book.version;
+    preconditions.respond(book, Some(version), modified)
 }
 
-// Para los siguietes enpoints no se uso cache por que el tiempo de permanencia en el cache es muy bajo
-// Esto evita complejidad innecesaria (hablado con el profesor)
+// Los endpoints de escritura invalidan las claves afectadas una vez confirmada la escritura
+// y encolan la sincronización con OpenSearch (nunca bloquea ni hace fallar la escritura)
 
 #[post("/books", data = "<book>")]
-pub async fn create_book(book: Json<CreateBook>, pool: &State<Db>) -> Json<ApiResponse<i32>> {
-    match repository::create_book(&pool.0, &book).await {
-        Ok(id) => Json(ApiResponse::success(id)),
-        Err(_) => Json(ApiResponse::<i32>::error("Error al crear libro")),
-    }
+pub async fn create_book(_editor: Editor, book: Json<CreateBook>, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<i32> {
+    error::validate(&pool.0, &*book).await?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::CreateBook { author_id: book.author_id }).await;
+    let id = repository::create_book(&pool.0, &book)
+        .await
+        .map_err(ApiError::db("Error al crear libro"))?;
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Book(id));
+    Ok(Json(ApiResponse::success(id)))
 }
 
+// Con `If-Match` (el ETag del GET) o `version` en el cuerpo solo se guarda si nadie lo editó antes; si no, 412.
+// La respuesta trae el ETag nuevo
 #[put("/books/<id>", data = "<book_update>")]
-pub async fn update_book(id: i32, book_update: Json<UpdateBook>, pool: &State<Db>) -> Json<ApiResponse<BookWithAuthor>> {
-    match repository::update_book(&pool.0, id, &book_update).await {
-        Ok(Some(book)) => Json(ApiResponse::success(book)),
-        Ok(None) => Json(ApiResponse::<BookWithAuthor>::error("Libro no encontrado")),
-        Err(_) => Json(ApiResponse::<BookWithAuthor>::error("Error al actualizar libro")),
-    }
+pub async fn update_book(id: i32, _editor: Editor, if_match: IfMatch, book_update: Json<UpdateBook>, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> Result<Conditional, ApiError> {
+    let mut book_update = book_update.into_inner();
+    book_update.version = if_match.version(book_update.version, &pool.0, "books", id).await?;
+    error::validate(&pool.0, &book_update).await?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateBook { id, new_author_id: book_update.author_id }).await;
+    let outcome = repository::update_book(&pool.0, id, &book_update)
+        .await
+        .map_err(ApiError::db("Error al actualizar libro"))?;
+    let book = error::updated(outcome, "Libro no encontrado")?;
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Book(id));
+    let version = book.version;
+    conditional::updated(ApiResponse::success(book), version)
 }
 
 #[delete("/books/<id>")]
-pub async fn delete_book(id: i32, pool: &State<Db>) -> Json<ApiResponse<()>> {
-    match repository::delete_book(&pool.0, id).await {
-        Ok(true) => Json(ApiResponse::success(())),
-        Ok(false) => Json(ApiResponse::<()>::error("Libro no encontrado")),
-        Err(_) => Json(ApiResponse::<()>::error("Error al eliminar libro")),
+pub async fn delete_book(id: i32, _editor: Editor, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<()> {
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::DeleteBook(id)).await;
+    let cover = books_repo::get_book_cover(&pool.0, id).await.ok().flatten().flatten();
+    let deleted = repository::delete_book(&pool.0, id)
+        .await
+        .map_err(ApiError::db("Error al eliminar libro"))?;
+    if !deleted {
+        return Err(ApiError::not_found("Libro no encontrado"));
     }
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Book(id));
+    if let Some(cover) = cover {
+        uploads::remove_if_unused(&pool.0, ImageKind::BookCover, &cover).await;
+    }
+    Ok(Json(ApiResponse::success(())))
 }
 
 #[derive(Serialize)]
-struct Paged<T> {
+pub struct Paged<T> {
     items: Vec<T>,
     total: i64,
     page: i64,
@@ -110,18 +202,18 @@ pub async fn search_books(
     page: Option<i64>,
     per_page: Option<i64>,
     pool: &State<Db>,
-) -> Json<ApiResponse<Paged<BookWithAuthor>>> {
+) -> ApiResult<Paged<BookWithAuthor>> {
     let page = page.unwrap_or(1);
     let per_page = per_page.unwrap_or(10);
 
-    match books_repo::search_books_by_description(&pool.0, q, page, per_page).await {
-        Ok((items, total)) => Json(ApiResponse::success(Paged {
-            items,
-            total,
-            page,
-            per_page,
-            query: q.to_string(),
-        })),
-        Err(_) => Json(ApiResponse::error("Error en la búsqueda")),
-    }
+    let (items, total) = books_repo::search_books_by_description(&pool.0, q, page, per_page)
+        .await
+        .map_err(ApiError::db("Error en la búsqueda"))?;
+    Ok(Json(ApiResponse::success(Paged {
+        items,
+        total,
+        page,
+        per_page,
+        query: q.to_string(),
+    })))
 }
\ No newline at end of file
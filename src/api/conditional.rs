@@ -0,0 +1,321 @@
+use std::io::Cursor;
+
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use rocket_db_pools::sqlx::SqlitePool;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::PrimitiveDateTime;
+
+use crate::api::error::ApiError;
+use crate::models::ApiResponse;
+use crate::repository;
+
+// Peticiones condicionales (RFC 9110 §13) para los GET JSON más pedidos:
+// ETag = hash del cuerpo (cambia con cualquier dato que se serialice, aunque sea en el mismo segundo);
+// en los recursos editables va precedido de su `version` (`"3-9f2c..."`), que es lo que mira `If-Match`;
+// Last-Modified = `updated_at` del recurso, solo donde no depende de filas que pueden borrarse
+// (en listados un borrado no mueve el máximo y `If-Modified-Since` daría un 304 falso).
+
+// Navegadores y proxies (Traefik) reutilizan la respuesta 30 s y después revalidan con el ETag
+pub const CACHE_CONTROL: &str = "public, max-age=30, must-revalidate";
+
+// `updated_at` tal como lo guarda SQLite (CURRENT_TIMESTAMP, UTC)
+const DB_TIMESTAMP: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+// IMF-fixdate: `Sun, 06 Nov 1994 08:49:37 GMT`
+const HTTP_DATE: &[FormatItem<'static>] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+// Cabeceras condicionales del request (request guard, nunca falla)
+#[derive(Debug)]
+pub struct Preconditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<PrimitiveDateTime>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Preconditions {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        Outcome::Success(Self {
+            if_none_match: headers.get_one("If-None-Match").map(str::to_string),
+            // Una fecha que no se entiende se ignora (RFC 9110 §13.1.3)
+            if_modified_since: headers
+                .get_one("If-Modified-Since")
+                .and_then(|value| PrimitiveDateTime::parse(value.trim(), HTTP_DATE).ok()),
+        })
+    }
+}
+
+impl Preconditions {
+    // Arma la respuesta de un GET: 304 sin cuerpo si el cliente ya tiene esta versión, si no 200
+    // Argumentos:
+    // - data: T - Lo que iría en `data` del sobre ApiResponse
+    // - version: Option<i32> - `version` del registro si se edita con PUT (va en el ETag para `If-Match`); None en listados
+    // - last_modified: Option<PrimitiveDateTime> - Ver `last_modified`; None si el recurso no lo admite
+    // Retorna: Result<Conditional, ApiError> - 500 solo si el cuerpo no se puede serializar
+    pub fn respond<T: Serialize>(&self, data: T, version: Option<i32>, last_modified: Option<PrimitiveDateTime>) -> Result<Conditional, ApiError> {
+        let body = serde_json::to_string(&ApiResponse::success(data))
+            .map_err(|_| ApiError::internal("Error al serializar la respuesta"))?;
+        let etag = etag(&body, version);
+
+        // If-None-Match manda; If-Modified-Since solo se mira si el cliente no envió ETags
+        let fresh = match (&self.if_none_match, self.if_modified_since) {
+            (Some(candidates), _) => etag_matches(candidates, &etag),
+            (None, Some(since)) => last_modified.is_some_and(|modified| modified <= since),
+            (None, None) => false,
+        };
+
+        Ok(Conditional {
+            body: (!fresh).then_some(body),
+            etag,
+            last_modified: last_modified.and_then(|modified| modified.format(HTTP_DATE).ok()),
+            cache_control: Some(CACHE_CONTROL),
+            headers: Vec::new(),
+        })
+    }
+}
+
+// Respuesta de un PUT exitoso: el registro nuevo y su ETag, listo para el próximo `If-Match`
+// Argumentos: response: ApiResponse<T> - Sobre con el registro actualizado; version: i32 - Su `version` nueva
+// Retorna: Result<Conditional, ApiError> - 500 solo si el cuerpo no se puede serializar
+pub fn updated<T: Serialize>(response: ApiResponse<T>, version: i32) -> Result<Conditional, ApiError> {
+    let body = serde_json::to_string(&response).map_err(|_| ApiError::internal("Error al serializar la respuesta"))?;
+    Ok(Conditional {
+        etag: etag(&body, Some(version)),
+        body: Some(body),
+        last_modified: None,
+        cache_control: None,
+        headers: Vec::new(),
+    })
+}
+
+// Fecha de modificación de un recurso compuesto: la más reciente de sus `updated_at`
+// Argumentos: stamps - `updated_at` de cada fila que aparece en la respuesta (None = sin dato)
+// Retorna: Option<PrimitiveDateTime> - None si alguna falta o no se puede leer (no se envía Last-Modified)
+pub fn last_modified<'a>(stamps: impl IntoIterator<Item = Option<&'a str>>) -> Option<PrimitiveDateTime> {
+    stamps
+        .into_iter()
+        .map(|stamp| stamp.and_then(|s| PrimitiveDateTime::parse(s, DB_TIMESTAMP).ok()))
+        .try_fold(None, |latest: Option<PrimitiveDateTime>, stamp| Some(latest.max(Some(stamp?))))
+        .flatten()
+}
+
+// ETag fuerte: primeros 128 bits del SHA-256 del cuerpo, con la `version` delante si la hay
+fn etag(body: &str, version: Option<i32>) -> String {
+    let hash: String = Sha256::digest(body.as_bytes())[..16].iter().map(|b| format!("{b:02x}")).collect();
+    match version {
+        Some(version) => format!("\"{version}-{hash}\""),
+        None => format!("\"{hash}\""),
+    }
+}
+
+// If-None-Match usa comparación débil: `W/"x"` equivale a `"x"` (p.ej. si un proxy comprimió la respuesta)
+fn etag_matches(candidates: &str, etag: &str) -> bool {
+    candidates
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+// `If-Match` de los PUT: el ETag que devolvió el GET o el PUT anterior (`"3-9f2c..."`) o solo la versión (`"3"`).
+// Se compara la `version`, no el hash: ventas o votos nuevos cambian el cuerpo pero no pisan la edición de nadie
+#[derive(Debug)]
+pub struct IfMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Self(req.headers().get_one("If-Match").map(str::to_string)))
+    }
+}
+
+impl IfMatch {
+    // Versión que tiene que coincidir para aplicar el UPDATE
+    // Argumentos:
+    // - body_version: Option<i32> - `version` del cuerpo, se usa si no llega el header
+    // - pool, table, id - Registro a editar: solo se lee si el header lista varias versiones distintas
+    // Retorna: Result<Option<i32>, ApiError> - None sin versión o con `*` (edición sin control);
+    //          412 si ningún tag es de este registro o ninguna versión es la actual
+    pub async fn version(&self, body_version: Option<i32>, pool: &SqlitePool, table: &'static str, id: i32) -> Result<Option<i32>, ApiError> {
+        let Some(value) = self.0.as_deref() else {
+            return Ok(body_version);
+        };
+        let Some(mut versions) = tag_versions(value) else {
+            return Ok(None);
+        };
+        versions.sort_unstable();
+        versions.dedup();
+        match versions[..] {
+            [] => Err(ApiError::PreconditionFailed(format!(
+                "`If-Match` debe ser el ETag del registro (p.ej. \"3-9f2c...\") o su versión (\"3\"), no `{value}`"
+            ))),
+            [version] => Ok(Some(version)),
+            // Varias: vale la que sea la actual (si ninguna lo es, el UPDATE con la primera da 412)
+            _ => {
+                let current = repository::current_version(pool, table, id)
+                    .await
+                    .map_err(ApiError::db("Error al leer la versión del registro"))?;
+                Ok(current.filter(|v| versions.contains(v)).or(Some(versions[0])))
+            }
+        }
+    }
+}
+
+// Versiones de los entity tags de `If-Match` (`"3-9f2c..."`, `"3"`, `W/"3-..."`, separados por comas)
+// Retorna: Option<Vec<i32>> - None con `*`; los tags que no son de un recurso versionado se descartan
+fn tag_versions(header: &str) -> Option<Vec<i32>> {
+    let mut versions = Vec::new();
+    for candidate in header.split(',').map(str::trim) {
+        if candidate == "*" {
+            return None;
+        }
+        let tag = candidate.trim_start_matches("W/").trim_matches('"');
+        if let Ok(version) = tag.split('-').next().unwrap_or_default().parse() {
+            versions.push(version);
+        }
+    }
+    Some(versions)
+}
+
+// Respuesta de un GET condicional: JSON con ETag/Last-Modified/Cache-Control, o 304 con las mismas cabeceras
+pub struct Conditional {
+    body: Option<String>,
+    etag: String,
+    last_modified: Option<String>,
+    cache_control: Option<&'static str>,
+    headers: Vec<Header<'static>>,
+}
+
+impl Conditional {
+    // Cabecera adicional (p.ej. `Link` de los listados paginados)
+    pub fn with_header(mut self, header: Header<'static>) -> Self {
+        self.headers.push(header);
+        self
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Conditional {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response.raw_header("ETag", self.etag);
+        if let Some(cache_control) = self.cache_control {
+            response.raw_header("Cache-Control", cache_control);
+        }
+        if let Some(last_modified) = self.last_modified {
+            response.raw_header("Last-Modified", last_modified);
+        }
+        for header in self.headers {
+            response.header(header);
+        }
+        match self.body {
+            Some(body) => response.header(ContentType::JSON).sized_body(body.len(), Cursor::new(body)),
+            None => response.status(Status::NotModified),
+        };
+        response.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket_db_pools::sqlx::{self, sqlite::SqlitePoolOptions};
+    use time::macros::datetime;
+
+    fn preconditions(if_none_match: Option<&str>, if_modified_since: Option<PrimitiveDateTime>) -> Preconditions {
+        Preconditions { if_none_match: if_none_match.map(str::to_string), if_modified_since }
+    }
+
+    async fn pool_with_version(version: i32) -> SqlitePool {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE authors (id INTEGER PRIMARY KEY, version INTEGER NOT NULL)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO authors (id, version) VALUES (1, ?)").bind(version).execute(&pool).await.unwrap();
+        pool
+    }
+
+    #[test]
+    fn etag_carries_version() {
+        let plain = etag("{}", None);
+        let versioned = etag("{}", Some(3));
+        assert_eq!(plain.len(), 34);
+        assert_eq!(versioned, format!("\"3-{}", &plain[1..]));
+    }
+
+    #[test]
+    fn etag_matching_is_weak() {
+        let tag = etag("{}", Some(3));
+        assert!(etag_matches(&tag, &tag));
+        assert!(etag_matches(&format!("W/{tag}"), &tag));
+        assert!(etag_matches(&format!("\"otro\", {tag}"), &tag));
+        assert!(etag_matches("*", &tag));
+        assert!(!etag_matches("\"otro\"", &tag));
+        assert!(!etag_matches("", &tag));
+    }
+
+    #[test]
+    fn respond_honours_if_none_match_before_if_modified_since() {
+        let modified = datetime!(2024-05-01 10:00:00);
+        let first = preconditions(None, None).respond("x", Some(2), Some(modified)).unwrap();
+        assert!(first.body.is_some());
+        assert_eq!(first.last_modified.as_deref(), Some("Wed, 01 May 2024 10:00:00 GMT"));
+
+        let cached = preconditions(Some(&first.etag), None).respond("x", Some(2), Some(modified)).unwrap();
+        assert!(cached.body.is_none());
+
+        let stale_tag = preconditions(Some("\"viejo\""), Some(modified)).respond("x", Some(2), Some(modified)).unwrap();
+        assert!(stale_tag.body.is_some());
+
+        let since = preconditions(None, Some(modified)).respond("x", Some(2), Some(modified)).unwrap();
+        assert!(since.body.is_none());
+        let without_date = preconditions(None, Some(modified)).respond("x", Some(2), None).unwrap();
+        assert!(without_date.body.is_some());
+    }
+
+    #[test]
+    fn last_modified_is_the_latest_stamp() {
+        let stamps = [Some("2024-01-02 00:00:00"), Some("2024-03-01 12:00:00"), Some("2023-12-31 23:59:59")];
+        assert_eq!(last_modified(stamps), Some(datetime!(2024-03-01 12:00:00)));
+        assert_eq!(last_modified([Some("2024-01-02 00:00:00"), None]), None);
+        assert_eq!(last_modified([Some("ayer")]), None);
+        assert_eq!(last_modified(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn if_match_tags() {
+        assert_eq!(tag_versions("\"3-9f2c\""), Some(vec![3]));
+        assert_eq!(tag_versions("\"3\""), Some(vec![3]));
+        assert_eq!(tag_versions("W/\"4-aa\", \"5-bb\""), Some(vec![4, 5]));
+        assert_eq!(tag_versions("\"3-aa\", *"), None);
+        assert_eq!(tag_versions("\"9f2c\""), Some(vec![]));
+    }
+
+    #[rocket::async_test]
+    async fn if_match_version() {
+        let pool = pool_with_version(5).await;
+        let if_match = |value: Option<&str>| IfMatch(value.map(str::to_string));
+
+        assert_eq!(if_match(None).version(Some(2), &pool, "authors", 1).await.unwrap(), Some(2));
+        assert_eq!(if_match(Some("*")).version(Some(2), &pool, "authors", 1).await.unwrap(), None);
+
+        // El ETag del GET sirve tal cual como If-Match
+        let get = preconditions(None, None).respond("x", Some(5), None).unwrap();
+        assert_eq!(if_match(Some(&get.etag)).version(None, &pool, "authors", 1).await.unwrap(), Some(5));
+
+        let listed = if_match(Some("\"4-aa\", W/\"5-bb\", \"4-cc\""));
+        assert_eq!(listed.version(None, &pool, "authors", 1).await.unwrap(), Some(5));
+        let outdated = if_match(Some("\"2-aa\", \"3-bb\""));
+        assert_eq!(outdated.version(None, &pool, "authors", 1).await.unwrap(), Some(2));
+
+        let unversioned = if_match(Some("\"9f2c\"")).version(None, &pool, "authors", 1).await;
+        assert!(matches!(unversioned, Err(ApiError::PreconditionFailed(_))));
+    }
+}
@@ -0,0 +1,248 @@
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, status, Responder};
+use rocket::serde::json::Json;
+use rocket_db_pools::sqlx::{self, error::ErrorKind, SqlitePool};
+use tracing::error;
+
+use crate::models::{ApiResponse, UpdateOutcome};
+use crate::uploads::UploadError;
+use crate::validation::{self, FieldError, Validate};
+
+// Resultado estándar de los handlers JSON: el éxito sale con 200 y el error con su status
+pub type ApiResult<T> = Result<Json<ApiResponse<T>>, ApiError>;
+
+// Errores de la API. Cada variante define el status HTTP y el `code` del sobre ApiResponse.
+#[derive(Debug)]
+pub enum ApiError {
+    // 401 - Sin credenciales o credenciales inválidas
+    Unauthorized(String),
+    // 403 - Autenticado pero sin permiso (rol insuficiente, reseña ajena)
+    Forbidden(String),
+    // 404 - El recurso no existe
+    NotFound(String),
+    // 422 - Datos de entrada inválidos
+    Validation(String),
+    // 422 - DTO inválido: `data` lleva la lista de campos con su error
+    InvalidFields { message: String, errors: Vec<FieldError> },
+    // 409 - Choca con datos existentes (clave foránea, registro duplicado)
+    Conflict(String),
+    // 412 - `If-Match` / `version` no es la versión actual (otro usuario editó el registro)
+    PreconditionFailed(String),
+    // 413 - Cuerpo o archivo demasiado grande
+    PayloadTooLarge(String),
+    // 415 - Tipo de archivo no soportado (según su contenido)
+    UnsupportedMediaType(String),
+    // 503 - Dependencia externa deshabilitada o caída (p.ej. OpenSearch)
+    Unavailable(String),
+    // 500 - Error interno; el detalle se registra en el log, nunca se envía al cliente
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn unauthorized(message: &str) -> Self {
+        Self::Unauthorized(message.to_string())
+    }
+
+    pub fn forbidden(message: &str) -> Self {
+        Self::Forbidden(message.to_string())
+    }
+
+    pub fn not_found(message: &str) -> Self {
+        Self::NotFound(message.to_string())
+    }
+
+    pub fn validation(message: &str) -> Self {
+        Self::Validation(message.to_string())
+    }
+
+    pub fn invalid_fields(errors: Vec<FieldError>) -> Self {
+        Self::InvalidFields { message: format!("Datos inválidos: {}", validation::summary(&errors)), errors }
+    }
+
+    pub fn unavailable(message: &str) -> Self {
+        Self::Unavailable(message.to_string())
+    }
+
+    pub fn internal(message: &str) -> Self {
+        Self::Internal(message.to_string())
+    }
+
+    // Convierte un sqlx::Error según su tipo; `context` es el mensaje para errores internos.
+    // Uso: `repository::create_book(..).await.map_err(ApiError::db("Error al crear libro"))?`
+    // Argumentos: context: &'static str - Mensaje que ve el cliente si el error es un 500
+    // Retorna: impl FnOnce(sqlx::Error) -> ApiError - Adaptador para map_err
+    pub fn db(context: &'static str) -> impl FnOnce(sqlx::Error) -> ApiError {
+        move |e| {
+            let (kind, detail) = match &e {
+                sqlx::Error::Database(db) => (Some(db.kind()), db.message().to_string()),
+                _ => (None, String::new()),
+            };
+            match kind {
+                Some(ErrorKind::ForeignKeyViolation) => {
+                    Self::Conflict("La operación hace referencia a un registro inexistente o con dependencias".to_string())
+                }
+                Some(ErrorKind::UniqueViolation) => Self::Conflict("Ya existe un registro con esos datos".to_string()),
+                Some(ErrorKind::NotNullViolation | ErrorKind::CheckViolation) => {
+                    Self::Validation(format!("Datos inválidos: {detail}"))
+                }
+                _ => match e {
+                    sqlx::Error::RowNotFound => Self::not_found("Registro no encontrado"),
+                    e => {
+                        error!(error = %e, "{context}");
+                        Self::internal(context)
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            Self::Unauthorized(_) => Status::Unauthorized,
+            Self::Forbidden(_) => Status::Forbidden,
+            Self::NotFound(_) => Status::NotFound,
+            Self::Validation(_) | Self::InvalidFields { .. } => Status::UnprocessableEntity,
+            Self::Conflict(_) => Status::Conflict,
+            Self::PreconditionFailed(_) => Status::PreconditionFailed,
+            Self::PayloadTooLarge(_) => Status::PayloadTooLarge,
+            Self::UnsupportedMediaType(_) => Status::UnsupportedMediaType,
+            Self::Unavailable(_) => Status::ServiceUnavailable,
+            Self::Internal(_) => Status::InternalServerError,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::NotFound(_) => "not_found",
+            Self::Validation(_) | Self::InvalidFields { .. } => "validation_error",
+            Self::Conflict(_) => "conflict",
+            Self::PreconditionFailed(_) => "precondition_failed",
+            Self::PayloadTooLarge(_) => "payload_too_large",
+            Self::UnsupportedMediaType(_) => "unsupported_media_type",
+            Self::Unavailable(_) => "service_unavailable",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Unauthorized(m)
+            | Self::Forbidden(m)
+            | Self::NotFound(m)
+            | Self::Validation(m)
+            | Self::Conflict(m)
+            | Self::PreconditionFailed(m)
+            | Self::PayloadTooLarge(m)
+            | Self::UnsupportedMediaType(m)
+            | Self::Unavailable(m)
+            | Self::Internal(m)
+            | Self::InvalidFields { message: m, .. } => m,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message(), self.code())
+    }
+}
+
+impl From<UploadError> for ApiError {
+    fn from(e: UploadError) -> Self {
+        match e {
+            UploadError::TooLarge => Self::PayloadTooLarge(e.to_string()),
+            UploadError::UnsupportedType => Self::UnsupportedMediaType(e.to_string()),
+            UploadError::InvalidImage(_) => Self::Validation(e.to_string()),
+            UploadError::Io(_) => {
+                error!(error = %e, "Error guardando imagen");
+                Self::internal("No se pudo guardar la imagen")
+            }
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        if let Self::InvalidFields { message, errors } = self {
+            let body = ApiResponse { success: false, code: "validation_error", data: Some(errors), message };
+            return status::Custom(status, Json(body)).respond_to(req);
+        }
+        let body = Json(ApiResponse::<()>::error(self.code(), self.message()));
+        status::Custom(status, body).respond_to(req)
+    }
+}
+
+// Valida un DTO de alta/edición (reglas + existencia de author_id/book_id)
+// Argumentos:
+// - pool: &SqlitePool - Conexión a la BD
+// - dto: &T - DTO recibido
+// Retorna: Result<(), ApiError> - 422 con todos los campos inválidos en `data`
+pub async fn validate<T: Validate + ?Sized>(pool: &SqlitePool, dto: &T) -> Result<(), ApiError> {
+    let errors = validation::check(pool, dto)
+        .await
+        .map_err(ApiError::db("Error al validar los datos"))?;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::invalid_fields(errors))
+    }
+}
+
+// Resultado de un PUT con control de concurrencia optimista
+// Argumentos:
+// - outcome: UpdateOutcome<T> - Lo que devolvió el repository
+// - not_found: &str - Mensaje del 404
+// Retorna: Result<T, ApiError> - 404 si no existe, 412 si otro usuario lo editó después de leerlo
+pub fn updated<T>(outcome: UpdateOutcome<T>, not_found: &str) -> Result<T, ApiError> {
+    match outcome {
+        UpdateOutcome::Updated(value) => Ok(value),
+        UpdateOutcome::NotFound => Err(ApiError::not_found(not_found)),
+        UpdateOutcome::Conflict { current } => Err(ApiError::PreconditionFailed(format!(
+            "El registro fue modificado por otro usuario (versión actual: {current}); volvé a leerlo antes de editar"
+        ))),
+    }
+}
+
+// Catchers para /api: errores que Rocket genera antes del handler
+// (JSON mal formado, ruta inexistente, panic) también salen con el sobre ApiResponse
+
+// Los guards de auth::{Reader, Editor, Admin} fallan con 401/403
+#[catch(401)]
+pub fn unauthorized() -> ApiError {
+    ApiError::unauthorized("Se requiere autenticación: header `Authorization: Bearer <token>` o sesión iniciada")
+}
+
+#[catch(403)]
+pub fn forbidden() -> ApiError {
+    ApiError::forbidden("Tu rol no tiene permiso para esta operación")
+}
+
+#[catch(404)]
+pub fn not_found() -> ApiError {
+    ApiError::not_found("Ruta no encontrada")
+}
+
+#[catch(400)]
+pub fn bad_request() -> status::Custom<Json<ApiResponse<()>>> {
+    status::Custom(Status::BadRequest, Json(ApiResponse::error("bad_request", "Petición mal formada")))
+}
+
+// Rocket corta la subida al superar `limits.file` / `limits.data-form` (Rocket.toml)
+#[catch(413)]
+pub fn payload_too_large() -> ApiError {
+    ApiError::PayloadTooLarge("El cuerpo de la petición supera el tamaño máximo".to_string())
+}
+
+#[catch(422)]
+pub fn unprocessable_entity() -> ApiError {
+    ApiError::validation("El cuerpo de la petición no tiene el formato esperado")
+}
+
+#[catch(500)]
+pub fn internal_error() -> ApiError {
+    ApiError::internal("Error interno del servidor")
+}
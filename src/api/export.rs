@@ -0,0 +1,330 @@
+use rocket::futures::stream::{Stream, StreamExt};
+use rocket::http::ContentType;
+use rocket::request::Request;
+use rocket::response::{self, stream::ByteStream, Responder, Response};
+use rocket::State;
+use rocket_db_pools::sqlx::{QueryBuilder, Sqlite, SqlitePool};
+use rocket_db_pools::sqlx::sqlite::SqliteRow;
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::Db;
+use crate::repository::{authors as authors_repo, books as books_repo, reviews as reviews_repo, sales as sales_repo};
+use crate::api::authors::AuthorListQuery;
+use crate::api::books::BookListQuery;
+use crate::api::error::ApiError;
+
+// Exportación del catálogo completo. Las filas se leen de SQLite como stream y se envían
+// en bloques de CHUNK_BYTES: nunca se arma la tabla entera en memoria.
+// Un error de la BD o una fila que no se puede codificar a mitad de camino corta la descarga
+// (el status 200 ya se envió) y queda en el log.
+
+const CHUNK_BYTES: usize = 64 * 1024;
+// Marca UTF-8 para que Excel detecte la codificación (acentos y ñ)
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    // CSV con BOM y fin de línea CRLF: se abre directo en Excel / LibreOffice.
+    // Las celdas que la planilla tomaría como fórmula se escapan (ver `excel_cell`)
+    Excel,
+    // Un objeto JSON por línea
+    Ndjson,
+}
+
+impl ExportFormat {
+    // Argumentos: value: Option<&str> - `?format=` (por defecto csv)
+    // Retorna: Result<ExportFormat, ApiError> - 422 si no es csv, excel ni ndjson
+    fn parse(value: Option<&str>) -> Result<Self, ApiError> {
+        match value.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("csv") => Ok(Self::Csv),
+            Some("excel") => Ok(Self::Excel),
+            Some("ndjson") => Ok(Self::Ndjson),
+            Some(_) => Err(ApiError::validation("`format` inválido; valores permitidos: csv, excel, ndjson")),
+        }
+    }
+
+    fn content_type(&self) -> ContentType {
+        match self {
+            Self::Csv | Self::Excel => ContentType::new("text", "csv").with_params(("charset", "utf-8")),
+            Self::Ndjson => ContentType::new("application", "x-ndjson"),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv | Self::Excel => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+}
+
+// Acumula filas ya codificadas hasta que conviene enviarlas
+enum Encoder {
+    // csv::Writer no permite vaciar su Vec: en cada bloque se reemplaza por uno nuevo sin encabezado
+    Csv { writer: Box<csv::Writer<Vec<u8>>>, format: ExportFormat },
+    // Cada fila se serializa aparte para escapar sus celdas y se escribe con write_record
+    Excel { writer: Box<csv::Writer<Vec<u8>>>, header_pending: bool },
+    Ndjson(Vec<u8>),
+}
+
+impl Encoder {
+    fn new(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Csv => Self::Csv { writer: csv_writer(format, true, Vec::new()), format },
+            ExportFormat::Excel => Self::Excel { writer: csv_writer(format, false, UTF8_BOM.to_vec()), header_pending: true },
+            ExportFormat::Ndjson => Self::Ndjson(Vec::new()),
+        }
+    }
+
+    // El encabezado CSV sale con la primera fila (nombres de los campos)
+    // Retorna: Result<(), String> - Error si la fila no se pudo codificar (la exportación se corta)
+    fn write<T: Serialize>(&mut self, row: &T) -> Result<(), String> {
+        match self {
+            Self::Csv { writer, .. } => writer.serialize(row).map_err(|e| e.to_string()),
+            Self::Excel { writer, header_pending } => {
+                for record in excel_records(row, *header_pending).map_err(|e| e.to_string())? {
+                    writer.write_record(&record).map_err(|e| e.to_string())?;
+                }
+                *header_pending = false;
+                Ok(())
+            }
+            Self::Ndjson(buffer) => serde_json::to_writer(&mut *buffer, row)
+                .map(|_| buffer.push(b'\n'))
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    fn buffered(&self) -> usize {
+        match self {
+            Self::Csv { writer, .. } | Self::Excel { writer, .. } => writer.get_ref().len(),
+            Self::Ndjson(buffer) => buffer.len(),
+        }
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        match self {
+            Self::Csv { writer, format } => take_csv(writer, *format),
+            Self::Excel { writer, .. } => take_csv(writer, ExportFormat::Excel),
+            Self::Ndjson(buffer) => std::mem::take(buffer),
+        }
+    }
+}
+
+fn take_csv(writer: &mut Box<csv::Writer<Vec<u8>>>, format: ExportFormat) -> Vec<u8> {
+    let full = std::mem::replace(writer, csv_writer(format, false, Vec::new()));
+    full.into_inner().unwrap_or_else(|e| {
+        warn!(error = %e.error(), "Error vaciando CSV");
+        Vec::new()
+    })
+}
+
+// Una fila como registros CSV (más el encabezado si se pide) con las celdas escapadas para Excel
+fn excel_records<T: Serialize>(row: &T, header: bool) -> Result<Vec<csv::StringRecord>, csv::Error> {
+    let mut writer = csv::WriterBuilder::new().has_headers(header).from_writer(Vec::new());
+    writer.serialize(row)?;
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(bytes.as_slice())
+        .records()
+        .map(|record| record.map(|record| record.iter().map(excel_cell).collect()))
+        .collect()
+}
+
+// Inyección de fórmulas (CSV injection): una celda que empieza con `=`, `+`, `-`, `@`, tab o CR
+// la planilla la evalúa (`=HYPERLINK(...)` en el título de una reseña). Se le antepone `'` para que
+// quede como texto; los números negativos se dejan como están
+fn excel_cell(cell: &str) -> String {
+    let formula = cell.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    if formula && cell.parse::<f64>().is_err() {
+        format!("'{cell}")
+    } else {
+        cell.to_string()
+    }
+}
+
+fn csv_writer(format: ExportFormat, headers: bool, buffer: Vec<u8>) -> Box<csv::Writer<Vec<u8>>> {
+    let terminator = if format == ExportFormat::Excel { csv::Terminator::CRLF } else { csv::Terminator::Any(b'\n') };
+    Box::new(csv::WriterBuilder::new().has_headers(headers).terminator(terminator).from_writer(buffer))
+}
+
+// Recorre la consulta fila por fila y la emite codificada
+// Argumentos:
+// - pool: SqlitePool - Pool (se clona: el stream vive más que el handler)
+// - query: QueryBuilder - Consulta armada por el repositorio
+// - to_row: Fn(&SqliteRow) -> T - Convierte cada fila al registro exportado
+fn stream_rows<T, F>(
+    pool: SqlitePool,
+    mut query: QueryBuilder<'static, Sqlite>,
+    format: ExportFormat,
+    to_row: F,
+) -> ByteStream<impl Stream<Item = Vec<u8>>>
+where
+    T: Serialize,
+    F: Fn(&SqliteRow) -> T + Send + 'static,
+{
+    ByteStream! {
+        let mut rows = query.build().fetch(&pool);
+        let mut encoder = Encoder::new(format);
+        let mut count = 0usize;
+        while let Some(row) = rows.next().await {
+            match row {
+                Ok(row) => {
+                    if let Err(e) = encoder.write(&to_row(&row)) {
+                        error!(rows = count, error = %e, "Exportación interrumpida: fila que no se pudo codificar");
+                        break;
+                    }
+                    count += 1;
+                    if encoder.buffered() >= CHUNK_BYTES {
+                        yield encoder.take();
+                    }
+                }
+                Err(e) => {
+                    error!(rows = count, error = %e, "Exportación interrumpida");
+                    break;
+                }
+            }
+        }
+        yield encoder.take();
+        info!(rows = count, "Exportación terminada");
+    }
+}
+
+// Respuesta de descarga: Content-Type según el formato y nombre de archivo sugerido
+pub struct Export<S> {
+    name: &'static str,
+    format: ExportFormat,
+    stream: ByteStream<S>,
+}
+
+impl<'r, S> Responder<'r, 'r> for Export<S>
+where
+    S: Stream<Item = Vec<u8>> + Send + 'r,
+{
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'r> {
+        let disposition = format!("attachment; filename=\"{}.{}\"", self.name, self.format.extension());
+        Response::build_from(self.stream.respond_to(req)?)
+            .header(self.format.content_type())
+            .raw_header("Content-Disposition", disposition)
+            .ok()
+    }
+}
+
+// GET /api/export/authors?format=csv|excel|ndjson
+// Mismos filtros y orden que GET /api/authors (sin paginar), con libros, ventas y rating de cada autor
+#[get("/export/authors?<format>&<query..>")]
+pub async fn export_authors(
+    format: Option<&str>,
+    query: AuthorListQuery,
+    pool: &State<Db>,
+) -> Result<Export<impl Stream<Item = Vec<u8>>>, ApiError> {
+    let format = ExportFormat::parse(format)?;
+    let listing = query.parse()?;
+    let sql = authors_repo::export_authors_query(&listing.filter, listing.sort, listing.order);
+    let stream = stream_rows(pool.0.clone(), sql, format, authors_repo::author_export_from_row);
+    Ok(Export { name: "authors", format, stream })
+}
+
+// GET /api/export/books?format=csv|excel|ndjson
+// Mismos filtros y orden que GET /api/books (sin paginar)
+#[get("/export/books?<format>&<query..>")]
+pub async fn export_books(
+    format: Option<&str>,
+    query: BookListQuery,
+    pool: &State<Db>,
+) -> Result<Export<impl Stream<Item = Vec<u8>>>, ApiError> {
+    let format = ExportFormat::parse(format)?;
+    let listing = query.parse()?;
+    let sql = books_repo::export_books_query(&listing.filter, listing.sort, listing.order);
+    let stream = stream_rows(pool.0.clone(), sql, format, books_repo::book_export_from_row);
+    Ok(Export { name: "books", format, stream })
+}
+
+// GET /api/export/catalog?format=csv|excel|ndjson
+// Libros con autor, rating promedio, cantidad de reseñas y ventas totales; filtros de GET /api/books
+#[get("/export/catalog?<format>&<query..>")]
+pub async fn export_catalog(
+    format: Option<&str>,
+    query: BookListQuery,
+    pool: &State<Db>,
+) -> Result<Export<impl Stream<Item = Vec<u8>>>, ApiError> {
+    let format = ExportFormat::parse(format)?;
+    let listing = query.parse()?;
+    let sql = books_repo::export_catalog_query(&listing.filter, listing.sort, listing.order);
+    let stream = stream_rows(pool.0.clone(), sql, format, books_repo::catalog_export_from_row);
+    Ok(Export { name: "catalog", format, stream })
+}
+
+// GET /api/export/reviews?format=&book_id=&min_rating=
+#[get("/export/reviews?<format>&<book_id>&<min_rating>")]
+pub async fn export_reviews(
+    format: Option<&str>,
+    book_id: Option<i32>,
+    min_rating: Option<i32>,
+    pool: &State<Db>,
+) -> Result<Export<impl Stream<Item = Vec<u8>>>, ApiError> {
+    let format = ExportFormat::parse(format)?;
+    if min_rating.is_some_and(|r| !(1..=5).contains(&r)) {
+        return Err(ApiError::validation("`min_rating` debe estar entre 1 y 5"));
+    }
+    let sql = reviews_repo::export_reviews_query(book_id, min_rating);
+    let stream = stream_rows(pool.0.clone(), sql, format, reviews_repo::review_export_from_row);
+    Ok(Export { name: "reviews", format, stream })
+}
+
+// GET /api/export/sales?format=&book_id=&year_from=&year_to=
+#[get("/export/sales?<format>&<book_id>&<year_from>&<year_to>")]
+pub async fn export_sales(
+    format: Option<&str>,
+    book_id: Option<i32>,
+    year_from: Option<i32>,
+    year_to: Option<i32>,
+    pool: &State<Db>,
+) -> Result<Export<impl Stream<Item = Vec<u8>>>, ApiError> {
+    let format = ExportFormat::parse(format)?;
+    if let (Some(from), Some(to)) = (year_from, year_to) {
+        if from > to {
+            return Err(ApiError::validation("`year_from` no puede ser mayor que `year_to`"));
+        }
+    }
+    let sql = sales_repo::export_sales_query(book_id, year_from, year_to);
+    let stream = stream_rows(pool.0.clone(), sql, format, sales_repo::sales_export_from_row);
+    Ok(Export { name: "sales", format, stream })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Row {
+        title: &'static str,
+        sales: i64,
+    }
+
+    #[test]
+    fn excel_cell_escapes_formulas() {
+        for cell in ["=1+1", "+cmd", "-cmd|' /C calc'!A0", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(excel_cell(cell), format!("'{cell}"));
+        }
+    }
+
+    #[test]
+    fn excel_cell_keeps_text_and_numbers() {
+        for cell in ["Rayuela", "", "-3", "+4.5", "a=b"] {
+            assert_eq!(excel_cell(cell), cell);
+        }
+    }
+
+    #[test]
+    fn excel_encoder_writes_header_once_with_escaped_cells() {
+        let mut encoder = Encoder::new(ExportFormat::Excel);
+        encoder.write(&Row { title: "=HYPERLINK(\"x\")", sales: -2 }).unwrap();
+        encoder.write(&Row { title: "Rayuela", sales: 10 }).unwrap();
+        let bytes = encoder.take();
+        let text = std::str::from_utf8(bytes.strip_prefix(UTF8_BOM).unwrap()).unwrap();
+        assert_eq!(text, "title,sales\r\n\"'=HYPERLINK(\"\"x\"\")\",-2\r\nRayuela,10\r\n");
+    }
+}
@@ -0,0 +1,122 @@
+use rocket::form::Form;
+use rocket::{serde::json::Json, State};
+use rocket_db_pools::sqlx::SqlitePool;
+use crate::{Db, cache::Cache, repository};
+use crate::models::{ApiResponse, ImageRef};
+use crate::auth::Editor;
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::uploads::{self, ImageKind, ImageUpload};
+use crate::api::error::{ApiError, ApiResult};
+use tracing::info;
+
+// Subida multipart (campo `file`): foto de autor y portada de libro.
+// El archivo se valida por contenido, se guarda con nombre = hash y reemplaza al anterior,
+// que se borra del disco si ya nadie lo usa. Cambiar la imagen sube la `version` de la entidad.
+
+#[post("/authors/<id>/photo", data = "<upload>")]
+pub async fn upload_author_photo(id: i32, _editor: Editor, upload: Form<ImageUpload<'_>>, pool: &State<Db>, cache: &Cache) -> ApiResult<ImageRef> {
+    let image = replace_image(&pool.0, cache, ImageKind::AuthorPhoto, id, &upload).await?;
+    Ok(Json(ApiResponse::success(image)))
+}
+
+#[delete("/authors/<id>/photo")]
+pub async fn delete_author_photo(id: i32, _editor: Editor, pool: &State<Db>, cache: &Cache) -> ApiResult<()> {
+    remove_image(&pool.0, cache, ImageKind::AuthorPhoto, id).await?;
+    Ok(Json(ApiResponse::success(())))
+}
+
+#[post("/books/<id>/cover", data = "<upload>")]
+pub async fn upload_book_cover(id: i32, _editor: Editor, upload: Form<ImageUpload<'_>>, pool: &State<Db>, cache: &Cache) -> ApiResult<ImageRef> {
+    let image = replace_image(&pool.0, cache, ImageKind::BookCover, id, &upload).await?;
+    Ok(Json(ApiResponse::success(image)))
+}
+
+#[delete("/books/<id>/cover")]
+pub async fn delete_book_cover(id: i32, _editor: Editor, pool: &State<Db>, cache: &Cache) -> ApiResult<()> {
+    remove_image(&pool.0, cache, ImageKind::BookCover, id).await?;
+    Ok(Json(ApiResponse::success(())))
+}
+
+// Imagen actual de la entidad: None si la entidad no existe, Some(None) si no tiene imagen
+async fn current_image(pool: &SqlitePool, kind: ImageKind, id: i32) -> Result<Option<Option<String>>, ApiError> {
+    match kind {
+        ImageKind::AuthorPhoto => repository::authors::get_author_photo(pool, id).await,
+        ImageKind::BookCover => repository::books::get_book_cover(pool, id).await,
+    }
+    .map_err(ApiError::db("Error al obtener imagen"))
+}
+
+// Asigna la imagen y devuelve la que reemplazó. Compare-and-set: si otra subida la cambió entre la
+// lectura y el UPDATE se vuelve a leer, así cada request limpia exactamente la imagen que sacó
+// (con dos subidas a la vez, la que pierde no deja huérfana la de la otra)
+async fn swap_image(pool: &SqlitePool, kind: ImageKind, id: i32, name: Option<&str>) -> Result<Option<String>, ApiError> {
+    loop {
+        let previous = current_image(pool, kind, id).await?.ok_or_else(|| not_found(kind))?;
+        let swapped = match kind {
+            ImageKind::AuthorPhoto => repository::authors::set_author_photo(pool, id, previous.as_deref(), name).await,
+            ImageKind::BookCover => repository::books::set_book_cover(pool, id, previous.as_deref(), name).await,
+        }
+        .map_err(ApiError::db("Error al guardar imagen"))?;
+        if swapped {
+            return Ok(previous);
+        }
+    }
+}
+
+fn mutation(kind: ImageKind, id: i32) -> Mutation {
+    match kind {
+        ImageKind::AuthorPhoto => Mutation::UpdateAuthor(id),
+        ImageKind::BookCover => Mutation::UpdateBook { id, new_author_id: None },
+    }
+}
+
+fn not_found(kind: ImageKind) -> ApiError {
+    match kind {
+        ImageKind::AuthorPhoto => ApiError::not_found("Autor no encontrado"),
+        ImageKind::BookCover => ApiError::not_found("Libro no encontrado"),
+    }
+}
+
+// Guarda la imagen subida y la asigna a la entidad (también lo usa el formulario HTML de portada)
+// Argumentos:
+// - kind: ImageKind - Foto de autor o portada de libro
+// - id: i32 - ID del autor/libro
+// - upload: &ImageUpload - Formulario multipart recibido
+// Retorna: Result<ImageRef, ApiError> - URLs de la imagen y su miniatura
+pub(crate) async fn replace_image(pool: &SqlitePool, cache: &Cache, kind: ImageKind, id: i32, upload: &ImageUpload<'_>) -> Result<ImageRef, ApiError> {
+    current_image(pool, kind, id).await?.ok_or_else(|| not_found(kind))?;
+
+    let bytes = uploads::read_upload(&upload.file).await?;
+    let name = uploads::store(kind, bytes).await?;
+
+    let plan = InvalidationPlan::prepare(pool, mutation(kind, id)).await;
+    let previous = match swap_image(pool, kind, id, Some(&name)).await {
+        Ok(previous) => previous,
+        Err(e) => {
+            // La entidad se borró mientras tanto (o falló la BD): no dejar el archivo huérfano
+            uploads::remove_if_unused(pool, kind, &name).await;
+            return Err(e);
+        }
+    };
+    plan.purge(cache).await;
+
+    if let Some(old) = previous.filter(|old| *old != name) {
+        uploads::remove_if_unused(pool, kind, &old).await;
+    }
+    info!(image = %name, ?kind, id, "Imagen asignada");
+    Ok(uploads::image_ref(kind, &name))
+}
+
+async fn remove_image(pool: &SqlitePool, cache: &Cache, kind: ImageKind, id: i32) -> Result<(), ApiError> {
+    if current_image(pool, kind, id).await?.ok_or_else(|| not_found(kind))?.is_none() {
+        return Ok(());
+    }
+
+    let plan = InvalidationPlan::prepare(pool, mutation(kind, id)).await;
+    let previous = swap_image(pool, kind, id, None).await?;
+    plan.purge(cache).await;
+    if let Some(old) = previous {
+        uploads::remove_if_unused(pool, kind, &old).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use rocket::data::{Data, Limits, ToByteUnit};
+use rocket::http::{ContentType, Status};
+use rocket::response::status;
+use rocket::{serde::json::Json, State};
+use crate::{Db, cache::Cache};
+use crate::models::ApiResponse;
+use crate::auth::Editor;
+use crate::import::{self, Entity, Format, ImportError, ImportReport};
+use crate::indexer::Indexer;
+use crate::moderation::Moderation;
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::api::error::ApiError;
+use tracing::warn;
+
+// Tamaño máximo del archivo si Rocket.toml no define `limits.import`
+const DEFAULT_LIMIT_MIB: u64 = 20;
+
+// POST /api/import?entity=books&format=csv&dry_run=true
+// Cuerpo: el archivo tal cual (CSV con encabezado o NDJSON). Sin `format` se deduce del Content-Type.
+// 200 si se importaron todas las filas (o dry run sin errores); 422 con el reporte por fila si alguna falla.
+#[post("/import?<entity>&<format>&<dry_run>", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_data(
+    entity: &str,
+    format: Option<&str>,
+    dry_run: Option<bool>,
+    _editor: Editor,
+    content_type: Option<&ContentType>,
+    limits: &Limits,
+    data: Data<'_>,
+    pool: &State<Db>,
+    cache: &Cache,
+    indexer: &State<Indexer>,
+    moderation: &State<Moderation>,
+) -> Result<status::Custom<Json<ApiResponse<ImportReport>>>, ApiError> {
+    let entity = Entity::parse(entity).ok_or_else(|| {
+        let valid: Vec<&str> = Entity::ALL.iter().map(Entity::as_str).collect();
+        ApiError::Validation(format!("`entity` inválido; valores permitidos: {}", valid.join(", ")))
+    })?;
+    let format = match format {
+        Some(value) => Format::parse(value).ok_or_else(|| ApiError::validation("`format` inválido; valores permitidos: csv, ndjson"))?,
+        None => format_from_content_type(content_type)
+            .ok_or_else(|| ApiError::validation("Indicar `format` (csv o ndjson) o un Content-Type text/csv / application/x-ndjson"))?,
+    };
+    let dry_run = dry_run.unwrap_or(false);
+
+    let limit = limits.get("import").unwrap_or_else(|| DEFAULT_LIMIT_MIB.mebibytes());
+    let input = data.open(limit).into_string().await.map_err(|e| {
+        warn!(error = %e, "Error leyendo archivo de importación");
+        ApiError::validation("No se pudo leer el archivo (¿no es UTF-8?)")
+    })?;
+    if !input.is_complete() {
+        return Err(ApiError::PayloadTooLarge(format!("El archivo supera el máximo de {limit}")));
+    }
+
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::Import).await;
+    let mut report = import::run(&pool.0, moderation, entity, format, &input, dry_run).await.map_err(|e| match e {
+        ImportError::Invalid(message) => ApiError::Validation(message),
+        ImportError::Database(e) => ApiError::db("Error al importar")(e),
+    })?;
+
+    if report.committed {
+        plan.purge(cache).await;
+        indexer.enqueue_all(std::mem::take(&mut report.index_ops));
+    }
+
+    if report.errors.is_empty() {
+        return Ok(status::Custom(Status::Ok, Json(ApiResponse::success(report))));
+    }
+    let message = format!("{} de {} filas con errores; no se importó ninguna", report.errors.len(), report.total);
+    Ok(status::Custom(
+        Status::UnprocessableEntity,
+        Json(ApiResponse { success: false, code: "validation_error", data: Some(report), message }),
+    ))
+}
+
+fn format_from_content_type(content_type: Option<&ContentType>) -> Option<Format> {
+    let content_type = content_type?;
+    match (content_type.top().as_str(), content_type.sub().as_str()) {
+        ("text", "csv") => Some(Format::Csv),
+        ("application", "x-ndjson" | "ndjson" | "jsonl") => Some(Format::Ndjson),
+        _ => None,
+    }
+}
@@ -3,3 +3,12 @@ pub mod books;
 pub mod reviews;
 pub mod sales;
 pub mod dashboard;
+pub mod stats;
+pub mod admin;
+pub mod auth;
+pub mod images;
+pub mod import;
+pub mod export;
+pub mod error;
+pub mod pagination;
+pub mod conditional;
@@ -0,0 +1,205 @@
+use rocket::http::Header;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::api::error::ApiError;
+use crate::models::{ApiResponse, SortOrder};
+
+pub const DEFAULT_PER_PAGE: i64 = 20;
+pub const MAX_PER_PAGE: i64 = 100;
+// Tope de `page`: (page - 1) * per_page no puede desbordar y ningún listado tiene tantas páginas
+pub const MAX_PAGE: i64 = 1_000_000;
+
+// Filtros/orden normalizados como pares clave-valor: se repiten en los enlaces
+// next/prev y forman la clave de caché
+pub type QueryParams = Vec<(&'static str, String)>;
+
+// Query de un listado ya validada
+#[derive(Debug)]
+pub struct Listing<F, S> {
+    pub filter: F,
+    pub sort: S,
+    pub order: SortOrder,
+    pub page: PageRequest,
+    pub params: QueryParams,
+}
+
+// Página solicitada (`?page=&per_page=`), ya validada
+#[derive(Debug, Clone, Copy)]
+pub struct PageRequest {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl PageRequest {
+    // Argumentos: page / per_page: Option<i64> - Parámetros tal como llegan en la query
+    // Retorna: Result<PageRequest, ApiError> - 422 si page fuera de 1..=MAX_PAGE o per_page fuera de 1..=MAX_PER_PAGE
+    pub fn new(page: Option<i64>, per_page: Option<i64>) -> Result<Self, ApiError> {
+        let page = page.unwrap_or(1);
+        let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if !(1..=MAX_PAGE).contains(&page) {
+            return Err(ApiError::Validation(format!("`page` debe estar entre 1 y {MAX_PAGE}")));
+        }
+        if !(1..=MAX_PER_PAGE).contains(&per_page) {
+            return Err(ApiError::Validation(format!("`per_page` debe estar entre 1 y {MAX_PER_PAGE}")));
+        }
+        Ok(Self { page, per_page })
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+}
+
+// Enlaces de navegación (mismas URLs que el header `Link`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub current: String,
+    pub first: String,
+    pub last: String,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    pub total_pages: i64,
+    pub links: PageLinks,
+}
+
+impl<T> Page<T> {
+    // Argumentos:
+    // - items: Vec<T> - Elementos de la página
+    // - total: i64 - Total de elementos que cumplen los filtros
+    // - req: PageRequest - Página solicitada
+    // - path: &str - Ruta del recurso (p.ej. "/api/books")
+    // - params: &[(&str, String)] - Filtros/orden normalizados que se repiten en cada enlace
+    pub fn new(items: Vec<T>, total: i64, req: PageRequest, path: &str, params: &[(&'static str, String)]) -> Self {
+        let total_pages = ((total + req.per_page - 1) / req.per_page).max(1);
+        let link = |page: i64| page_url(path, params, page, req.per_page);
+
+        let links = PageLinks {
+            current: link(req.page),
+            first: link(1),
+            last: link(total_pages),
+            next: (req.page < total_pages).then(|| link(req.page + 1)),
+            prev: (req.page > 1).then(|| link((req.page - 1).min(total_pages))),
+        };
+        Self { items, total, page: req.page, per_page: req.per_page, total_pages, links }
+    }
+
+    // Header `Link` estilo RFC 8288: `<url>; rel="next", <url>; rel="prev", ...`
+    pub fn link_header(&self) -> Header<'static> {
+        let mut rels = vec![
+            format!("<{}>; rel=\"first\"", self.links.first),
+            format!("<{}>; rel=\"last\"", self.links.last),
+        ];
+        if let Some(next) = &self.links.next {
+            rels.push(format!("<{next}>; rel=\"next\""));
+        }
+        if let Some(prev) = &self.links.prev {
+            rels.push(format!("<{prev}>; rel=\"prev\""));
+        }
+        Header::new("Link", rels.join(", "))
+    }
+}
+
+fn page_url(path: &str, params: &[(&'static str, String)], page: i64, per_page: i64) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in params {
+        query.append_pair(key, value);
+    }
+    query.append_pair("page", &page.to_string());
+    query.append_pair("per_page", &per_page.to_string());
+    format!("{path}?{}", query.finish())
+}
+
+// Forma canónica de una consulta para usarla en claves de caché:
+// mismos filtros en distinto orden => misma clave
+pub fn cache_suffix(params: &[(&'static str, String)], req: PageRequest) -> String {
+    let mut sorted: Vec<_> = params.to_vec();
+    sorted.sort();
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in &sorted {
+        query.append_pair(key, value);
+    }
+    query.append_pair("page", &req.page.to_string());
+    query.append_pair("per_page", &req.per_page.to_string());
+    query.finish()
+}
+
+// Respuesta de listados: sobre ApiResponse<Page<T>> + header `Link`
+#[derive(Responder)]
+pub struct Paginated<T: Serialize> {
+    inner: Json<ApiResponse<Page<T>>>,
+    link: Header<'static>,
+}
+
+impl<T: Serialize> From<Page<T>> for Paginated<T> {
+    fn from(page: Page<T>) -> Self {
+        let link = page.link_header();
+        Self { inner: Json(ApiResponse::success(page)), link }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_request_defaults() {
+        let req = PageRequest::new(None, None).unwrap();
+        assert_eq!((req.page, req.per_page, req.offset()), (1, DEFAULT_PER_PAGE, 0));
+    }
+
+    #[test]
+    fn page_request_offset() {
+        let req = PageRequest::new(Some(3), Some(25)).unwrap();
+        assert_eq!(req.offset(), 50);
+    }
+
+    #[test]
+    fn page_request_rejects_out_of_range() {
+        for (page, per_page) in [(Some(0), None), (Some(-1), None), (None, Some(0)), (None, Some(MAX_PER_PAGE + 1))] {
+            assert!(matches!(PageRequest::new(page, per_page), Err(ApiError::Validation(_))), "{page:?} {per_page:?}");
+        }
+    }
+
+    #[test]
+    fn page_request_bounds_page_so_offset_cannot_overflow() {
+        assert!(matches!(PageRequest::new(Some(i64::MAX), None), Err(ApiError::Validation(_))));
+        let last = PageRequest::new(Some(MAX_PAGE), Some(MAX_PER_PAGE)).unwrap();
+        assert_eq!(last.offset(), (MAX_PAGE - 1) * MAX_PER_PAGE);
+    }
+
+    #[test]
+    fn cache_suffix_ignores_param_order() {
+        let req = PageRequest::new(Some(2), Some(10)).unwrap();
+        let a = cache_suffix(&[("q", "rust".into()), ("country", "AR".into())], req);
+        let b = cache_suffix(&[("country", "AR".into()), ("q", "rust".into())], req);
+        assert_eq!(a, b);
+        assert_eq!(a, "country=AR&q=rust&page=2&per_page=10");
+    }
+
+    #[test]
+    fn cache_suffix_encodes_values() {
+        let req = PageRequest::new(None, None).unwrap();
+        assert_eq!(cache_suffix(&[("q", "a&b c".into())], req), "q=a%26b+c&page=1&per_page=20");
+    }
+
+    #[test]
+    fn page_links() {
+        let req = PageRequest::new(Some(2), Some(10)).unwrap();
+        let page = Page::new(vec![1, 2, 3], 25, req, "/api/books", &[("q", "x".into())]);
+        assert_eq!(page.total_pages, 3);
+        assert_eq!(page.links.next.as_deref(), Some("/api/books?q=x&page=3&per_page=10"));
+        assert_eq!(page.links.prev.as_deref(), Some("/api/books?q=x&page=1&per_page=10"));
+        assert_eq!(page.links.last, "/api/books?q=x&page=3&per_page=10");
+    }
+}
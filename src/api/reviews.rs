@@ -1,55 +1,164 @@
 use rocket::{serde::json::Json, State};
+use rocket_db_pools::sqlx::SqlitePool;
 use crate::{models::*, repository, cache::Cache};
 use crate::models::ApiResponse;
 use crate::Db;
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::indexer::{IndexOp, Indexer};
+use crate::api::error::{self, ApiError, ApiResult};
+use crate::auth::{self, Reader, Voter};
+use crate::moderation::Moderation;
+use crate::api::conditional::{self, Conditional, IfMatch, Preconditions};
 
 #[get("/books/<book_id>/reviews")]
 pub async fn get_book_reviews(
     book_id: i32, 
     pool: &State<Db>,
-    cache: &Cache
-) -> Json<ApiResponse<Vec<ReviewWithBook>>> {
-    println!("🔍 Entrando a get_book_reviews para book_id: {}", book_id);
+    cache: &Cache,
+    preconditions: Preconditions
+) -> Result<Conditional, ApiError> {
     let cache_key = format!("{}{}", Cache::KEY_REVIEWS_PREFIX, book_id);
     
-    if let Ok(cached_reviews) = cache.get::<Vec<ReviewWithBook>>(&cache_key).await {
-        println!("✅ Reseñas del libro {} obtenidas del CACHÉ", book_id);
-        return Json(ApiResponse::success(cached_reviews));
-    }
-    println!("🔄 Obteniendo reseñas del libro {} de la BASE DE DATOS", book_id);
-    
-    match repository::get_reviews_by_book(&pool.0, book_id).await {
-        Ok(reviews) => {
-            let _ = cache.set(&cache_key, &reviews, Some(Cache::TTL_5_MIN)).await;
-            println!("💾 Reseñas del libro {} guardadas en CACHÉ", book_id);
-            Json(ApiResponse::success(reviews))
-        },
-        Err(_) => Json(ApiResponse::<Vec<ReviewWithBook>>::error("Error al obtener reseñas")),
-    }
+    let db = pool.0.clone();
+    let reviews = cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, move |_| vec![Cache::tag_book(book_id)], move || async move {
+            repository::get_reviews_by_book(&db, book_id)
+                .await
+                .map_err(ApiError::db("Error al obtener reseñas"))
+        })
+        .await?;
+    // Solo ETag: una reseña borrada o despublicada no cambia el máximo `updated_at` del resto
+    preconditions.respond(reviews, None, None)
 }
 
+// La reseña queda a nombre del usuario autenticado. Pasa por la moderación (src/moderation.rs):
+// si no queda aprobada se guarda igual y `message` lo indica
 #[post("/reviews", data = "<review>")]
-pub async fn create_review(review: Json<CreateReview>, pool: &State<Db>) -> Json<ApiResponse<i32>> {
-    match repository::create_review(&pool.0, &review).await {
-        Ok(id) => Json(ApiResponse::success(id)),
-        Err(_) => Json(ApiResponse::<i32>::error("Error al crear reseña")),
-    }
+#[allow(clippy::too_many_arguments)]
+pub async fn create_review(reader: Reader, review: Json<CreateReview>, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>, moderation: &State<Moderation>) -> ApiResult<i32> {
+    error::validate(&pool.0, &*review).await?;
+    let verdict = moderation.review(&pool.0, review.book_id, None, &review.review_text)
+        .await
+        .map_err(ApiError::db("Error al moderar reseña"))?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::CreateReview { book_id: review.book_id }).await;
+    let id = repository::create_review(&pool.0, &review, Some(reader.0.id), &verdict)
+        .await
+        .map_err(ApiError::db("Error al crear reseña"))?;
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Review(id));
+    Ok(Json(with_moderation_message(ApiResponse::success(id), Some(&verdict))))
 }
 
+// Si cambia el texto se vuelve a moderar (una reseña aprobada puede pasar a pendiente).
+// Edición concurrente: con `If-Match` (o `version`) de una versión vieja responde 412
 #[put("/reviews/<id>", data = "<review_update>")]
-pub async fn update_review(id: i32, review_update: Json<UpdateReview>, pool: &State<Db>) -> Json<ApiResponse<ReviewWithBook>> {
-    match repository::update_review(&pool.0, id, &review_update).await {
-        Ok(Some(review)) => Json(ApiResponse::success(review)),
-        Ok(None) => Json(ApiResponse::<ReviewWithBook>::error("Reseña no encontrada")),
-        Err(_) => Json(ApiResponse::<ReviewWithBook>::error("Error al actualizar reseña")),
+#[allow(clippy::too_many_arguments)]
+pub async fn update_review(id: i32, reader: Reader, if_match: IfMatch, review_update: Json<UpdateReview>, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>, moderation: &State<Moderation>) -> Result<Conditional, ApiError> {
+    check_review_owner(&pool.0, &reader.0, id).await?;
+    let mut review_update = review_update.into_inner();
+    review_update.version = if_match.version(review_update.version, &pool.0, "reviews", id).await?;
+    error::validate(&pool.0, &review_update).await?;
+    let verdict = match &review_update.review_text {
+        Some(text) => {
+            let book_id = repository::reviews::get_review_book_id(&pool.0, id)
+                .await
+                .map_err(ApiError::db("Error al obtener reseña"))?
+                .ok_or_else(|| ApiError::not_found("Reseña no encontrada"))?;
+            let verdict = moderation.review(&pool.0, book_id, Some(id), text)
+                .await
+                .map_err(ApiError::db("Error al moderar reseña"))?;
+            Some(verdict)
+        }
+        None => None,
+    };
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateReview(id)).await;
+    let outcome = repository::update_review(&pool.0, id, &review_update, verdict.as_ref())
+        .await
+        .map_err(ApiError::db("Error al actualizar reseña"))?;
+    let review = error::updated(outcome, "Reseña no encontrada")?;
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Review(id));
+    let version = review.version;
+    conditional::updated(with_moderation_message(ApiResponse::success(review), verdict.as_ref()), version)
+}
+
+// Avisa al autor cuando su reseña no quedó publicada
+fn with_moderation_message<T>(mut response: ApiResponse<T>, verdict: Option<&ModerationResult>) -> ApiResponse<T> {
+    let Some(verdict) = verdict else {
+        return response;
+    };
+    let reason = verdict.reason.as_deref().unwrap_or_default();
+    match verdict.status {
+        ReviewStatus::Pending => response.message = format!("Reseña guardada; queda pendiente de moderación ({reason})"),
+        ReviewStatus::Rejected => response.message = format!("Reseña rechazada por la moderación: {reason}"),
+        ReviewStatus::Approved => {}
     }
+    response
 }
 
 #[delete("/reviews/<id>")]
-pub async fn delete_review(id: i32, pool: &State<Db>) -> Json<ApiResponse<()>> {
-    match repository::delete_review(&pool.0, id).await {
-        Ok(true) => Json(ApiResponse::success(())),
-        Ok(false) => Json(ApiResponse::<()>::error("Reseña no encontrada")),
-        Err(_) => Json(ApiResponse::<()>::error("Error al eliminar reseña")),
+pub async fn delete_review(id: i32, reader: Reader, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<()> {
+    check_review_owner(&pool.0, &reader.0, id).await?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::DeleteReview(id)).await;
+    // Se necesita el libro para recalcular su rating en el índice
+    let book_id = repository::reviews::get_review_book_id(&pool.0, id).await.ok().flatten();
+    let deleted = repository::delete_review(&pool.0, id)
+        .await
+        .map_err(ApiError::db("Error al eliminar reseña"))?;
+    if !deleted {
+        return Err(ApiError::not_found("Reseña no encontrada"));
+    }
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Review(id));
+    indexer.enqueue_all(book_id.map(IndexOp::Book));
+    Ok(Json(ApiResponse::success(())))
+}
+
+// POST /api/reviews/<id>/vote
+// Un voto positivo por votante (usuario o cliente anónimo); repetirlo no suma
+#[post("/reviews/<id>/vote")]
+pub async fn vote_review(id: i32, voter: Voter, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<VoteResult> {
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateReview(id)).await;
+    let (changed, positive_votes) = repository::reviews::add_vote(&pool.0, id, &voter.key, voter.user_id)
+        .await
+        .map_err(ApiError::db("Error al registrar voto"))?
+        .ok_or_else(|| ApiError::not_found("Reseña no encontrada"))?;
+
+    if changed {
+        plan.purge(cache).await;
+        indexer.enqueue(IndexOp::Review(id));
+    }
+    Ok(Json(ApiResponse::success(VoteResult { review_id: id, positive_votes, voted: true })))
+}
+
+// DELETE /api/reviews/<id>/vote
+// Retira el voto del mismo votante (sin voto previo no hace nada)
+#[delete("/reviews/<id>/vote")]
+pub async fn unvote_review(id: i32, voter: Voter, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<VoteResult> {
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateReview(id)).await;
+    let (changed, positive_votes) = repository::reviews::remove_vote(&pool.0, id, &voter.key)
+        .await
+        .map_err(ApiError::db("Error al retirar voto"))?
+        .ok_or_else(|| ApiError::not_found("Reseña no encontrada"))?;
+
+    if changed {
+        plan.purge(cache).await;
+        indexer.enqueue(IndexOp::Review(id));
+    }
+    Ok(Json(ApiResponse::success(VoteResult { review_id: id, positive_votes, voted: false })))
+}
+
+// 404 si la reseña no existe, 403 si no es del usuario y no es Editor/Admin
+async fn check_review_owner(pool: &SqlitePool, user: &User, id: i32) -> Result<(), ApiError> {
+    let owner = repository::reviews::get_review_owner(pool, id)
+        .await
+        .map_err(ApiError::db("Error al obtener reseña"))?
+        .ok_or_else(|| ApiError::not_found("Reseña no encontrada"))?;
+    if !auth::can_manage_review(user, owner) {
+        return Err(ApiError::forbidden("Solo el autor de la reseña o un editor puede modificarla"));
     }
+    Ok(())
 }
@@ -2,54 +2,77 @@ use rocket::{serde::json::Json, State};
 use crate::{models::*, repository, cache::Cache};
 use crate::models::ApiResponse;
 use crate::Db;
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::indexer::{IndexOp, Indexer};
+use crate::api::error::{self, ApiError, ApiResult};
+use crate::auth::Editor;
+use crate::api::conditional::{self, Conditional, IfMatch};
 
 #[get("/books/<book_id>/sales")]
 pub async fn get_book_sales(
     book_id: i32, 
     pool: &State<Db>,
     cache: &Cache
-) -> Json<ApiResponse<Vec<YearlySalesWithBook>>> {
-    println!("🔍 Entrando a get_book_sales para book_id: {}", book_id);
+) -> ApiResult<Vec<YearlySalesWithBook>> {
     let cache_key = format!("{}{}", Cache::KEY_SALES_PREFIX, book_id);
     
-    if let Ok(cached_sales) = cache.get::<Vec<YearlySalesWithBook>>(&cache_key).await {
-        println!("✅ Datos de ventas del libro {} obtenidos del CACHÉ", book_id);
-        return Json(ApiResponse::success(cached_sales));
-    }
-    println!("🔄 Obteniendo datos de ventas del libro {} de la BASE DE DATOS", book_id);
-    
-    match repository::get_yearly_sales_by_book(&pool.0, book_id).await {
-        Ok(sales) => {
-            let _ = cache.set(&cache_key, &sales, Some(Cache::TTL_5_MIN)).await;
-            println!("💾 Datos de ventas del libro {} guardados en CACHÉ", book_id);
-            Json(ApiResponse::success(sales))
-        },
-        Err(_) => Json(ApiResponse::<Vec<YearlySalesWithBook>>::error("Error al obtener ventas")),
-    }
+    let db = pool.0.clone();
+    let sales = cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, move |_| vec![Cache::tag_book(book_id)], move || async move {
+            repository::get_yearly_sales_by_book(&db, book_id)
+                .await
+                .map_err(ApiError::db("Error al obtener ventas"))
+        })
+        .await?;
+    Ok(Json(ApiResponse::success(sales)))
 }
 
+// Las escrituras de ventas disparan los triggers que recalculan books.sales_count,
+// por eso también se re-sincroniza el libro en el índice de búsqueda
+
 #[post("/sales", data = "<sales>")]
-pub async fn create_yearly_sales(sales: Json<CreateYearlySales>, pool: &State<Db>) -> Json<ApiResponse<i32>> {
-    match repository::create_yearly_sales(&pool.0, &sales).await {
-        Ok(id) => Json(ApiResponse::success(id)),
-        Err(_) => Json(ApiResponse::<i32>::error("Error al crear registro de ventas")),
-    }
+pub async fn create_yearly_sales(_editor: Editor, sales: Json<CreateYearlySales>, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<i32> {
+    error::validate(&pool.0, &*sales).await?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::CreateSales { book_id: sales.book_id }).await;
+    let id = repository::create_yearly_sales(&pool.0, &sales)
+        .await
+        .map_err(ApiError::db("Error al crear registro de ventas"))?;
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Book(sales.book_id));
+    Ok(Json(ApiResponse::success(id)))
 }
 
+// Igual que los demás PUT: `If-Match` / `version` desactualizado => 412
 #[put("/sales/<id>", data = "<sales_update>")]
-pub async fn update_yearly_sales(id: i32, sales_update: Json<UpdateYearlySales>, pool: &State<Db>) -> Json<ApiResponse<YearlySalesWithBook>> {
-    match repository::update_yearly_sales(&pool.0, id, &sales_update).await {
-        Ok(Some(sales)) => Json(ApiResponse::success(sales)),
-        Ok(None) => Json(ApiResponse::<YearlySalesWithBook>::error("Registro de ventas no encontrado")),
-        Err(_) => Json(ApiResponse::<YearlySalesWithBook>::error("Error al actualizar registro de ventas")),
-    }
+pub async fn update_yearly_sales(id: i32, _editor: Editor, if_match: IfMatch, sales_update: Json<UpdateYearlySales>, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> Result<Conditional, ApiError> {
+    let mut sales_update = sales_update.into_inner();
+    sales_update.version = if_match.version(sales_update.version, &pool.0, "yearly_sales", id).await?;
+    error::validate(&pool.0, &sales_update).await?;
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateSales(id)).await;
+    let outcome = repository::update_yearly_sales(&pool.0, id, &sales_update)
+        .await
+        .map_err(ApiError::db("Error al actualizar registro de ventas"))?;
+    let sales = error::updated(outcome, "Registro de ventas no encontrado")?;
+
+    plan.purge(cache).await;
+    indexer.enqueue(IndexOp::Book(sales.book_id));
+    let version = sales.version;
+    conditional::updated(ApiResponse::success(sales), version)
 }
 
 #[delete("/sales/<id>")]
-pub async fn delete_yearly_sales(id: i32, pool: &State<Db>) -> Json<ApiResponse<()>> {
-    match repository::delete_yearly_sales(&pool.0, id).await {
-        Ok(true) => Json(ApiResponse::success(())),
-        Ok(false) => Json(ApiResponse::<()>::error("Registro de ventas no encontrado")),
-        Err(_) => Json(ApiResponse::<()>::error("Error al eliminar registro de ventas")),
+pub async fn delete_yearly_sales(id: i32, _editor: Editor, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>) -> ApiResult<()> {
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::DeleteSales(id)).await;
+    let book_id = repository::sales::get_yearly_sales_book_id(&pool.0, id).await.ok().flatten();
+    let deleted = repository::delete_yearly_sales(&pool.0, id)
+        .await
+        .map_err(ApiError::db("Error al eliminar registro de ventas"))?;
+    if !deleted {
+        return Err(ApiError::not_found("Registro de ventas no encontrado"));
     }
+
+    plan.purge(cache).await;
+    indexer.enqueue_all(book_id.map(IndexOp::Book));
+    Ok(Json(ApiResponse::success(())))
 }
@@ -0,0 +1,81 @@
+use rocket::{serde::json::Json, State};
+use rocket_db_pools::sqlx::{self, SqlitePool};
+
+use crate::{Db, cache::Cache, models::*, repository::stats as stats_repo};
+use crate::api::error::{ApiError, ApiResult};
+
+const DEFAULT_TOP_RATED: i64 = 10;
+const DEFAULT_TOP_SELLING: i64 = 50;
+const MAX_TOP: i64 = 200;
+
+// Loaders con caché compartidos por la API y la vista /tables.
+// Sin Redis (circuito abierto) van directo a la BD.
+
+pub async fn load_author_stats(pool: &SqlitePool, cache: &Cache) -> Result<Vec<AuthorStats>, sqlx::Error> {
+    let pool = pool.clone();
+    cache
+        .get_or_compute(Cache::KEY_STATS_AUTHORS, Cache::TTL_5_MIN, |_| Vec::new(), move || async move {
+            stats_repo::get_author_stats(&pool).await
+        })
+        .await
+}
+
+pub async fn load_top_rated(pool: &SqlitePool, cache: &Cache, limit: i64) -> Result<Vec<TopRatedBook>, sqlx::Error> {
+    let cache_key = format!("{}{}", Cache::KEY_STATS_TOP_RATED_PREFIX, limit);
+    let pool = pool.clone();
+    cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, |_| Vec::new(), move || async move {
+            stats_repo::get_top_rated_books(&pool, limit).await
+        })
+        .await
+}
+
+pub async fn load_top_selling(pool: &SqlitePool, cache: &Cache, limit: i64) -> Result<Vec<TopSellingBook>, sqlx::Error> {
+    let cache_key = format!("{}{}", Cache::KEY_STATS_TOP_SELLING_PREFIX, limit);
+    let pool = pool.clone();
+    cache
+        .get_or_compute(&cache_key, Cache::TTL_5_MIN, |_| Vec::new(), move || async move {
+            stats_repo::get_top_selling_books(&pool, limit).await
+        })
+        .await
+}
+
+fn top_limit(limit: Option<i64>, default: i64) -> Result<i64, ApiError> {
+    let limit = limit.unwrap_or(default);
+    if !(1..=MAX_TOP).contains(&limit) {
+        return Err(ApiError::Validation(format!("`limit` debe estar entre 1 y {MAX_TOP}")));
+    }
+    Ok(limit)
+}
+
+// GET /api/stats/authors
+// Autores con cantidad de libros, rating promedio y ventas totales
+#[get("/stats/authors")]
+pub async fn author_stats(pool: &State<Db>, cache: &Cache) -> ApiResult<Vec<AuthorStats>> {
+    let stats = load_author_stats(&pool.0, cache)
+        .await
+        .map_err(ApiError::db("Error al calcular estadísticas de autores"))?;
+    Ok(Json(ApiResponse::success(stats)))
+}
+
+// GET /api/stats/top-rated?limit=10
+// Libros con mejor rating promedio y su reseña más alta/más baja más popular
+#[get("/stats/top-rated?<limit>")]
+pub async fn top_rated(limit: Option<i64>, pool: &State<Db>, cache: &Cache) -> ApiResult<Vec<TopRatedBook>> {
+    let limit = top_limit(limit, DEFAULT_TOP_RATED)?;
+    let top = load_top_rated(&pool.0, cache, limit)
+        .await
+        .map_err(ApiError::db("Error al calcular top por rating"))?;
+    Ok(Json(ApiResponse::success(top)))
+}
+
+// GET /api/stats/top-selling?limit=50
+// Libros más vendidos con ventas del autor y si están en el top 5 de su año
+#[get("/stats/top-selling?<limit>")]
+pub async fn top_selling(limit: Option<i64>, pool: &State<Db>, cache: &Cache) -> ApiResult<Vec<TopSellingBook>> {
+    let limit = top_limit(limit, DEFAULT_TOP_SELLING)?;
+    let top = load_top_selling(&pool.0, cache, limit)
+        .await
+        .map_err(ApiError::db("Error al calcular top por ventas"))?;
+    Ok(Json(ApiResponse::success(top)))
+}
@@ -0,0 +1,270 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::time::Duration;
+use rocket::{Build, Rocket};
+use rocket_db_pools::{sqlx::{self, SqlitePool}, Database};
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::models::{Role, User};
+use crate::repository::users as users_repo;
+use crate::Db;
+
+// Cookie de sesión de las vistas HTML (el valor es un token aleatorio; en la BD solo su hash)
+pub const SESSION_COOKIE: &str = "session";
+const SESSION_DAYS: i64 = 7;
+
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_USERNAME_LEN: usize = 32;
+
+// ===== Contraseñas y tokens =====
+
+// Argon2 es costoso a propósito: se ejecuta fuera del runtime async
+pub async fn hash_password(password: String) -> Result<String, String> {
+    rocket::tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+pub async fn verify_password(password: String, hash: String) -> bool {
+    rocket::tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .unwrap_or(false)
+    })
+    .await
+    .unwrap_or(false)
+}
+
+// Token aleatorio de 256 bits en hex (cookies de sesión y tokens de la API)
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Lo que se guarda en la BD: si se filtra la tabla, los tokens no sirven
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Reglas de registro: usuario de 3 a 32 caracteres [a-zA-Z0-9_.-] y contraseña de 8+ caracteres
+// Retorna: Result<(), String> - Mensaje para el cliente si no cumple
+pub fn validate_credentials(username: &str, password: &str) -> Result<(), String> {
+    let valid_chars = username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if username.len() < 3 || username.len() > MAX_USERNAME_LEN || !valid_chars {
+        return Err(format!(
+            "El usuario debe tener entre 3 y {MAX_USERNAME_LEN} caracteres (letras, números, '_', '.' o '-')"
+        ));
+    }
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!("La contraseña debe tener al menos {MIN_PASSWORD_LEN} caracteres"));
+    }
+    Ok(())
+}
+
+// Verifica usuario y contraseña
+// Retorna: Result<Option<User>, sqlx::Error> - None si el usuario no existe o la contraseña no coincide
+pub async fn authenticate(pool: &SqlitePool, username: &str, password: &str) -> Result<Option<User>, sqlx::Error> {
+    let Some((user, hash)) = users_repo::get_credentials(pool, username.trim()).await? else {
+        return Ok(None);
+    };
+    let valid = verify_password(password.to_string(), hash).await;
+    Ok(valid.then_some(user))
+}
+
+// ===== Sesiones del HTML =====
+
+pub async fn start_session(pool: &SqlitePool, cookies: &CookieJar<'_>, user_id: i32) -> Result<(), sqlx::Error> {
+    let token = generate_token();
+    users_repo::create_session(pool, &hash_token(&token), user_id, SESSION_DAYS).await?;
+    cookies.add(
+        Cookie::build((SESSION_COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(Duration::days(SESSION_DAYS)),
+    );
+    Ok(())
+}
+
+pub async fn end_session(pool: &SqlitePool, cookies: &CookieJar<'_>) {
+    if let Some(cookie) = cookies.get(SESSION_COOKIE) {
+        if let Err(e) = users_repo::delete_session(pool, &hash_token(cookie.value())).await {
+            error!(error = %e, "Error cerrando sesión");
+        }
+    }
+    cookies.remove(Cookie::build(SESSION_COOKIE).path("/"));
+}
+
+// ===== Request guards =====
+
+// Motivo por el que un guard rechazó la petición (el catcher 401/403 arma la respuesta)
+#[derive(Debug)]
+pub enum AuthError {
+    // Sin cookie ni header Authorization
+    Missing,
+    // Token bearer o cookie inválidos / vencidos
+    Invalid,
+    // Autenticado pero sin el rol necesario
+    Forbidden,
+    // No se pudo consultar la BD
+    Database,
+}
+
+// Resultado de identificar al usuario, calculado una vez por request
+struct Identity(Result<Option<User>, ()>);
+
+// `Authorization: Bearer <token>` tiene prioridad; si no viene, se usa la cookie de sesión
+// (las vistas HTML llaman a /api con fetch y envían la cookie)
+async fn identify(req: &Request<'_>) -> Result<Option<User>, ()> {
+    let Some(db) = Db::fetch(req.rocket()) else {
+        return Err(());
+    };
+
+    let lookup = if let Some(header) = req.headers().get_one("Authorization") {
+        let token = header.strip_prefix("Bearer ").map(str::trim).unwrap_or_default();
+        if token.is_empty() {
+            return Ok(None);
+        }
+        users_repo::get_user_by_api_token(&db.0, &hash_token(token)).await
+    } else if let Some(cookie) = req.cookies().get(SESSION_COOKIE) {
+        users_repo::get_user_by_session(&db.0, &hash_token(cookie.value())).await
+    } else {
+        return Ok(None);
+    };
+
+    lookup.map_err(|e| error!(error = %e, "Error consultando credenciales"))
+}
+
+async fn require(req: &Request<'_>, min_role: Role) -> Outcome<User, AuthError> {
+    let identity = req.local_cache_async(async { Identity(identify(req).await) }).await;
+    match &identity.0 {
+        Err(()) => Outcome::Error((Status::InternalServerError, AuthError::Database)),
+        Ok(None) if has_credentials(req) => Outcome::Error((Status::Unauthorized, AuthError::Invalid)),
+        Ok(None) => Outcome::Error((Status::Unauthorized, AuthError::Missing)),
+        Ok(Some(user)) if user.role < min_role => Outcome::Error((Status::Forbidden, AuthError::Forbidden)),
+        Ok(Some(user)) => Outcome::Success(user.clone()),
+    }
+}
+
+fn has_credentials(req: &Request<'_>) -> bool {
+    req.headers().contains("Authorization") || req.cookies().get(SESSION_COOKIE).is_some()
+}
+
+// Cualquier usuario autenticado
+pub struct Reader(pub User);
+// Editor o Admin
+pub struct Editor(#[allow(dead_code)] pub User);
+// Solo Admin
+pub struct Admin(pub User);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Reader {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        require(req, Role::Reader).await.map(Reader)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Editor {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        require(req, Role::Editor).await.map(Editor)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        require(req, Role::Admin).await.map(Admin)
+    }
+}
+
+// Quién vota una reseña: el usuario autenticado o, si no hay sesión ni token,
+// una huella anónima (SHA-256 de IP + User-Agent) para no contar dos veces al mismo cliente
+pub struct Voter {
+    pub key: String,
+    pub user_id: Option<i32>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Voter {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match require(req, Role::Reader).await {
+            Outcome::Success(user) => Outcome::Success(Voter { key: format!("user:{}", user.id), user_id: Some(user.id) }),
+            Outcome::Error((_, AuthError::Missing)) => {
+                let ip = req.client_ip().map(|ip| ip.to_string()).unwrap_or_default();
+                let agent = req.headers().get_one("User-Agent").unwrap_or_default();
+                let key = format!("anon:{}", hash_token(&format!("{ip}|{agent}")));
+                Outcome::Success(Voter { key, user_id: None })
+            }
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
+
+// Una reseña la puede modificar su autor o cualquier Editor/Admin.
+// Las reseñas sin autor (anteriores a los usuarios) solo Editor/Admin.
+pub fn can_manage_review(user: &User, owner: Option<i32>) -> bool {
+    user.role >= Role::Editor || owner == Some(user.id)
+}
+
+// Fairing de ignición: crea el admin inicial desde ADMIN_USERNAME / ADMIN_PASSWORD
+// si todavía no existe (no cambia la contraseña de un usuario existente)
+pub async fn bootstrap_admin(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let (Some(username), Some(password)) = (var("ADMIN_USERNAME"), var("ADMIN_PASSWORD")) else {
+        return Ok(rocket);
+    };
+    let Some(pool) = Db::fetch(&rocket).map(|db| db.0.clone()) else {
+        error!("Admin inicial: la BD no está inicializada");
+        return Err(rocket);
+    };
+    if let Err(e) = validate_credentials(&username, &password) {
+        error!(error = %e, "ADMIN_USERNAME / ADMIN_PASSWORD inválidos");
+        return Err(rocket);
+    }
+
+    match users_repo::get_credentials(&pool, &username).await {
+        Ok(Some(_)) => Ok(rocket),
+        Ok(None) => {
+            let created = match hash_password(password).await {
+                Ok(hash) => users_repo::create_user(&pool, &username, &hash, Role::Admin).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+            match created {
+                Ok(_) => {
+                    info!(username = %username, "Usuario admin inicial creado");
+                    Ok(rocket)
+                }
+                Err(e) => {
+                    error!(error = %e, "No se pudo crear el admin inicial");
+                    Err(rocket)
+                }
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "No se pudo consultar el admin inicial");
+            Err(rocket)
+        }
+    }
+}
@@ -1,15 +1,224 @@
-use bb8::Pool;
+use bb8::{Pool, PooledConnection};
 use bb8_redis::RedisConnectionManager;
-use bb8_redis::redis::{AsyncCommands, FromRedisValue, RedisError, RedisResult, ToRedisArgs};
-use std::time::Duration;
+use bb8_redis::redis::aio::Connection;
+use bb8_redis::redis::{AsyncCommands, Client, ErrorKind, RedisError, RedisResult};
+use futures_util::StreamExt;
+use hashlink::LruCache;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::logging;
+use crate::metrics::{self, CacheResult};
 
 // Alias para el pool de conexiones
 pub type RedisPool = Pool<RedisConnectionManager>; 
 
+// Redis es opcional: el caché siempre está en el State de Rocket aunque Redis no responda.
+// - El pool conecta de forma perezosa (no hace falta Redis al arrancar) y reintenta en cada uso
+// - Tras BREAKER_THRESHOLD fallas de conexión seguidas el circuit breaker se abre: durante
+//   BREAKER_OPEN_FOR las operaciones fallan al instante (los handlers leen de SQLite) y después
+//   se deja pasar una sola operación de prueba; si responde se cierra, si no vuelve a abrirse
+// - Las invalidaciones que no llegaron a Redis (claves, tags, patrones) se anotan y se reintentan,
+//   solo esas, con la siguiente operación que sí responda
+
+// Tags: cada entrada se anota en sets `tag:<tag>` para invalidar sin recorrer el keyspace.
+// - Tag de familia, automático: el prefijo de la clave sin el `:` final (`books:list`, `books:id`, `stats`, ...)
+// - Tags de entidad, los pasa el handler (`book:17`, `author:3`) para las entradas que embeben esa entidad
+// Un set de tags vive un poco más que la entrada más larga que contiene; los miembros vencidos no molestan
+// (UNLINK de una clave que no existe no hace nada).
+const TAG_PREFIX: &str = "tag:";
+const TAG_TTL_SLACK: Duration = Duration::from_secs(60);
+// Claves por UNLINK y por iteración de SCAN
+const DELETE_BATCH: usize = 500;
+
+// get_or_compute (lo usan todos los handlers cacheados):
+// - Single-flight: dentro del proceso los requests concurrentes por la misma clave esperan un
+//   solo cálculo; entre réplicas, quien calcula toma el lock `lock:<clave>` (SET NX PX) y las
+//   demás esperan hasta LOCK_WAIT a que aparezca el valor antes de ir a SQLite por su cuenta
+// - Soft TTL: la entrada guarda hasta cuándo está fresca; vencida se sigue sirviendo hasta
+//   STALE_GRACE más mientras un solo task la recalcula en segundo plano
+// - Jitter: el TTL de cada entrada varía ±TTL_JITTER para que no venzan todas juntas
+const LOCK_PREFIX: &str = "lock:";
+const LOCK_TTL: Duration = Duration::from_secs(5);
+const LOCK_WAIT: Duration = Duration::from_secs(2);
+const LOCK_POLL: Duration = Duration::from_millis(50);
+const STALE_GRACE: Duration = Duration::from_secs(60);
+const TTL_JITTER: f64 = 0.1;
+// Borra el lock solo si sigue siendo nuestro (pudo vencer y tomarlo otra réplica)
+const RELEASE_LOCK_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end";
+
+// L1: caché en memoria de cada réplica delante de Redis (solo get_or_compute). Guarda el valor ya
+// deserializado por poco tiempo (L1_TTL, o menos si la entrada de Redis vence antes). Cada vez que
+// se borra algo de Redis (delete, tags, patrón) se publican las claves en
+// INVALIDATION_CHANNEL y todas las réplicas detrás de Traefik las sacan de su L1.
+const L1_CAPACITY: usize = 1000;
+const L1_TTL: Duration = Duration::from_secs(10);
+const INVALIDATION_CHANNEL: &str = "cache:invalidate";
+// Mensaje que vacía el L1 entero (si no, el mensaje es un array JSON de claves)
+const INVALIDATE_ALL: &str = "*";
+// Espera entre intentos de suscripción mientras Redis no responde
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+// Espera máxima por una conexión del pool y por la respuesta de un comando
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+// Fallas seguidas que abren el circuito y tiempo que queda abierto
+const BREAKER_THRESHOLD: u32 = 3;
+const BREAKER_OPEN_FOR: Duration = Duration::from_secs(30);
+// Lo máximo que puede durar la operación de prueba. Si el request que la hacía se cancela
+// (el cliente cortó) nadie la cierra: vencido este plazo la siguiente operación hace otra prueba
+const PROBE_TIMEOUT: Duration = Duration::from_secs(CONNECT_TIMEOUT.as_secs() + COMMAND_TIMEOUT.as_secs());
+// Invalidaciones pendientes como máximo; las que no entran vencen solas con su TTL
+const LOST_CAPACITY: usize = 10_000;
+
+// Estado del circuit breaker
+#[derive(Debug, Default)]
+struct Breaker {
+    // Fallas de conexión seguidas
+    failures: u32,
+    // Abierto hasta este instante (None = cerrado)
+    open_until: Option<Instant>,
+    // Operación de prueba en curso (circuito medio abierto) y hasta cuándo se la espera
+    probing: Option<Instant>,
+    // Invalidaciones que no llegaron a Redis
+    lost: Lost,
+}
+
+// Invalidaciones pendientes de reintentar
+#[derive(Debug, Default)]
+struct Lost {
+    keys: HashSet<String>,
+    tags: HashSet<String>,
+    patterns: HashSet<String>,
+}
+
+impl Lost {
+    fn len(&self) -> usize {
+        self.keys.len() + self.tags.len() + self.patterns.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn merge(&mut self, other: Lost) {
+        self.keys.extend(other.keys);
+        self.tags.extend(other.tags);
+        self.patterns.extend(other.patterns);
+    }
+}
+
+// Valor guardado por get_or_compute
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    // Hasta cuándo está fresco (ms desde la época Unix); después se sirve viejo y se refresca
+    fresh_until: u64,
+    value: T,
+}
+
+// Valor del L1 (del tipo con el que se calculó la clave)
+struct L1Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    expires: Instant,
+}
+
+// Cálculo en curso de una clave, compartido por los requests que la piden a la vez.
+// En el mapa se guarda como `dyn Any` (cada clave tiene su tipo) y se recupera con `Arc::downcast`
+type Flight<T> = Arc<OnceCell<T>>;
+
+// Resultado de pedir el lock de una clave
+enum Lock {
+    Acquired(u64),
+    // Lo tiene otra réplica (o otro task)
+    Busy,
+    // Redis no responde: se calcula sin lock
+    Unavailable,
+}
+
+// Mensaje de INVALIDATION_CHANNEL con las claves borradas
+fn invalidation_message<S: AsRef<str>>(keys: &[S]) -> String {
+    let keys: Vec<&str> = keys.iter().map(AsRef::as_ref).collect();
+    serde_json::to_string(&keys).unwrap_or_else(|_| INVALIDATE_ALL.to_string())
+}
+
+// UNLINK de un lote de claves y aviso a los L1 de todas las réplicas
+async fn unlink_keys<S: AsRef<str> + bb8_redis::redis::ToRedisArgs>(conn: &mut Connection, keys: &[S]) -> RedisResult<()> {
+    bb8_redis::redis::pipe()
+        .unlink(keys)
+        .ignore()
+        .publish(INVALIDATION_CHANNEL, invalidation_message(keys))
+        .ignore()
+        .query_async::<_, ()>(conn)
+        .await
+}
+
+// Borra las entradas de un tag; solo se sacan del set los miembros leídos
+// (una entrada registrada mientras tanto sigue en el tag)
+async fn unlink_tag(conn: &mut Connection, tag: &str) -> RedisResult<Vec<String>> {
+    let tag_key = format!("{TAG_PREFIX}{tag}");
+    let members: Vec<String> = conn.smembers(&tag_key).await?;
+    for batch in members.chunks(DELETE_BATCH) {
+        bb8_redis::redis::pipe()
+            .unlink(batch)
+            .ignore()
+            .srem(&tag_key, batch)
+            .ignore()
+            .publish(INVALIDATION_CHANNEL, invalidation_message(batch))
+            .ignore()
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+    }
+    Ok(members)
+}
+
+// Una iteración de SCAN sobre el patrón, borrando lo que trajo
+async fn unlink_pattern_page(conn: &mut Connection, pattern: &str, cursor: u64) -> RedisResult<(u64, Vec<String>)> {
+    let (next, keys): (u64, Vec<String>) = bb8_redis::redis::cmd("SCAN")
+        .arg(cursor)
+        .arg("MATCH")
+        .arg(pattern)
+        .arg("COUNT")
+        .arg(DELETE_BATCH)
+        .query_async(&mut *conn)
+        .await?;
+    if !keys.is_empty() {
+        unlink_keys(conn, &keys).await?;
+    }
+    Ok((next, keys))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+// Estado del backend, para /health/ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendState {
+    // URL inválida: el caché no hace nada
+    Disabled,
+    Closed,
+    Open,
+}
+
 // Estructura principal del caché
 #[derive(Clone)]
 pub struct Cache {
-    pool: RedisPool,
+    // None si la URL de Redis no es válida
+    pool: Option<RedisPool>,
+    breaker: Arc<Mutex<Breaker>>,
+    // Cálculos en curso por clave (single-flight dentro del proceso)
+    flights: Arc<Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>>,
+    // Claves que se están refrescando en segundo plano
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    l1: Arc<Mutex<LruCache<String, L1Entry>>>,
 }
 
 // Implementación de métodos para la estructura Cache
@@ -18,10 +227,11 @@ impl Cache {
     pub const TTL_5_MIN: Duration = Duration::from_secs(300); // 5 minutos
 
     //Patrones de claves
-    pub const KEY_BOOKS_LIST: &str = "books:list";
+    // Listados paginados: prefijo + query canónica (ver api::pagination::cache_suffix)
+    pub const KEY_BOOKS_LIST_PREFIX: &str = "books:list:";
     pub const KEY_BOOK_PREFIX: &str = "books:id:";
 
-    pub const KEY_AUTHORS_LIST: &str = "authors:list"; 
+    pub const KEY_AUTHORS_LIST_PREFIX: &str = "authors:list:";
     pub const KEY_AUTHOR_PREFIX: &str = "authors:id:";
     pub const KEY_AUTHOR_DETAILS_PREFIX: &str = "authors:details:";
 
@@ -30,13 +240,267 @@ impl Cache {
 
     pub const KEY_DASHBOARD_STATS: &str = "dashboard:stats";
 
-    // Inicialización del caché con la URL de Redis
-    // Argumenteos: redis_url: &str - URL de conexión a Redis
-    // Retorna: Result<Self, Box<dyn std::error::Error>> - Instancia de Cache o error
-    pub async fn new(redis_url: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let manager = RedisConnectionManager::new(redis_url)?;
-        let pool = Pool::builder().build(manager).await?;
-        Ok(Self { pool })
+    // Estadísticas de /api/stats/* (los top N llevan el límite como sufijo)
+    pub const KEY_STATS_PREFIX: &str = "stats:";
+    pub const KEY_STATS_AUTHORS: &str = "stats:authors";
+    pub const KEY_STATS_TOP_RATED_PREFIX: &str = "stats:top-rated:";
+    pub const KEY_STATS_TOP_SELLING_PREFIX: &str = "stats:top-selling:";
+
+    // Prefijos que definen el tag de familia de una clave
+    const FAMILY_PREFIXES: &[&str] = &[
+        Self::KEY_BOOKS_LIST_PREFIX,
+        Self::KEY_BOOK_PREFIX,
+        Self::KEY_AUTHORS_LIST_PREFIX,
+        Self::KEY_AUTHOR_PREFIX,
+        Self::KEY_AUTHOR_DETAILS_PREFIX,
+        Self::KEY_REVIEWS_PREFIX,
+        Self::KEY_SALES_PREFIX,
+        Self::KEY_DASHBOARD_STATS,
+        Self::KEY_STATS_PREFIX,
+    ];
+
+    // Tags de entidad
+    pub fn tag_book(id: i32) -> String {
+        format!("book:{id}")
+    }
+
+    pub fn tag_author(id: i32) -> String {
+        format!("author:{id}")
+    }
+
+    // Tag de familia de un prefijo de clave (`books:list:` => `books:list`)
+    pub fn family_tag(prefix: &str) -> String {
+        prefix.trim_end_matches(':').to_string()
+    }
+
+    // Tag de familia de una clave (None si no tiene un prefijo conocido)
+    fn family_of(key: &str) -> Option<String> {
+        Self::FAMILY_PREFIXES
+            .iter()
+            .find(|prefix| key.starts_with(*prefix))
+            .map(|prefix| Self::family_tag(prefix))
+    }
+
+    // Inicialización del caché con la URL de Redis (no se conecta hasta el primer uso)
+    // Argumentos: redis_url: &str - URL de conexión a Redis
+    // Retorna: Self - Con la URL inválida el caché queda deshabilitado (se loguea el error)
+    pub fn new(redis_url: &str) -> Self {
+        let pool = match RedisConnectionManager::new(redis_url) {
+            Ok(manager) => Some(Pool::builder().connection_timeout(CONNECT_TIMEOUT).build_unchecked(manager)),
+            Err(e) => {
+                error!(error = %e, "URL de Redis inválida; caché deshabilitado");
+                None
+            }
+        };
+        Self {
+            pool,
+            breaker: Arc::default(),
+            flights: Arc::default(),
+            refreshing: Arc::default(),
+            l1: Arc::new(Mutex::new(LruCache::new(L1_CAPACITY))),
+        }
+    }
+
+    // Escucha las invalidaciones de las otras réplicas (y las propias) para limpiar el L1.
+    // Se reconecta sola; al (re)suscribirse vacía el L1 porque pudo perder mensajes.
+    // Argumentos: redis_url: &str - La misma URL de `new`
+    pub fn listen_invalidations(&self, redis_url: &str) {
+        // Con la URL inválida `new` ya lo avisó
+        let Ok(client) = Client::open(redis_url) else {
+            return;
+        };
+        let cache = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match cache.subscribe(&client).await {
+                    Ok(()) => warn!("Se cortó la suscripción a invalidaciones del caché"),
+                    Err(e) => debug!(error = %e, "Sin suscripción a invalidaciones del caché"),
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        });
+    }
+
+    // Se suscribe y procesa mensajes hasta que se corta la conexión
+    async fn subscribe(&self, client: &Client) -> RedisResult<()> {
+        let connection = tokio::time::timeout(CONNECT_TIMEOUT, client.get_async_connection())
+            .await
+            .map_err(|_| RedisError::from((ErrorKind::IoError, "Redis no respondió a tiempo")))??;
+        let mut pubsub = connection.into_pubsub();
+        pubsub.subscribe(INVALIDATION_CHANNEL).await?;
+        self.clear_local();
+        info!(channel = INVALIDATION_CHANNEL, "Suscripto a invalidaciones del caché");
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let payload: String = message.get_payload()?;
+            if payload == INVALIDATE_ALL {
+                self.clear_local();
+                continue;
+            }
+            match serde_json::from_str::<Vec<String>>(&payload) {
+                Ok(keys) => self.evict_local(&keys),
+                Err(e) => warn!(error = %e, "Mensaje de invalidación inválido"),
+            }
+        }
+        Ok(())
+    }
+
+    // Estado actual del backend (un circuito ya vencido cuenta como cerrado: admite la prueba)
+    pub fn state(&self) -> BackendState {
+        if self.pool.is_none() {
+            return BackendState::Disabled;
+        }
+        match self.breaker.lock().unwrap().open_until {
+            Some(until) if Instant::now() < until => BackendState::Open,
+            _ => BackendState::Closed,
+        }
+    }
+
+    // --- CONEXIÓN Y CIRCUIT BREAKER ---
+
+    // Error para cuando no se intenta hablar con Redis (deshabilitado o circuito abierto)
+    fn unavailable(reason: &'static str) -> RedisError {
+        RedisError::from((ErrorKind::ClientError, "Redis no disponible", reason.to_string()))
+    }
+
+    // Conexión del pool si el circuit breaker lo permite
+    async fn conn(&self) -> RedisResult<PooledConnection<'_, RedisConnectionManager>> {
+        let pool = self.pool.as_ref().ok_or_else(|| Self::unavailable("caché deshabilitado"))?;
+        {
+            let mut breaker = self.breaker.lock().unwrap();
+            if let Some(until) = breaker.open_until {
+                let now = Instant::now();
+                if now < until || breaker.probing.is_some_and(|deadline| now < deadline) {
+                    return Err(Self::unavailable("circuit breaker abierto"));
+                }
+                breaker.probing = Some(now + PROBE_TIMEOUT);
+            }
+        }
+        pool.get().await.map_err(|e| {
+            self.failure();
+            RedisError::from((ErrorKind::IoError, "Pool connection error", e.to_string()))
+        })
+    }
+
+    // Corre un comando con una conexión del pool, con timeout, y actualiza el circuit breaker
+    // Argumentos: op - Recibe la conexión y devuelve el future del comando
+    // Retorna: RedisResult<T> - Resultado del comando o error (inmediato si el circuito está abierto)
+    async fn run<'a, T, F, Fut>(&'a self, op: F) -> RedisResult<T>
+    where
+        F: FnOnce(PooledConnection<'a, RedisConnectionManager>) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        let conn = self.conn().await?;
+        let result = tokio::time::timeout(COMMAND_TIMEOUT, op(conn))
+            .await
+            .unwrap_or_else(|_| Err(RedisError::from((ErrorKind::IoError, "Redis no respondió a tiempo"))));
+        match &result {
+            Err(e) if e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal() || e.is_timeout() => {
+                self.failure()
+            }
+            _ => self.success(),
+        }
+        result
+    }
+
+    fn failure(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.failures += 1;
+        // Una prueba fallida vuelve a abrir el circuito sin esperar al umbral
+        let reopen = breaker.probing.take().is_some();
+        if reopen || breaker.failures >= BREAKER_THRESHOLD {
+            if breaker.open_until.is_none() {
+                warn!(failures = breaker.failures, open_for_s = BREAKER_OPEN_FOR.as_secs(), "Redis no responde; circuit breaker abierto");
+            }
+            breaker.open_until = Some(Instant::now() + BREAKER_OPEN_FOR);
+        }
+    }
+
+    fn success(&self) {
+        let lost = {
+            let mut breaker = self.breaker.lock().unwrap();
+            if breaker.failures > 0 {
+                if breaker.open_until.is_some() {
+                    info!("Redis disponible de nuevo; circuit breaker cerrado");
+                }
+                breaker.failures = 0;
+                breaker.open_until = None;
+                breaker.probing = None;
+            }
+            if breaker.lost.is_empty() {
+                return;
+            }
+            // Se las lleva un solo task; las que vuelvan a fallar se anotan de nuevo
+            std::mem::take(&mut breaker.lost)
+        };
+        // En segundo plano: el request que encontró a Redis de vuelta no espera la recuperación
+        let cache = self.clone();
+        tokio::spawn(async move { cache.retry_lost(lost).await }.in_current_span());
+    }
+
+    // Reintenta las invalidaciones perdidas. Usa la conexión directamente (no `run`) para no
+    // volver a entrar en `success`
+    async fn retry_lost(&self, mut lost: Lost) {
+        let pending = lost.len();
+        let result = match self.conn().await {
+            Ok(mut conn) => {
+                let retry = async {
+                    let keys: Vec<&String> = lost.keys.iter().collect();
+                    for batch in keys.chunks(DELETE_BATCH) {
+                        unlink_keys(&mut conn, batch).await?;
+                    }
+                    lost.keys.clear();
+                    while let Some(tag) = lost.tags.iter().next().cloned() {
+                        let members = unlink_tag(&mut conn, &tag).await?;
+                        self.evict_local(&members);
+                        lost.tags.remove(&tag);
+                    }
+                    while let Some(pattern) = lost.patterns.iter().next().cloned() {
+                        let mut cursor = 0;
+                        loop {
+                            let (next, keys) = unlink_pattern_page(&mut conn, &pattern, cursor).await?;
+                            self.evict_local(&keys);
+                            if next == 0 {
+                                break;
+                            }
+                            cursor = next;
+                        }
+                        lost.patterns.remove(&pattern);
+                    }
+                    Ok(())
+                };
+                tokio::time::timeout(COMMAND_TIMEOUT, retry)
+                    .await
+                    .unwrap_or_else(|_| Err(RedisError::from((ErrorKind::IoError, "Redis no respondió a tiempo"))))
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => info!(pending, "Invalidaciones perdidas aplicadas en Redis"),
+            Err(e) => {
+                warn!(error = %e, remaining = lost.len(), "No se pudieron reintentar las invalidaciones perdidas");
+                self.breaker.lock().unwrap().lost.merge(lost);
+            }
+        }
+    }
+
+    // Anota una invalidación que no llegó a Redis. Las de tags y patrones no dicen qué claves
+    // tocaban, así que el L1 propio se vacía entero
+    fn record_lost(&self, record: impl FnOnce(&mut Lost)) {
+        let recorded = {
+            let mut breaker = self.breaker.lock().unwrap();
+            if breaker.lost.len() < LOST_CAPACITY {
+                record(&mut breaker.lost);
+                true
+            } else {
+                false
+            }
+        };
+        if !recorded {
+            warn!(capacity = LOST_CAPACITY, "Demasiadas invalidaciones pendientes; la entrada vence con su TTL");
+        }
+        self.clear_local();
     }
 
     // --- OPERACIONES BÁSICAS ---
@@ -45,25 +509,52 @@ impl Cache {
     // Argumentos: key: &str - Clave del valor a obtener
     // Retorna: RedisResult<T> - Valor obtenido o error
     pub async fn get<T: serde::de::DeserializeOwned>(&self, key: &str) -> RedisResult<T> {
-    let mut conn = self.pool.get().await.map_err(|e| {
-        RedisError::from((
-            bb8_redis::redis::ErrorKind::IoError,
-            "Pool connection error",
-            e.to_string(),
-        ))
-    })?;
-    
-    // Obtener como string y deserializar desde JSON
-    let json_str: Option<String> = conn.get(key).await?;
-    match json_str {
-        Some(json) => {
-            let value: T = serde_json::from_str(&json)
-                .map_err(|e| RedisError::from((bb8_redis::redis::ErrorKind::TypeError, "JSON deserialization error", e.to_string())))?;
-            Ok(value)
+        let result = self.get_json(key).await;
+        let outcome = match &result {
+            Ok(Some(_)) => CacheResult::Hit,
+            Ok(None) => CacheResult::Miss,
+            Err(e) => Self::read_error(key, e),
+        };
+        Self::record_read(key, outcome);
+        result?.ok_or_else(|| RedisError::from((ErrorKind::TypeError, "Key not found")))
+    }
+
+    // Resultado de una lectura fallida (y su log)
+    fn read_error(key: &str, e: &RedisError) -> CacheResult {
+        match e.kind() {
+            // Con el circuito abierto no se loguea cada lectura (ya se avisó al abrirlo)
+            ErrorKind::ClientError => CacheResult::Error,
+            // Formato viejo o JSON corrupto: se recalcula y se pisa
+            ErrorKind::TypeError => {
+                debug!(key, error = %e, "Entrada del caché ilegible");
+                CacheResult::Miss
+            }
+            _ => {
+                warn!(key, error = %e, "Error leyendo del caché");
+                CacheResult::Error
+            }
+        }
+    }
+
+    fn record_read(key: &str, outcome: CacheResult) {
+        debug!(key, result = outcome.as_str(), "Lectura del caché");
+        metrics::record_cache(key, outcome);
+        logging::record_cache(outcome);
+    }
+
+    // GET + JSON; Ok(None) si la clave no existe
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, key: &str) -> RedisResult<Option<T>> {
+        // Obtener como string y deserializar desde JSON
+        let json_str = self.run(|mut conn| async move { conn.get::<_, Option<String>>(key).await }).await?;
+        match json_str {
+            Some(json) => {
+                let value: T = serde_json::from_str(&json)
+                    .map_err(|e| RedisError::from((ErrorKind::TypeError, "JSON deserialization error", e.to_string())))?;
+                Ok(Some(value))
+            }
+            None => Ok(None)
         }
-        None => Err(RedisError::from((bb8_redis::redis::ErrorKind::TypeError, "Key not found")))
     }
-}
 
     // Establecer un valor en el caché (SET) con opción de TTL
     // Argumentos:
@@ -80,23 +571,53 @@ impl Cache {
         value: &T,  // ← Cambiar a referencia
         ttl: Option<Duration>
     ) -> RedisResult<()> {
-        let mut conn = self.pool.get().await.map_err(|e| {
-            RedisError::from((
-                bb8_redis::redis::ErrorKind::IoError,
-                "Pool connection error",
-                e.to_string(),
-            ))
-        })?;
-        
+        self.set_tagged(key, value, ttl, &[]).await
+    }
+
+    // SET + registro de la clave en sus tags (el de familia se agrega solo)
+    // Argumentos:
+    // - key, value, ttl: Igual que `set`
+    // - tags: &[String] - Tags de entidad (`Cache::tag_book`, `Cache::tag_author`)
+    // Retorna: RedisResult<()> - Resultado de la operación o error
+    pub async fn set_tagged<T: serde::Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Option<Duration>,
+        tags: &[String],
+    ) -> RedisResult<()> {
         // Serializar a JSON
         let json_str = serde_json::to_string(value)
-            .map_err(|e| RedisError::from((bb8_redis::redis::ErrorKind::TypeError, "JSON serialization error", e.to_string())))?;
-        
-        if let Some(ttl) = ttl {
-            conn.set_ex(key, json_str, ttl.as_secs()).await
-        } else {
-            conn.set(key, json_str).await
-        }
+            .map_err(|e| RedisError::from((ErrorKind::TypeError, "JSON serialization error", e.to_string())))?;
+        let tag_keys: Vec<String> = Self::family_of(key)
+            .into_iter()
+            .chain(tags.iter().cloned())
+            .map(|tag| format!("{TAG_PREFIX}{tag}"))
+            .collect();
+
+        self.run(|mut conn| async move {
+            let mut pipe = bb8_redis::redis::pipe();
+            match ttl {
+                Some(ttl) => pipe.set_ex(key, json_str, ttl.as_secs()).ignore(),
+                None => pipe.set(key, json_str).ignore(),
+            };
+            for tag_key in &tag_keys {
+                pipe.sadd(tag_key, key).ignore();
+                match ttl {
+                    // NX para un set nuevo, GT para no acortar la vida de las entradas anteriores
+                    Some(ttl) => {
+                        let secs = (ttl + TAG_TTL_SLACK).as_secs();
+                        pipe.cmd("EXPIRE").arg(tag_key).arg(secs).arg("NX").ignore();
+                        pipe.cmd("EXPIRE").arg(tag_key).arg(secs).arg("GT").ignore();
+                    }
+                    None => {
+                        pipe.persist(tag_key).ignore();
+                    }
+                }
+            }
+            pipe.query_async::<_, ()>(&mut *conn).await
+        })
+        .await
     }
 
 
@@ -104,51 +625,298 @@ impl Cache {
     // Argumentos: key: &str - Clave del valor a eliminar
     // Retorna: RedisResult<()> - Resultado de la operación o error
     pub async fn delete(&self, key: &str) -> RedisResult<()> {
-        let mut conn = self.pool.get().await.map_err(|e| {
-            RedisError::from((
-                bb8_redis::redis::ErrorKind::IoError,
-                "Pool connection error",
-                e.to_string(),
-            ))
-        })?;
-        conn.del(key).await
-    }
-
-    // Eliminar múltiples claves que coincidan con un patrón del caché (DEL con patrón)
-    // Argumentos: pattern: &str - Patrón para coincidir con las claves a eliminar
-    // Retorna: RedisResult<()> - Resultado de la operación o error
-    pub async fn delete_pattern(&self, pattern: &str) -> RedisResult<()> {
-        let mut conn = self.pool.get().await.map_err(|e| {
-            RedisError::from((
-                bb8_redis::redis::ErrorKind::IoError,
-                "Pool connection error",
-                e.to_string(),
-            ))
-        })?;
-        
-        // Obtener todas las claves que coinciden con el patrón
-        let keys: Vec<String> = conn.keys(pattern).await?;
-        
-        // Eliminar todas las claves encontradas
-        if !keys.is_empty() {
-            conn.del(keys).await?;
-        }
-        
-        Ok(())
+        self.evict_local(&[key]);
+        let result = self.run(|mut conn| async move { unlink_keys(&mut conn, &[key]).await }).await;
+        if result.is_err() {
+            self.record_lost(|lost| {
+                lost.keys.insert(key.to_string());
+            });
+        }
+        result
+    }
+
+    // Invalidar todas las entradas de un tag (SMEMBERS + UNLINK por lotes)
+    // Argumentos: tag: &str - Tag sin el prefijo `tag:` (`book:17`, `books:list`, ...)
+    // Retorna: RedisResult<usize> - Cantidad de claves eliminadas (incluye las ya vencidas)
+    pub async fn invalidate_tag(&self, tag: &str) -> RedisResult<usize> {
+        let result = self.run(|mut conn| async move { unlink_tag(&mut conn, tag).await }).await;
+        match result {
+            Ok(members) => {
+                self.evict_local(&members);
+                Ok(members.len())
+            }
+            Err(e) => {
+                self.record_lost(|lost| {
+                    lost.tags.insert(tag.to_string());
+                });
+                Err(e)
+            }
+        }
+    }
+
+    // Eliminar las claves que coincidan con un patrón (SCAN + UNLINK por lotes)
+    // Para patrones puntuales: las invalidaciones de la app usan tags. SCAN no bloquea a Redis
+    // como KEYS, pero igual recorre todo el keyspace.
+    // Argumentos: pattern: &str - Patrón glob de Redis (`books:id:*`)
+    // Retorna: RedisResult<usize> - Cantidad de claves eliminadas
+    pub async fn delete_pattern(&self, pattern: &str) -> RedisResult<usize> {
+        let mut cursor: u64 = 0;
+        let mut deleted = 0;
+        loop {
+            let batch = self
+                .run(|mut conn| async move { unlink_pattern_page(&mut conn, pattern, cursor).await })
+                .await;
+            let (next, keys) = match batch {
+                Ok(batch) => batch,
+                Err(e) => {
+                    // Se reintenta el patrón entero, también las páginas que ya se habían borrado
+                    self.record_lost(|lost| {
+                        lost.patterns.insert(pattern.to_string());
+                    });
+                    return Err(e);
+                }
+            };
+            self.evict_local(&keys);
+            deleted += keys.len();
+            if next == 0 {
+                return Ok(deleted);
+            }
+            cursor = next;
+        }
     }
 
     // Verificar si una clave existe en el caché (EXISTS)
     // Argumentos: key: &str - Clave a verificar
     // Retorna: RedisResult<bool> - true si existe, false si no, o error
     pub async fn exists(&self, key: &str) -> RedisResult<bool> {
-        let mut conn = self.pool.get().await.map_err(|e| {
-            RedisError::from((
-                bb8_redis::redis::ErrorKind::IoError,
-                "Pool connection error",
-                e.to_string(),
-            ))
-        })?;
-        conn.exists(key).await
+        self.run(|mut conn| async move { conn.exists(key).await }).await
+    }
+
+    // Verificar que Redis responde (PING), para /health/ready
+    // Retorna: RedisResult<()> - Ok si respondió PONG
+    pub async fn ping(&self) -> RedisResult<()> {
+        self.run(|mut conn| async move { bb8_redis::redis::cmd("PING").query_async::<_, ()>(&mut *conn).await })
+            .await
+    }
+
+    // --- GET OR COMPUTE ---
+
+    // Lee la clave o la calcula, con single-flight, soft TTL y jitter (ver arriba)
+    // Argumentos:
+    // - key: &str - Clave del caché
+    // - ttl: Duration - TTL fresco nominal (se le aplica jitter)
+    // - tags: FnOnce(&T) -> Vec<String> - Tags de entidad del valor calculado (`|_| Vec::new()` si no tiene)
+    // - compute: FnOnce() -> Future - Cálculo contra la BD; tiene que ser 'static para poder refrescar en segundo plano
+    // Retorna: Result<T, E> - Valor del caché o calculado; los errores del cálculo no se cachean
+    pub async fn get_or_compute<T, E, F, Fut, G>(&self, key: &str, ttl: Duration, tags: G, compute: F) -> Result<T, E>
+    where
+        T: Serialize + serde::de::DeserializeOwned + Clone + Send + Sync + 'static,
+        E: std::fmt::Display + Send + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        G: FnOnce(&T) -> Vec<String> + Send + 'static,
+    {
+        if let Some(value) = self.l1_get::<T>(key) {
+            Self::record_read(key, CacheResult::L1Hit);
+            return Ok(value);
+        }
+        let result = self.get_json::<Entry<T>>(key).await;
+        let outcome = match &result {
+            Ok(Some(entry)) if entry.fresh_until > now_ms() => CacheResult::Hit,
+            Ok(Some(_)) => CacheResult::Stale,
+            Ok(None) => CacheResult::Miss,
+            Err(e) => Self::read_error(key, e),
+        };
+        Self::record_read(key, outcome);
+        if let Ok(Some(entry)) = result {
+            if matches!(outcome, CacheResult::Stale) {
+                self.refresh_in_background(key, ttl, tags, compute());
+            } else {
+                let fresh_for = Duration::from_millis(entry.fresh_until.saturating_sub(now_ms()));
+                self.l1_put(key, Arc::new(entry.value.clone()), fresh_for);
+            }
+            return Ok(entry.value);
+        }
+
+        let flight = self.flight::<T>(key);
+        let result = flight.get_or_try_init(|| self.fill(key, ttl, tags, compute)).await.cloned();
+        self.land(key, &flight);
+        let value = result?;
+        self.l1_put(key, Arc::new(value.clone()), L1_TTL);
+        Ok(value)
+    }
+
+    // Cálculo de un miss (lo corre un solo request por clave en el proceso)
+    async fn fill<T, E, F, Fut, G>(&self, key: &str, ttl: Duration, tags: G, compute: F) -> Result<T, E>
+    where
+        T: Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        G: FnOnce(&T) -> Vec<String>,
+    {
+        let lock = self.acquire_lock(key).await;
+        if matches!(lock, Lock::Busy) {
+            if let Some(value) = self.wait_for::<T>(key).await {
+                return Ok(value);
+            }
+        }
+        let result = compute().await;
+        if let Ok(value) = &result {
+            self.store(key, ttl, value, &tags(value)).await;
+        }
+        if let Lock::Acquired(token) = lock {
+            self.release_lock(key, token).await;
+        }
+        result
+    }
+
+    // Recalcula una entrada vencida sin bloquear al request (uno por clave entre todas las réplicas)
+    fn refresh_in_background<T, E, Fut, G>(&self, key: &str, ttl: Duration, tags: G, compute: Fut)
+    where
+        T: Serialize + Send + Sync + 'static,
+        E: std::fmt::Display + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        G: FnOnce(&T) -> Vec<String> + Send + 'static,
+    {
+        if !self.refreshing.lock().unwrap().insert(key.to_string()) {
+            return;
+        }
+        let cache = self.clone();
+        let key = key.to_string();
+        let refresh = async move {
+            let lock = cache.acquire_lock(&key).await;
+            if !matches!(lock, Lock::Busy) {
+                match compute.await {
+                    Ok(value) => cache.store(&key, ttl, &value, &tags(&value)).await,
+                    Err(e) => warn!(key, error = %e, "Error refrescando entrada del caché"),
+                }
+                if let Lock::Acquired(token) = lock {
+                    cache.release_lock(&key, token).await;
+                }
+            }
+            cache.refreshing.lock().unwrap().remove(&key);
+        };
+        tokio::spawn(refresh.in_current_span());
+    }
+
+    // Guarda el valor con su soft TTL (con jitter) y STALE_GRACE más de vida en Redis
+    async fn store<T: Serialize + Send + Sync>(&self, key: &str, ttl: Duration, value: &T, tags: &[String]) {
+        let fresh_for = ttl.mul_f64(rand::thread_rng().gen_range(1.0 - TTL_JITTER..=1.0 + TTL_JITTER));
+        let entry = Entry { fresh_until: now_ms() + fresh_for.as_millis() as u64, value };
+        let _ = self.set_tagged(key, &entry, Some(fresh_for + STALE_GRACE), tags).await;
+    }
+
+    // Valor vigente del L1
+    fn l1_get<T: Clone + 'static>(&self, key: &str) -> Option<T> {
+        let mut l1 = self.l1.lock().unwrap();
+        let expired = l1.get(key)?.expires <= Instant::now();
+        if expired {
+            l1.remove(key);
+            return None;
+        }
+        let value = l1.get(key)?.value.downcast_ref::<T>().cloned();
+        if value.is_none() {
+            // Otro tipo guardado con la misma clave (error de programación): cuenta como miss
+            error!(key, "Tipo distinto para la misma clave del L1; se descarta la entrada");
+            l1.remove(key);
+        }
+        value
+    }
+
+    // Guarda en el L1 por L1_TTL como máximo
+    fn l1_put(&self, key: &str, value: Arc<dyn Any + Send + Sync>, fresh_for: Duration) {
+        let expires = Instant::now() + fresh_for.min(L1_TTL);
+        self.l1.lock().unwrap().insert(key.to_string(), L1Entry { value, expires });
+    }
+
+    fn evict_local<S: AsRef<str>>(&self, keys: &[S]) {
+        let mut l1 = self.l1.lock().unwrap();
+        for key in keys {
+            l1.remove(key.as_ref());
+        }
+    }
+
+    fn clear_local(&self) {
+        self.l1.lock().unwrap().clear();
+    }
+
+    // Una clave siempre se calcula con el mismo tipo; si no (error de programación) el request
+    // calcula por su cuenta en vez de compartir un valor que no puede leer
+    fn flight<T: Send + Sync + 'static>(&self, key: &str) -> Flight<T> {
+        let shared = self
+            .flights
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(OnceCell::<T>::new()))
+            .clone();
+        shared.downcast().unwrap_or_else(|_| {
+            error!(key, "La clave del caché ya se está calculando con otro tipo; se calcula sin compartir");
+            Arc::default()
+        })
+    }
+
+    // Saca el cálculo terminado (si no lo reemplazó otro después de un error)
+    fn land<T>(&self, key: &str, flight: &Flight<T>) {
+        let mut flights = self.flights.lock().unwrap();
+        if flights.get(key).is_some_and(|current| std::ptr::addr_eq(Arc::as_ptr(current), Arc::as_ptr(flight))) {
+            flights.remove(key);
+        }
+    }
+
+    async fn acquire_lock(&self, key: &str) -> Lock {
+        let lock_key = format!("{LOCK_PREFIX}{key}");
+        let token: u64 = rand::random();
+        let result = self
+            .run(|mut conn| async move {
+                bb8_redis::redis::cmd("SET")
+                    .arg(&lock_key)
+                    .arg(token)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(LOCK_TTL.as_millis() as u64)
+                    .query_async::<_, Option<String>>(&mut *conn)
+                    .await
+            })
+            .await;
+        match result {
+            Ok(Some(_)) => Lock::Acquired(token),
+            Ok(None) => Lock::Busy,
+            Err(_) => Lock::Unavailable,
+        }
+    }
+
+    async fn release_lock(&self, key: &str, token: u64) {
+        let lock_key = format!("{LOCK_PREFIX}{key}");
+        let result = self
+            .run(|mut conn| async move {
+                bb8_redis::redis::cmd("EVAL")
+                    .arg(RELEASE_LOCK_SCRIPT)
+                    .arg(1)
+                    .arg(&lock_key)
+                    .arg(token)
+                    .query_async::<_, i64>(&mut *conn)
+                    .await
+            })
+            .await;
+        // Si falla el lock vence solo (LOCK_TTL)
+        if let Err(e) = result {
+            debug!(key, error = %e, "No se pudo liberar el lock del caché");
+        }
+    }
+
+    // Espera a que la réplica que tiene el lock guarde el valor
+    async fn wait_for<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let deadline = Instant::now() + LOCK_WAIT;
+        while Instant::now() < deadline {
+            tokio::time::sleep(LOCK_POLL).await;
+            match self.get_json::<Entry<T>>(key).await {
+                Ok(Some(entry)) => return Some(entry.value),
+                Ok(None) => continue,
+                Err(_) => return None,
+            }
+        }
+        None
     }
 }
 
@@ -158,10 +926,10 @@ impl<'r> rocket::request::FromRequest<'r> for &'r Cache {
     type Error = ();
 
     async fn from_request(request: &'r rocket::Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
-        // Implementación mínima que funciona
+        // El fairing "Redis Cache" siempre lo registra, esté Redis arriba o no
         match request.guard::<&rocket::State<Cache>>().await {
             rocket::request::Outcome::Success(cache) => rocket::request::Outcome::Success(cache),
             _ => rocket::request::Outcome::Forward(rocket::http::Status::InternalServerError),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_db_pools::sqlx::{self, SqlitePool};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::cache::{BackendState, Cache};
+use crate::migrations;
+use crate::search::Search;
+use crate::Db;
+
+// Health checks:
+// - /health y /health/live: el proceso responde (no mira dependencias)
+// - /health/ready: revisa cada dependencia y responde 503 si falla una requerida
+//   (SQLite y migraciones) para que Traefik saque la réplica del balanceo.
+//   Redis y OpenSearch no son requeridos (la app lee de SQLite / usa el fallback de SQLite):
+//   si fallan el estado general queda `degraded` con 200.
+
+// Tiempo máximo por chequeo (un Redis caído tarda en rechazar la conexión del pool)
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+    Failed,
+    // Dependencia apagada por configuración (USE_OPENSEARCH=0)
+    Disabled,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub status: HealthStatus,
+    pub required: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: HealthStatus,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+impl Readiness {
+    // failed si falla algo requerido, degraded si algo más no está ok, ok si todo responde
+    fn from_checks(checks: BTreeMap<&'static str, Check>) -> Self {
+        let status = if checks.values().any(|c| c.required && c.status == HealthStatus::Failed) {
+            HealthStatus::Failed
+        } else if checks.values().any(|c| matches!(c.status, HealthStatus::Failed | HealthStatus::Degraded)) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Ok
+        };
+        Self { status, checks }
+    }
+}
+
+#[get("/health")]
+pub fn health() -> &'static str {
+    "ok"
+}
+
+#[get("/health/live")]
+pub fn live() -> &'static str {
+    "ok"
+}
+
+// GET /health/ready
+// 200 con status ok/degraded, 503 con status failed; `checks` trae el detalle por dependencia
+#[get("/health/ready")]
+pub async fn ready(pool: &State<Db>, cache: &Cache, search: &State<Search>) -> status::Custom<Json<Readiness>> {
+    let (sqlite, migrations, redis, opensearch) = tokio::join!(
+        check_sqlite(&pool.0),
+        check_migrations(&pool.0),
+        check_redis(cache),
+        check_opensearch(search),
+    );
+    let checks = BTreeMap::from([
+        ("sqlite", sqlite),
+        ("migrations", migrations),
+        ("redis", redis),
+        ("opensearch", opensearch),
+    ]);
+    let readiness = Readiness::from_checks(checks);
+    let code = if readiness.status == HealthStatus::Failed {
+        for (name, check) in readiness.checks.iter().filter(|(_, c)| c.status == HealthStatus::Failed) {
+            warn!(dependency = name, detail = check.detail.as_deref().unwrap_or_default(), "Readiness: dependencia caída");
+        }
+        Status::ServiceUnavailable
+    } else {
+        Status::Ok
+    };
+    status::Custom(code, Json(readiness))
+}
+
+// Corre un chequeo con timeout y mide su latencia
+// Argumentos:
+// - required: bool - Si su falla deja a la réplica fuera del balanceo
+// - check: Future que devuelve (estado, detalle) o Err con el motivo de la falla
+async fn timed<F>(required: bool, check: F) -> Check
+where
+    F: Future<Output = Result<(HealthStatus, Option<String>), String>>,
+{
+    let started = Instant::now();
+    let (status, detail) = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(Ok((status, detail))) => (status, detail),
+        Ok(Err(e)) => (HealthStatus::Failed, Some(e)),
+        Err(_) => (HealthStatus::Failed, Some(format!("sin respuesta en {} s", CHECK_TIMEOUT.as_secs()))),
+    };
+    Check { status, required, latency_ms: started.elapsed().as_secs_f64() * 1000.0, detail }
+}
+
+async fn check_sqlite(pool: &SqlitePool) -> Check {
+    timed(true, async {
+        sqlx::query("SELECT 1").execute(pool).await.map_err(|e| e.to_string())?;
+        Ok((HealthStatus::Ok, None))
+    })
+    .await
+}
+
+// La versión aplicada debe coincidir con la última migración de MIGRATIONS_DIR.
+// Una BD adelantada (otra réplica ya desplegó una versión nueva) queda degraded.
+async fn check_migrations(pool: &SqlitePool) -> Check {
+    timed(true, async {
+        let current = migrations::current_version(pool).await.map_err(|e| e.to_string())?;
+        let expected = migrations::load(&migrations::migrations_dir())?.last().map(|m| m.version);
+        let detail = format!(
+            "versión {} (última migración: {})",
+            current.map_or("-".to_string(), |v| v.to_string()),
+            expected.map_or("-".to_string(), |v| v.to_string())
+        );
+        match (current, expected) {
+            (Some(current), Some(expected)) if current > expected => Ok((HealthStatus::Degraded, Some(detail))),
+            (current, expected) if current == expected => Ok((HealthStatus::Ok, Some(detail))),
+            _ => Err(detail),
+        }
+    })
+    .await
+}
+
+// Con el circuit breaker abierto no se hace PING (fallaría al instante): se informa como failed
+async fn check_redis(cache: &Cache) -> Check {
+    timed(false, async {
+        match cache.state() {
+            BackendState::Disabled => Ok((HealthStatus::Disabled, Some("URL de Redis inválida".to_string()))),
+            BackendState::Open => Err("circuit breaker abierto; se lee de SQLite".to_string()),
+            BackendState::Closed => {
+                cache.ping().await.map_err(|e| e.to_string())?;
+                Ok((HealthStatus::Ok, None))
+            }
+        }
+    })
+    .await
+}
+
+// red => failed; yellow es normal con un solo nodo (réplicas sin asignar)
+async fn check_opensearch(search: &Search) -> Check {
+    timed(false, async {
+        if !search.enabled() {
+            return Ok((HealthStatus::Disabled, None));
+        }
+        match search.cluster_health().await.map_err(|e| e.to_string())?.as_str() {
+            "red" => Err("cluster en estado red".to_string()),
+            status => Ok((HealthStatus::Ok, Some(format!("cluster {status}")))),
+        }
+    })
+    .await
+}
@@ -0,0 +1,413 @@
+use rocket_db_pools::sqlx::{self, error::ErrorKind, SqliteConnection, SqlitePool};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::indexer::IndexOp;
+use crate::moderation::Moderation;
+use crate::models::{CreateAuthor, CreateBook, CreateReview, CreateYearlySales};
+use crate::validation::{self, FieldError, Validate};
+use crate::repository::{authors as authors_repo, books as books_repo, reviews as reviews_repo, sales as sales_repo};
+
+// Importación masiva (POST /api/import y `libro_reviews import`).
+// Todas las filas se insertan en UNA transacción: si alguna falla no se importa nada
+// y el reporte lista cada fila con su error. En dry run siempre se hace rollback.
+
+// Entidad que contiene el archivo (una por importación)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Entity {
+    Authors,
+    Books,
+    Reviews,
+    Sales,
+}
+
+impl Entity {
+    pub const ALL: [Self; 4] = [Self::Authors, Self::Books, Self::Reviews, Self::Sales];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|entity| entity.as_str() == value.to_ascii_lowercase())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Authors => "authors",
+            Self::Books => "books",
+            Self::Reviews => "reviews",
+            Self::Sales => "sales",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    // Con fila de encabezados; los nombres de columna son los campos del JSON
+    Csv,
+    // Un objeto JSON por línea
+    Ndjson,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+
+    // Formato según la extensión del archivo (CLI)
+    pub fn from_path(path: &str) -> Option<Self> {
+        path.rsplit_once('.').and_then(|(_, ext)| Self::parse(ext))
+    }
+}
+
+// Error de una fila; `line` es la línea del archivo (en CSV el encabezado es la línea 1)
+#[derive(Debug, Serialize)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub entity: Entity,
+    pub format: Format,
+    pub dry_run: bool,
+    // Filas leídas
+    pub total: usize,
+    // Filas sin errores (las que se importaron, o se importarían en dry run)
+    pub valid: usize,
+    // true solo si se confirmó la transacción
+    pub committed: bool,
+    pub errors: Vec<RowError>,
+    // Documentos a sincronizar con OpenSearch tras el commit
+    #[serde(skip)]
+    pub index_ops: Vec<IndexOp>,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    // El archivo completo es inválido (encabezado CSV ilegible, sin filas...)
+    Invalid(String),
+    // Error de la BD que no es de una fila en particular
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for ImportError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+// Libro a importar: el autor se indica por `author_id` o, si no viene, por `author_name`
+#[derive(Debug, Deserialize)]
+struct BookRow {
+    title: String,
+    summary: Option<String>,
+    publication_date: String,
+    author_id: Option<i32>,
+    author_name: Option<String>,
+}
+
+// Por qué no se pudo importar una fila
+enum Failure {
+    Row(String),
+    Fatal(sqlx::Error),
+}
+
+impl From<Vec<FieldError>> for Failure {
+    fn from(errors: Vec<FieldError>) -> Self {
+        Self::Row(validation::summary(&errors))
+    }
+}
+
+// Las violaciones de restricciones son culpa de la fila; el resto aborta la importación
+impl From<sqlx::Error> for Failure {
+    fn from(e: sqlx::Error) -> Self {
+        let kind = match &e {
+            sqlx::Error::Database(db) => db.kind(),
+            _ => return Self::Fatal(e),
+        };
+        match kind {
+            ErrorKind::ForeignKeyViolation => Self::Row("hace referencia a un registro inexistente".to_string()),
+            ErrorKind::UniqueViolation => Self::Row("registro duplicado".to_string()),
+            ErrorKind::NotNullViolation | ErrorKind::CheckViolation => Self::Row(format!("datos inválidos: {e}")),
+            _ => Self::Fatal(e),
+        }
+    }
+}
+
+trait ImportRow: DeserializeOwned {
+    // Valida e inserta la fila dentro de la transacción
+    // Argumentos: moderation: &Moderation - Reglas para el texto de las reseñas
+    // Retorna: operación de indexación a encolar si la importación se confirma
+    async fn insert(self, conn: &mut SqliteConnection, moderation: &Moderation) -> Result<Option<IndexOp>, Failure>;
+}
+
+impl ImportRow for CreateAuthor {
+    async fn insert(self, conn: &mut SqliteConnection, _moderation: &Moderation) -> Result<Option<IndexOp>, Failure> {
+        self.validate()?;
+        authors_repo::create_author(conn, &self).await?;
+        // Un autor nuevo no tiene libros indexados
+        Ok(None)
+    }
+}
+
+impl ImportRow for BookRow {
+    async fn insert(self, conn: &mut SqliteConnection, _moderation: &Moderation) -> Result<Option<IndexOp>, Failure> {
+        let author_id = match (self.author_id, self.author_name.as_deref().map(str::trim)) {
+            (Some(id), _) => id,
+            (None, Some(name)) if !name.is_empty() => {
+                match authors_repo::find_author_ids_by_name(&mut *conn, name).await?.as_slice() {
+                    [id] => *id,
+                    [] => return Err(Failure::Row(format!("no existe un autor llamado '{name}'"))),
+                    _ => return Err(Failure::Row(format!("hay varios autores llamados '{name}'; usar `author_id`"))),
+                }
+            }
+            _ => return Err(Failure::Row("falta `author_id` o `author_name`".to_string())),
+        };
+        let book = CreateBook {
+            title: self.title,
+            summary: self.summary.filter(|s| !s.trim().is_empty()),
+            publication_date: self.publication_date,
+            author_id,
+        };
+        book.validate()?;
+        let id = books_repo::create_book(conn, &book).await?;
+        Ok(Some(IndexOp::Book(id)))
+    }
+}
+
+impl ImportRow for CreateReview {
+    async fn insert(self, conn: &mut SqliteConnection, moderation: &Moderation) -> Result<Option<IndexOp>, Failure> {
+        self.validate()?;
+        // Los duplicados se buscan dentro de la transacción: también cuentan las filas anteriores del archivo
+        let verdict = moderation.review(&mut *conn, self.book_id, None, &self.review_text).await?;
+        // Sin usuario: como las reseñas anteriores al sistema de usuarios
+        let id = reviews_repo::create_review(conn, &self, None, &verdict).await?;
+        Ok(Some(IndexOp::Review(id)))
+    }
+}
+
+impl ImportRow for CreateYearlySales {
+    async fn insert(self, conn: &mut SqliteConnection, _moderation: &Moderation) -> Result<Option<IndexOp>, Failure> {
+        self.validate()?;
+        sales_repo::create_yearly_sales(conn, &self).await?;
+        // Los triggers cambian books.sales_count, que está en el índice
+        Ok(Some(IndexOp::Book(self.book_id)))
+    }
+}
+
+// Importa el contenido de un archivo
+// Argumentos:
+// - moderation: &Moderation - Moderación de las reseñas importadas (las no aprobadas se guardan igual)
+// - entity: Entity - Qué contiene el archivo
+// - format: Format - CSV o NDJSON
+// - input: &str - Contenido del archivo
+// - dry_run: bool - Valida e inserta dentro de la transacción pero siempre hace rollback
+// Retorna: Result<ImportReport, ImportError> - Reporte por fila (Err solo si falla el archivo entero o la BD)
+pub async fn run(pool: &SqlitePool, moderation: &Moderation, entity: Entity, format: Format, input: &str, dry_run: bool) -> Result<ImportReport, ImportError> {
+    match entity {
+        Entity::Authors => import_rows::<CreateAuthor>(pool, moderation, entity, format, input, dry_run).await,
+        Entity::Books => import_rows::<BookRow>(pool, moderation, entity, format, input, dry_run).await,
+        Entity::Reviews => import_rows::<CreateReview>(pool, moderation, entity, format, input, dry_run).await,
+        Entity::Sales => import_rows::<CreateYearlySales>(pool, moderation, entity, format, input, dry_run).await,
+    }
+}
+
+async fn import_rows<T: ImportRow>(
+    pool: &SqlitePool,
+    moderation: &Moderation,
+    entity: Entity,
+    format: Format,
+    input: &str,
+    dry_run: bool,
+) -> Result<ImportReport, ImportError> {
+    let rows = match format {
+        Format::Csv => parse_csv::<T>(input)?,
+        Format::Ndjson => parse_ndjson::<T>(input),
+    };
+    if rows.is_empty() {
+        return Err(ImportError::Invalid("El archivo no tiene filas".to_string()));
+    }
+
+    let mut report = ImportReport {
+        entity,
+        format,
+        dry_run,
+        total: rows.len(),
+        valid: 0,
+        committed: false,
+        errors: Vec::new(),
+        index_ops: Vec::new(),
+    };
+
+    let mut tx = pool.begin().await?;
+    for (line, row) in rows {
+        let outcome = match row {
+            Ok(row) => row.insert(&mut tx, moderation).await,
+            Err(message) => Err(Failure::Row(message)),
+        };
+        match outcome {
+            Ok(op) => {
+                report.valid += 1;
+                report.index_ops.extend(op);
+            }
+            Err(Failure::Row(message)) => report.errors.push(RowError { line, message }),
+            Err(Failure::Fatal(e)) => return Err(ImportError::Database(e)),
+        }
+    }
+
+    if dry_run || !report.errors.is_empty() {
+        tx.rollback().await?;
+        report.index_ops.clear();
+    } else {
+        tx.commit().await?;
+        report.committed = true;
+    }
+    info!(
+        entity = entity.as_str(),
+        total = report.total,
+        valid = report.valid,
+        errors = report.errors.len(),
+        committed = report.committed,
+        "Importación terminada"
+    );
+    Ok(report)
+}
+
+// Cada fila junto a su número de línea; las que no se pueden leer quedan como Err
+type ParsedRows<T> = Vec<(u64, Result<T, String>)>;
+
+fn parse_csv<T: DeserializeOwned>(input: &str) -> Result<ParsedRows<T>, ImportError> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| ImportError::Invalid(format!("Encabezado CSV inválido: {e}")))?
+        .clone();
+
+    let rows = reader
+        .records()
+        .map(|record| match record {
+            Ok(record) => {
+                let line = record.position().map(|p| p.line()).unwrap_or_default();
+                (line, record.deserialize::<T>(Some(&headers)).map_err(|e| csv_message(&e)))
+            }
+            Err(e) => (e.position().map(|p| p.line()).unwrap_or_default(), Err(csv_message(&e))),
+        })
+        .collect();
+    Ok(rows)
+}
+
+// Sin el prefijo de posición que agrega csv (la línea ya va en el reporte)
+fn csv_message(e: &csv::Error) -> String {
+    match e.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => match err.field() {
+            Some(field) => format!("columna {}: {}", field + 1, err.kind()),
+            None => err.kind().to_string(),
+        },
+        _ => e.to_string(),
+    }
+}
+
+fn parse_ndjson<T: DeserializeOwned>(input: &str) -> ParsedRows<T> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i as u64 + 1, serde_json::from_str::<T>(line).map_err(|e| e.to_string())))
+        .collect()
+}
+
+// `libro_reviews import <authors|books|reviews|sales> <archivo> [--format csv|ndjson] [--dry-run]`
+// Importa sin levantar el servidor, usando la BD y Redis de Rocket.toml / ROCKET_*.
+// Aplica las migraciones pendientes antes de importar, igual que al arrancar el servidor.
+// Por stdout sale solo el reporte (resumen y filas con error); el resto va al log (stderr).
+// Retorna: Result<(), String> - Err si la importación no se confirmó (sale con código 1)
+pub async fn cli(args: &[String], database_url: &str, redis_url: &str) -> Result<(), String> {
+    const USAGE: &str = "Uso: libro_reviews import <authors|books|reviews|sales> <archivo> [--format csv|ndjson] [--dry-run]";
+
+    let mut positional = Vec::new();
+    let mut format = None;
+    let mut dry_run = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--format" => {
+                let value = args.next().ok_or(USAGE)?;
+                format = Some(Format::parse(value).ok_or_else(|| format!("Formato inválido: {value}"))?);
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let [entity, path] = positional.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+    let entity = Entity::parse(entity).ok_or_else(|| format!("Entidad inválida: {entity}\n{USAGE}"))?;
+    let format = format
+        .or_else(|| Format::from_path(path))
+        .ok_or_else(|| format!("No se puede deducir el formato de {path}; usar --format"))?;
+    let input = std::fs::read_to_string(path).map_err(|e| format!("No se pudo leer {path}: {e}"))?;
+    let moderation = Moderation::from_env()?;
+
+    let pool = SqlitePool::connect(database_url).await.map_err(|e| e.to_string())?;
+    crate::migrations::run(&pool, &crate::migrations::migrations_dir()).await?;
+
+    let plan = crate::invalidation::InvalidationPlan::prepare(&pool, crate::invalidation::Mutation::Import).await;
+    let mut report = run(&pool, &moderation, entity, format, &input, dry_run).await.map_err(|e| match e {
+        ImportError::Invalid(message) => message,
+        ImportError::Database(e) => format!("Error de base de datos: {e}"),
+    })?;
+
+    print_report(&report);
+    if report.committed {
+        // Sin Redis la importación igual vale: el caché vence por TTL
+        let cache = crate::cache::Cache::new(redis_url);
+        if let Err(e) = cache.ping().await {
+            warn!(error = %e, "Redis no disponible, no se invalidó el caché");
+        } else {
+            plan.purge(&cache).await;
+        }
+        // Write-through como POST /api/import, pero sin cola: se indexa antes de salir
+        if !report.index_ops.is_empty() {
+            match crate::search::Search::from_env() {
+                Ok(search) if search.enabled() => {
+                    let ops = std::mem::take(&mut report.index_ops);
+                    let total = ops.len();
+                    let failed = crate::indexer::apply_all(&search, &pool, ops).await;
+                    if failed > 0 {
+                        warn!(failed, "Quedaron documentos sin indexar; ejecutar POST /api/admin/reindex");
+                    } else {
+                        info!(operations = total, "OpenSearch actualizado");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "No se pudo inicializar Search; ejecutar POST /api/admin/reindex"),
+            }
+        }
+        Ok(())
+    } else if dry_run && report.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} filas con errores; no se importó ninguna", report.errors.len()))
+    }
+}
+
+// Reporte final de la CLI (stdout): una línea de resumen y una por fila con error
+fn print_report(report: &ImportReport) {
+    let outcome = if report.committed {
+        "importadas"
+    } else if report.dry_run && report.errors.is_empty() {
+        "válidas (dry run, no se guardó nada)"
+    } else {
+        "válidas; no se importó ninguna"
+    };
+    println!("{}: {} filas leídas, {} {outcome}", report.entity.as_str(), report.total, report.valid);
+    for error in &report.errors {
+        println!("línea {:>5}: {}", error.line, error.message);
+    }
+}
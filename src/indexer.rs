@@ -0,0 +1,195 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use anyhow::Result;
+use rocket_db_pools::sqlx::SqlitePool;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::repository::{books as books_repo, reviews as reviews_repo};
+use crate::search::Search;
+
+// Máximo de operaciones pendientes; si OpenSearch está caído por mucho tiempo
+// se descartan las más antiguas (POST /api/admin/reindex las recupera)
+const MAX_PENDING: usize = 10_000;
+// Backoff exponencial entre reintentos: 1s, 2s, 4s ... hasta 60s
+const RETRY_BASE: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+// Cada cuánto revisa la cola de reintentos si no llegan operaciones nuevas
+const IDLE_TICK: Duration = Duration::from_secs(1);
+
+// Operación de sincronización SQLite -> OpenSearch.
+// Son idempotentes: el worker lee el estado ACTUAL de la BD al procesarlas,
+// así un reintento nunca indexa datos viejos y si la fila ya no existe se borra el documento.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexOp {
+    // Libro (con autor desnormalizado, rating y `suggest`); si no existe se borra junto a sus reseñas
+    Book(i32),
+    // Todos los libros de un autor (cambió el nombre desnormalizado)
+    Author(i32),
+    // Reseña aprobada; también se re-sincroniza su libro (rating promedio).
+    // Si no existe o no está aprobada se borra del índice
+    Review(i32),
+}
+
+#[derive(Debug)]
+struct Pending {
+    op: IndexOp,
+    attempts: u32,
+    due: Instant,
+}
+
+// Cola de indexación write-through. Los handlers encolan DESPUÉS de escribir en SQLite
+// y nunca esperan a OpenSearch: un error de búsqueda jamás hace fallar la escritura.
+pub struct Indexer {
+    tx: Option<UnboundedSender<IndexOp>>,
+}
+
+impl Indexer {
+    // Crea el indexador y lanza el worker en segundo plano.
+    // Argumentos:
+    // - search: Search - Cliente de OpenSearch (si está deshabilitado no se lanza el worker)
+    // - pool: SqlitePool - Pool para leer el estado actual de las filas
+    // Retorna: Indexer - Handle para encolar operaciones
+    pub fn spawn(search: Search, pool: SqlitePool) -> Self {
+        if !search.enabled() {
+            return Self { tx: None };
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(worker(search, pool, rx));
+        Self { tx: Some(tx) }
+    }
+
+    pub fn enqueue(&self, op: IndexOp) {
+        if let Some(tx) = &self.tx {
+            if tx.send(op).is_err() {
+                warn!(?op, "Worker de indexación detenido; se descarta la operación");
+            }
+        }
+    }
+
+    pub fn enqueue_all(&self, ops: impl IntoIterator<Item = IndexOp>) {
+        for op in ops {
+            self.enqueue(op);
+        }
+    }
+}
+
+async fn worker(search: Search, pool: SqlitePool, mut rx: UnboundedReceiver<IndexOp>) {
+    let mut queue: VecDeque<Pending> = VecDeque::new();
+
+    loop {
+        // Esperar una operación nueva o el siguiente tick para revisar reintentos
+        match tokio::time::timeout(IDLE_TICK, rx.recv()).await {
+            Ok(Some(op)) => push(&mut queue, op),
+            Ok(None) => break, // Rocket se apagó
+            Err(_) => {}
+        }
+        while let Ok(op) = rx.try_recv() {
+            push(&mut queue, op);
+        }
+
+        let now = Instant::now();
+        for _ in 0..queue.len() {
+            let Some(mut pending) = queue.pop_front() else { break };
+            if pending.due > now {
+                queue.push_back(pending);
+                continue;
+            }
+            match apply(&search, &pool, pending.op).await {
+                Ok(follow_up) => {
+                    for op in follow_up {
+                        push(&mut queue, op);
+                    }
+                }
+                Err(e) => {
+                    pending.attempts += 1;
+                    let backoff = RETRY_BASE
+                        .saturating_mul(2u32.saturating_pow(pending.attempts - 1))
+                        .min(RETRY_MAX);
+                    warn!(
+                        op = ?pending.op,
+                        attempt = pending.attempts,
+                        retry_in_s = backoff.as_secs(),
+                        error = %e,
+                        "Indexación fallida, se reintenta"
+                    );
+                    pending.due = Instant::now() + backoff;
+                    queue.push_back(pending);
+                }
+            }
+        }
+    }
+}
+
+// Aplica las operaciones en el momento, sin cola ni reintentos (CLI de importación:
+// el proceso termina enseguida y no hay worker que las procese después)
+// Argumentos:
+// - search: &Search - Cliente de OpenSearch (habilitado)
+// - pool: &SqlitePool - Pool para leer el estado actual de las filas
+// - ops: IntoIterator<Item = IndexOp> - Operaciones a aplicar (más las derivadas)
+// Retorna: usize - Operaciones que fallaron (POST /api/admin/reindex las recupera)
+pub async fn apply_all(search: &Search, pool: &SqlitePool, ops: impl IntoIterator<Item = IndexOp>) -> usize {
+    let mut queue: VecDeque<IndexOp> = ops.into_iter().collect();
+    let mut done = HashSet::new();
+    let mut failed = 0;
+    while let Some(op) = queue.pop_front() {
+        if !done.insert(op) {
+            continue;
+        }
+        match apply(search, pool, op).await {
+            Ok(follow_up) => queue.extend(follow_up),
+            Err(e) => {
+                warn!(?op, error = %e, "Indexación fallida");
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+// Encola sin duplicar: si la misma operación ya está pendiente basta con una
+fn push(queue: &mut VecDeque<Pending>, op: IndexOp) {
+    if queue.iter().any(|p| p.op == op) {
+        return;
+    }
+    if queue.len() >= MAX_PENDING {
+        if let Some(dropped) = queue.pop_front() {
+            warn!(op = ?dropped.op, "Cola de indexación llena; se descarta la operación");
+        }
+    }
+    queue.push_back(Pending { op, attempts: 0, due: Instant::now() });
+}
+
+// Aplica una operación. Devuelve operaciones derivadas a encolar.
+async fn apply(search: &Search, pool: &SqlitePool, op: IndexOp) -> Result<Vec<IndexOp>> {
+    match op {
+        IndexOp::Book(id) => {
+            match books_repo::get_book_by_id(pool, id).await? {
+                Some(book) => {
+                    let rating = reviews_repo::get_average_rating(pool, id).await?;
+                    search.index_book(&book, rating).await?;
+                }
+                None => search.delete_book(id).await?,
+            }
+            Ok(vec![])
+        }
+        IndexOp::Author(id) => {
+            let book_ids = books_repo::get_book_ids_by_author(pool, id).await?;
+            Ok(book_ids.into_iter().map(IndexOp::Book).collect())
+        }
+        IndexOp::Review(id) => match reviews_repo::get_review_by_id(pool, id).await? {
+            Some(review) => {
+                search.index_review(&review).await?;
+                Ok(vec![IndexOp::Book(review.book_id)])
+            }
+            None => {
+                search.delete_review(id).await?;
+                // Pendiente/rechazada: el rating del libro ya no la incluye
+                let book_id = reviews_repo::get_review_book_id(pool, id).await?;
+                Ok(book_id.map(IndexOp::Book).into_iter().collect())
+            }
+        },
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::BTreeSet;
+
+use rocket_db_pools::sqlx::{self, SqlitePool};
+use tracing::{debug, warn};
+
+use crate::cache::Cache;
+use crate::repository::{books as books_repo, reviews as reviews_repo, sales as sales_repo};
+
+// Mutaciones que pueden dejar datos obsoletos en el caché.
+// Cada variante lleva los datos que el handler ya conoce; el resto de las
+// dependencias (libros de un autor, autor de un libro, etc.) se resuelven en BD.
+#[derive(Debug, Clone, Copy)]
+pub enum Mutation {
+    CreateAuthor,
+    UpdateAuthor(i32),
+    DeleteAuthor(i32),
+
+    CreateBook { author_id: i32 },
+    UpdateBook { id: i32, new_author_id: Option<i32> },
+    DeleteBook(i32),
+
+    CreateReview { book_id: i32 },
+    UpdateReview(i32),
+    DeleteReview(i32),
+
+    CreateSales { book_id: i32 },
+    UpdateSales(i32),
+    DeleteSales(i32),
+    // Recalculo masivo de books.sales_count (POST /api/admin/sales/recompute)
+    RecomputeSales,
+    // Importación masiva (POST /api/import): puede tocar cualquier entidad
+    Import,
+}
+
+// Conjunto de claves y tags a purgar una vez que la escritura se confirmó en BD.
+// Los listados paginados tienen una clave por combinación de filtros/página,
+// por eso se invalidan por su tag de familia (`books:list`); las entradas que embeben
+// un libro o un autor llevan su tag de entidad (`book:17`, `author:3`, ver cache.rs).
+#[derive(Debug, Default)]
+pub struct InvalidationPlan {
+    keys: BTreeSet<String>,
+    tags: BTreeSet<String>,
+}
+
+impl InvalidationPlan {
+    // Construye el plan ANTES de escribir en la BD, para capturar relaciones
+    // que desaparecen con la mutación (p.ej. el autor de un libro que se elimina).
+    // Argumentos:
+    // - pool: &SqlitePool - Pool de conexiones a la BD
+    // - mutation: Mutation - Mutación que se va a ejecutar
+    // Retorna: InvalidationPlan - Claves afectadas (si falla una consulta se conservan las directas)
+    pub async fn prepare(pool: &SqlitePool, mutation: Mutation) -> Self {
+        let mut plan = Self::default();
+        if let Err(e) = plan.collect(pool, mutation).await {
+            warn!(?mutation, error = %e, "No se pudieron resolver dependencias de caché");
+        }
+        plan
+    }
+
+    // Claves que se van a purgar (ordenadas, sin duplicados)
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(String::as_str)
+    }
+
+    // Elimina las claves del caché. Llamar SOLO después de que la escritura fue exitosa.
+    // Argumentos: cache: &Cache - Caché (si Redis no responde, lo que no se purgó se reintenta al reconectar;
+    //             pasado el tope de pendientes la entrada vence con su TTL)
+    pub async fn purge(self, cache: &Cache) {
+        for key in self.keys() {
+            if let Err(e) = cache.delete(key).await {
+                warn!(key = %key, error = %e, "Error invalidando clave de caché");
+            }
+        }
+        for tag in &self.tags {
+            if let Err(e) = cache.invalidate_tag(tag).await {
+                warn!(tag = %tag, error = %e, "Error invalidando tag de caché");
+            }
+        }
+        debug!(keys = self.keys.len(), tags = self.tags.len(), "Caché invalidado");
+    }
+
+    async fn collect(&mut self, pool: &SqlitePool, mutation: Mutation) -> Result<(), sqlx::Error> {
+        match mutation {
+            Mutation::CreateAuthor => {
+                self.authors_lists();
+                self.aggregates();
+            }
+            Mutation::UpdateAuthor(id) => {
+                // El tag del autor cubre sus libros (embeben al autor)
+                self.author(id);
+                self.books_lists();
+            }
+            Mutation::DeleteAuthor(id) => {
+                self.author(id);
+                // Borrado en cascada de libros, reseñas y ventas
+                for book_id in books_repo::get_book_ids_by_author(pool, id).await? {
+                    self.book(book_id);
+                }
+            }
+
+            Mutation::CreateBook { author_id } => {
+                self.books_lists();
+                self.aggregates();
+                self.add(format!("{}{}", Cache::KEY_AUTHOR_DETAILS_PREFIX, author_id));
+            }
+            // El detalle del autor anterior lleva el tag del libro; el del nuevo autor todavía no
+            Mutation::UpdateBook { id, new_author_id } => {
+                self.book(id);
+                if let Some(author_id) = new_author_id {
+                    self.add(format!("{}{}", Cache::KEY_AUTHOR_DETAILS_PREFIX, author_id));
+                }
+            }
+            Mutation::DeleteBook(id) => self.book(id),
+
+            Mutation::CreateReview { book_id } => self.reviews_of(book_id),
+            Mutation::UpdateReview(id) | Mutation::DeleteReview(id) => {
+                if let Some(book_id) = reviews_repo::get_review_book_id(pool, id).await? {
+                    self.reviews_of(book_id);
+                }
+            }
+
+            Mutation::CreateSales { book_id } => self.sales_of(book_id),
+            Mutation::UpdateSales(id) | Mutation::DeleteSales(id) => {
+                if let Some(book_id) = sales_repo::get_yearly_sales_book_id(pool, id).await? {
+                    self.sales_of(book_id);
+                }
+            }
+            Mutation::RecomputeSales => {
+                self.books_lists();
+                self.authors_lists();
+                self.family(Cache::KEY_BOOK_PREFIX);
+                self.aggregates();
+            }
+            Mutation::Import => {
+                self.books_lists();
+                self.authors_lists();
+                self.aggregates();
+                for prefix in [
+                    Cache::KEY_BOOK_PREFIX,
+                    Cache::KEY_AUTHOR_PREFIX,
+                    Cache::KEY_AUTHOR_DETAILS_PREFIX,
+                    Cache::KEY_REVIEWS_PREFIX,
+                    Cache::KEY_SALES_PREFIX,
+                ] {
+                    self.family(prefix);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, key: impl Into<String>) {
+        self.keys.insert(key.into());
+    }
+
+    // Todas las claves con un prefijo (tag de familia)
+    fn family(&mut self, prefix: &str) {
+        self.tags.insert(Cache::family_tag(prefix));
+    }
+
+    // Todas las páginas/filtros del listado de libros
+    fn books_lists(&mut self) {
+        self.family(Cache::KEY_BOOKS_LIST_PREFIX);
+    }
+
+    // Todas las páginas/filtros del listado de autores
+    fn authors_lists(&mut self) {
+        self.family(Cache::KEY_AUTHORS_LIST_PREFIX);
+    }
+
+    // Dashboard y estadísticas de /api/stats/* (dependen de casi todas las tablas)
+    fn aggregates(&mut self) {
+        self.add(Cache::KEY_DASHBOARD_STATS);
+        self.family(Cache::KEY_STATS_PREFIX);
+    }
+
+    // Entradas de un autor y las que lo embeben (las estadísticas incluyen su nombre)
+    fn author(&mut self, id: i32) {
+        self.aggregates();
+        self.authors_lists();
+        self.tags.insert(Cache::tag_author(id));
+    }
+
+    // Entradas de un libro y las que lo embeben: reseñas, ventas y el detalle de su autor
+    // (las estadísticas incluyen el título del libro)
+    fn book(&mut self, id: i32) {
+        self.aggregates();
+        self.books_lists();
+        self.tags.insert(Cache::tag_book(id));
+    }
+
+    fn reviews_of(&mut self, book_id: i32) {
+        self.add(format!("{}{}", Cache::KEY_REVIEWS_PREFIX, book_id));
+        self.aggregates();
+        // Los listados filtran/ordenan por rating promedio
+        self.books_lists();
+        self.authors_lists();
+    }
+
+    // Los triggers de ventas actualizan books.sales_count: también cambian el libro y los listados
+    fn sales_of(&mut self, book_id: i32) {
+        self.add(format!("{}{}", Cache::KEY_SALES_PREFIX, book_id));
+        self.add(format!("{}{}", Cache::KEY_BOOK_PREFIX, book_id));
+        self.aggregates();
+        self.books_lists();
+        self.authors_lists();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket_db_pools::sqlx::sqlite::SqlitePoolOptions;
+
+    async fn pool(schema: bool) -> SqlitePool {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        if schema {
+            for sql in [
+                "CREATE TABLE books (id INTEGER PRIMARY KEY, author_id INTEGER)",
+                "CREATE TABLE reviews (id INTEGER PRIMARY KEY, book_id INTEGER)",
+                "CREATE TABLE yearly_sales (id INTEGER PRIMARY KEY, book_id INTEGER)",
+                "INSERT INTO books (id, author_id) VALUES (10, 1), (11, 1), (12, 2)",
+                "INSERT INTO reviews (id, book_id) VALUES (100, 11)",
+                "INSERT INTO yearly_sales (id, book_id) VALUES (200, 12)",
+            ] {
+                sqlx::query(sql).execute(&pool).await.unwrap();
+            }
+        }
+        pool
+    }
+
+    fn keys(plan: &InvalidationPlan) -> Vec<&str> {
+        plan.keys().collect()
+    }
+
+    fn tags(plan: &InvalidationPlan) -> Vec<&str> {
+        plan.tags.iter().map(String::as_str).collect()
+    }
+
+    #[rocket::async_test]
+    async fn author_mutations() {
+        let pool = pool(true).await;
+
+        let plan = InvalidationPlan::prepare(&pool, Mutation::UpdateAuthor(1)).await;
+        assert_eq!(keys(&plan), ["dashboard:stats"]);
+        assert_eq!(tags(&plan), ["author:1", "authors:list", "books:list", "stats"]);
+
+        // El borrado en cascada alcanza a los libros del autor
+        let plan = InvalidationPlan::prepare(&pool, Mutation::DeleteAuthor(1)).await;
+        assert_eq!(tags(&plan), ["author:1", "authors:list", "book:10", "book:11", "books:list", "stats"]);
+    }
+
+    #[rocket::async_test]
+    async fn book_mutations() {
+        let pool = pool(true).await;
+
+        let plan = InvalidationPlan::prepare(&pool, Mutation::CreateBook { author_id: 2 }).await;
+        assert_eq!(keys(&plan), ["authors:details:2", "dashboard:stats"]);
+        assert_eq!(tags(&plan), ["books:list", "stats"]);
+
+        let plan = InvalidationPlan::prepare(&pool, Mutation::UpdateBook { id: 10, new_author_id: Some(2) }).await;
+        assert_eq!(keys(&plan), ["authors:details:2", "dashboard:stats"]);
+        assert_eq!(tags(&plan), ["book:10", "books:list", "stats"]);
+    }
+
+    #[rocket::async_test]
+    async fn review_and_sales_mutations_resolve_their_book() {
+        let pool = pool(true).await;
+
+        let plan = InvalidationPlan::prepare(&pool, Mutation::DeleteReview(100)).await;
+        assert_eq!(keys(&plan), ["dashboard:stats", "reviews:book:11"]);
+        assert_eq!(tags(&plan), ["authors:list", "books:list", "stats"]);
+
+        let plan = InvalidationPlan::prepare(&pool, Mutation::UpdateSales(200)).await;
+        assert_eq!(keys(&plan), ["books:id:12", "dashboard:stats", "sales:book:12"]);
+
+        // Registro inexistente: no hay nada que purgar
+        let plan = InvalidationPlan::prepare(&pool, Mutation::UpdateReview(999)).await;
+        assert!(keys(&plan).is_empty() && tags(&plan).is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn bulk_mutations_use_family_tags() {
+        let pool = pool(false).await;
+
+        let plan = InvalidationPlan::prepare(&pool, Mutation::RecomputeSales).await;
+        assert_eq!(tags(&plan), ["authors:list", "books:id", "books:list", "stats"]);
+
+        let plan = InvalidationPlan::prepare(&pool, Mutation::Import).await;
+        assert_eq!(keys(&plan), ["dashboard:stats"]);
+        assert_eq!(
+            tags(&plan),
+            ["authors:details", "authors:id", "authors:list", "books:id", "books:list", "reviews:book", "sales:book", "stats"]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn failed_lookup_keeps_direct_keys() {
+        let pool = pool(false).await;
+        let plan = InvalidationPlan::prepare(&pool, Mutation::DeleteAuthor(1)).await;
+        assert_eq!(keys(&plan), ["dashboard:stats"]);
+        assert_eq!(tags(&plan), ["author:1", "authors:list", "stats"]);
+    }
+}
@@ -0,0 +1,186 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::figment::Figment;
+use rocket::http::Header;
+use rocket::route::{self, Handler, Route};
+use rocket::{Data, Request, Response};
+use tracing::Instrument;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
+
+use crate::metrics::CacheResult;
+
+// Logs estructurados: una línea JSON por evento (timestamp, level, target, campos y el span
+// `request` con request_id/method/route). Cada request tiene un id (header X-Request-Id, o uno
+// nuevo si no viene o es inválido) que se devuelve en la respuesta, acompaña a los eventos del
+// handler (caché, OpenSearch, errores) y se envía a OpenSearch como X-Opaque-Id.
+// Al terminar cada request se emite una línea de acceso (target "access") con ruta, status,
+// latencia y hits/misses del caché.
+//
+// Configuración (Rocket.toml o ROCKET_*):
+// - log_level: el de Rocket (off | critical | normal | debug); normal => info
+// - log_filter: directivas de tracing que reemplazan a log_level (p. ej. "info,libro_reviews::cache=debug")
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// Datos del request en curso, compartidos entre el fairing y el handler
+pub struct RequestContext {
+    pub id: String,
+    started: Instant,
+    cache_hits: AtomicU32,
+    cache_misses: AtomicU32,
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<RequestContext>;
+}
+
+// Cache local del Request (se crea en on_request)
+struct ContextSlot(Option<Arc<RequestContext>>);
+
+// Instala el subscriber JSON (también recibe los logs de Rocket y sqlx vía `log`)
+// Argumentos:
+// - figment: &Figment - Configuración de Rocket (Rocket.toml + ROCKET_*)
+// - cli: bool - Comandos de consola (`import`, `--dry-run`): los logs van a stderr y stdout queda para su reporte
+// Retorna: Result<(), String> - Err si `log_filter` no es válido o ya había un subscriber
+pub fn init(figment: &Figment, cli: bool) -> Result<(), String> {
+    let directives = match figment.extract_inner::<String>("log_filter") {
+        Ok(filter) => filter,
+        Err(_) => {
+            let level = figment.extract_inner::<String>("log_level").unwrap_or_else(|_| "normal".to_string());
+            // Los logs propios de Rocket por request duplicarían la línea de acceso
+            match level.as_str() {
+                "off" => "off".to_string(),
+                "critical" => "warn".to_string(),
+                "debug" => "debug".to_string(),
+                _ => "info,rocket=warn".to_string(),
+            }
+        }
+    };
+    let filter = EnvFilter::try_new(&directives).map_err(|e| format!("log_filter inválido ({directives}): {e}"))?;
+    let writer = if cli { BoxMakeWriter::new(std::io::stderr) } else { BoxMakeWriter::new(std::io::stdout) };
+    tracing_subscriber::fmt()
+        .json()
+        .with_writer(writer)
+        .with_env_filter(filter)
+        .with_current_span(true)
+        .with_span_list(false)
+        .flatten_event(true)
+        .try_init()
+        .map_err(|e| e.to_string())
+}
+
+// Id del request en curso (None fuera de un handler, p. ej. en el worker de indexación)
+pub fn current_request_id() -> Option<String> {
+    CURRENT.try_with(|ctx| ctx.id.clone()).ok()
+}
+
+// Suma una lectura del caché al request en curso (se informa en la línea de acceso)
+pub fn record_cache(result: CacheResult) {
+    let _ = CURRENT.try_with(|ctx| match result {
+        CacheResult::L1Hit | CacheResult::Hit | CacheResult::Stale => ctx.cache_hits.fetch_add(1, Ordering::Relaxed),
+        CacheResult::Miss | CacheResult::Error => ctx.cache_misses.fetch_add(1, Ordering::Relaxed),
+    });
+}
+
+// Id recibido si es razonable (largo acotado, sin espacios ni caracteres de control)
+fn valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+fn context(req: &Request<'_>) -> Option<Arc<RequestContext>> {
+    req.local_cache(|| ContextSlot(None)).0.clone()
+}
+
+// Fairing que asigna el id, lo devuelve en la respuesta y escribe la línea de acceso
+pub struct RequestId;
+
+#[rocket::async_trait]
+impl Fairing for RequestId {
+    fn info(&self) -> Info {
+        Info { name: "Request ID", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let id = req
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|id| valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+        let ctx = RequestContext {
+            id,
+            started: Instant::now(),
+            cache_hits: AtomicU32::new(0),
+            cache_misses: AtomicU32::new(0),
+        };
+        req.local_cache(|| ContextSlot(Some(Arc::new(ctx))));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(ctx) = context(req) else {
+            return;
+        };
+        res.set_header(Header::new(REQUEST_ID_HEADER, ctx.id.clone()));
+
+        let route = req.route().map(|r| r.uri.path()).unwrap_or("unmatched");
+        let status = res.status().code;
+        let latency_ms = ctx.started.elapsed().as_secs_f64() * 1000.0;
+        let cache_hits = ctx.cache_hits.load(Ordering::Relaxed);
+        let cache_misses = ctx.cache_misses.load(Ordering::Relaxed);
+        macro_rules! access {
+            ($level:ident) => {
+                tracing::$level!(
+                    target: "access",
+                    request_id = %ctx.id,
+                    method = %req.method(),
+                    route,
+                    path = %req.uri().path(),
+                    status,
+                    latency_ms,
+                    cache_hits,
+                    cache_misses,
+                    "request"
+                )
+            };
+        }
+        if status >= 500 {
+            access!(error);
+        } else {
+            access!(info);
+        }
+    }
+}
+
+// Handler que corre el original dentro del span del request y con el contexto en CURRENT
+#[derive(Clone)]
+struct WithRequestContext(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for WithRequestContext {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let Some(ctx) = context(req) else {
+            return self.0.handle(req, data).await;
+        };
+        let route = req.route().map(|r| r.uri.path()).unwrap_or_default();
+        let span = tracing::info_span!("request", request_id = %ctx.id, method = %req.method(), route);
+        CURRENT.scope(ctx, self.0.handle(req, data)).instrument(span).await
+    }
+}
+
+// Envuelve los handlers de un `routes![...]` (usar en cada mount)
+pub fn with_request_context(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(WithRequestContext(route.handler));
+            route
+        })
+        .collect()
+}
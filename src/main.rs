@@ -3,52 +3,98 @@
 use rocket::{Build, Rocket};
 use rocket::fs::FileServer;
 use rocket_db_pools::{Database, sqlx};
+use tracing::{error, info, warn};
 
 mod models;
 mod repository;
 mod api;
 mod views;
 mod cache;
-mod opensearch_client;
+mod invalidation;
 mod routes_suggest;
 mod routes_search;
+mod search;
+mod indexer;
+mod migrations;
+mod auth;
+mod uploads;
+mod import;
+mod validation;
+mod moderation;
+mod metrics;
+mod logging;
+mod health;
 
 #[derive(Database)]
 #[database("sqlite_db")]
 struct Db(sqlx::SqlitePool);
 
 
-#[get("/health")]
-fn health() -> &'static str { "ok" }
-
 // Inicializa Redis (no rompe si no está disponible)
-async fn init_cache(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+async fn init_cache(rocket: Rocket<Build>) -> Rocket<Build> {
     let redis_url = rocket.figment()
         .extract_inner::<String>("redis_url")
         .unwrap_or_else(|_| "redis://redis:6379".to_string());
 
-    match cache::Cache::new(&redis_url).await {
-        Ok(cache) => {
-            println!("✅ Cache Redis inicializado correctamente");
-            Ok(rocket.manage(cache))
+    // Siempre se registra: si Redis no está arriba las lecturas van a SQLite hasta que vuelva
+    let cache = cache::Cache::new(&redis_url);
+    cache.listen_invalidations(&redis_url);
+    info!("Cache Redis inicializado (conexión perezosa)");
+    rocket.manage(cache)
+}
+
+// Inicializa el cliente de búsqueda (USE_OPENSEARCH decide si está habilitado)
+async fn init_search(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    match search::Search::from_env() {
+        Ok(search) => {
+            info!(opensearch = search.enabled(), "Search inicializado");
+            Ok(rocket.manage(search))
         }
         Err(e) => {
-            eprintln!("⚠️  Redis no disponible: {e}");
-            eprintln!("⚠️  Continuando sin cache Redis");
-            Ok(rocket) // continuar sin cache
+            error!(error = %e, "No se pudo inicializar Search");
+            Err(rocket)
         }
     }
 }
 
+// Reglas de moderación de reseñas (MODERATION_*; ver src/moderation.rs)
+async fn init_moderation(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    match moderation::Moderation::from_env() {
+        Ok(moderation) => Ok(rocket.manage(moderation)),
+        Err(e) => {
+            error!(error = %e, "No se pudo inicializar la moderación");
+            Err(rocket)
+        }
+    }
+}
+
+// Lanza el worker de indexación write-through (requiere Db y Search ya inicializados)
+async fn init_indexer(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let (Some(db), Some(search)) = (Db::fetch(&rocket), rocket.state::<search::Search>()) else {
+        error!("Indexer requiere la BD y Search inicializados");
+        return Err(rocket);
+    };
+    let indexer = indexer::Indexer::spawn(search.clone(), db.0.clone());
+    Ok(rocket.manage(indexer))
+}
+
 // Construye Rocket montando estáticos sólo si SERVE_STATIC=true
 fn build_rocket() -> Rocket<Build> {
     let mut app = rocket::build()
         .attach(Db::init())
-        .attach(rocket::fairing::AdHoc::on_ignite("Redis Cache", |rocket| async move {
-            init_cache(rocket).await.unwrap()
-        }))
+        // X-Request-Id y línea de acceso por request (src/logging.rs)
+        .attach(logging::RequestId)
+        // Conteo y latencia por ruta para /metrics
+        .attach(metrics::RequestMetrics)
+        // Migraciones versionadas antes que cualquier otro fairing que use la BD
+        .attach(rocket::fairing::AdHoc::try_on_ignite("Migraciones", migrations::run_fairing))
+        .attach(rocket::fairing::AdHoc::try_on_ignite("Admin inicial", auth::bootstrap_admin))
+        .attach(rocket::fairing::AdHoc::on_ignite("Redis Cache", init_cache))
+        .attach(rocket::fairing::AdHoc::try_on_ignite("Search", init_search))
+        .attach(rocket::fairing::AdHoc::try_on_ignite("Search Indexer", init_indexer))
+        .attach(rocket::fairing::AdHoc::try_on_ignite("Moderación", init_moderation))
         // Vistas (HTML)
-        .mount("/", routes![
+        .mount("/", logging::with_request_context(routes![
             views::index::index,
             views::books::books_index,
             views::authors::authors_index,
@@ -65,12 +111,20 @@ fn build_rocket() -> Rocket<Build> {
             views::reviews_form::reviews_update,
             views::books_search::books_search_page,
             views::sales::sales_by_book,
-            health,
+            views::auth::login_form,
+            views::auth::login,
+            views::auth::logout,
+            views::auth::register_form,
+            views::auth::register,
+            health::health,
+            health::live,
+            health::ready,
+            metrics::metrics,
             routes_suggest::suggest,
             routes_search::search
-        ])
+        ]))
         // API JSON
-        .mount("/api", routes![
+        .mount("/api", logging::with_request_context(routes![
             // Authors
             api::authors::get_authors,
             api::authors::get_author_details,
@@ -87,11 +141,19 @@ fn build_rocket() -> Rocket<Build> {
             api::books::delete_book,
             api::books::search_books,
 
+            // Imágenes (multipart)
+            api::images::upload_author_photo,
+            api::images::delete_author_photo,
+            api::images::upload_book_cover,
+            api::images::delete_book_cover,
+
             // Reviews
             api::reviews::get_book_reviews,
             api::reviews::create_review,
             api::reviews::update_review,
             api::reviews::delete_review,
+            api::reviews::vote_review,
+            api::reviews::unvote_review,
 
             // Sales
             api::sales::get_book_sales,
@@ -101,6 +163,51 @@ fn build_rocket() -> Rocket<Build> {
 
             // Dashboard
             api::dashboard::get_dashboard_stats,
+
+            // Stats (tablas agregadas)
+            api::stats::author_stats,
+            api::stats::top_rated,
+            api::stats::top_selling,
+
+            // Auth
+            api::auth::register,
+            api::auth::create_token,
+            api::auth::delete_token,
+            api::auth::me,
+
+            // Importación masiva
+            api::import::import_data,
+
+            // Exportación (stream)
+            api::export::export_authors,
+            api::export::export_books,
+            api::export::export_catalog,
+            api::export::export_reviews,
+            api::export::export_sales,
+
+            // Admin
+            api::admin::reindex_all,
+            api::admin::recompute_sales,
+            api::admin::purge_cache,
+            api::admin::list_users,
+            api::admin::update_user_role,
+            api::admin::moderation_queue,
+            api::admin::moderate_review,
+        ]))
+        // Sin sesión => login; rol insuficiente => página 403
+        .register("/", catchers![
+            views::auth::unauthorized,
+            views::auth::forbidden,
+        ])
+        // Errores de Rocket (401/403 de los guards, 404, JSON inválido, 500) con el mismo sobre ApiResponse
+        .register("/api", catchers![
+            api::error::unauthorized,
+            api::error::forbidden,
+            api::error::not_found,
+            api::error::bad_request,
+            api::error::payload_too_large,
+            api::error::unprocessable_entity,
+            api::error::internal_error,
         ]);
 
     // Solo servir archivos estáticos si SERVE_STATIC=true (modo sin proxy)
@@ -108,17 +215,48 @@ fn build_rocket() -> Rocket<Build> {
     if serve_static {
         // /static → archivos “fijos” (css/js/img)
         app = app.mount("/static", FileServer::from("public/static"));
-        // /uploads → imágenes subidas (autores/portadas)
-        app = app.mount("/uploads", FileServer::from("public/uploads"));
-        println!("📁 Sirviendo estáticos desde public/ (SERVE_STATIC=true)");
+        // /uploads → imágenes subidas (autores/portadas), UPLOAD_DIR
+        let upload_dir = uploads::upload_dir();
+        if let Err(e) = std::fs::create_dir_all(&upload_dir) {
+            warn!(dir = %upload_dir.display(), error = %e, "No se pudo crear el directorio de subidas");
+        }
+        app = app.mount("/uploads", FileServer::from(upload_dir));
+        info!("Sirviendo estáticos desde public/ (SERVE_STATIC=true)");
     } else {
-        println!("🧭 Modo proxy: no se sirven estáticos desde la app (SERVE_STATIC=false)");
+        info!("Modo proxy: no se sirven estáticos desde la app (SERVE_STATIC=false)");
     }
 
     app
 }
 
-#[launch]
-fn rocket() -> _ {
-    build_rocket()
+// `libro_reviews --dry-run` muestra las migraciones pendientes sin tocar la BD ni levantar el servidor
+// `libro_reviews import ...` importa un archivo CSV/NDJSON (ver src/import.rs)
+#[rocket::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let import = args.get(1).map(String::as_str) == Some("import");
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    if let Err(e) = logging::init(&rocket::Config::figment(), import || dry_run) {
+        eprintln!("❌ No se pudo configurar el logging: {e}");
+        std::process::exit(1);
+    }
+    if import {
+        let figment = rocket::Config::figment();
+        let url: String = figment.extract_inner("databases.sqlite_db.url")?;
+        let redis_url = figment.extract_inner::<String>("redis_url").unwrap_or_else(|_| "redis://redis:6379".to_string());
+        if let Err(e) = import::cli(&args[2..], &url, &redis_url).await {
+            error!(error = %e, "La importación no se realizó");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if dry_run {
+        let url: String = rocket::Config::figment().extract_inner("databases.sqlite_db.url")?;
+        migrations::dry_run(&url).await?;
+        return Ok(());
+    }
+
+    let _ = build_rocket().launch().await?;
+    Ok(())
 }
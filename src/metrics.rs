@@ -0,0 +1,251 @@
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::{Data, Request, Response, State};
+
+use crate::cache::Cache;
+use crate::Db;
+
+// Métricas Prometheus de la app (GET /metrics, formato texto 0.0.4).
+// - http_requests_total / http_request_duration_seconds: por método y ruta montada
+//   (la plantilla, p. ej. `/api/books/<id>`, no la URL concreta; lo que no matchea es "unmatched")
+// - db_pool_connections{state} / db_pool_max_connections: uso del pool SQLite al momento del scrape
+// - cache_requests_total{family,result}: hits/misses de Cache::get por familia de claves
+// - opensearch_request_duration_seconds / opensearch_errors_total: por operación contra OpenSearch
+//
+// Los contadores son globales (el caché y Search no tienen acceso al State de Rocket).
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    db_pool_max: IntGauge,
+    cache_requests: IntCounterVec,
+    opensearch_duration: HistogramVec,
+    opensearch_errors: IntCounterVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+// Latencias en segundos: de 1 ms a 10 s
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+impl Metrics {
+    fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Requests HTTP atendidos"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Latencia de los requests HTTP")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["method", "route"],
+        )
+        .unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Conexiones del pool SQLite (idle / in_use)"),
+            &["state"],
+        )
+        .unwrap();
+        let db_pool_max = IntGauge::new("db_pool_max_connections", "Máximo de conexiones del pool SQLite").unwrap();
+        let cache_requests = IntCounterVec::new(
+            Opts::new("cache_requests_total", "Lecturas del caché Redis por familia de claves"),
+            &["family", "result"],
+        )
+        .unwrap();
+        let opensearch_duration = HistogramVec::new(
+            HistogramOpts::new("opensearch_request_duration_seconds", "Latencia de los requests a OpenSearch")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["operation"],
+        )
+        .unwrap();
+        let opensearch_errors = IntCounterVec::new(
+            Opts::new("opensearch_errors_total", "Requests a OpenSearch fallidos (red o status de error)"),
+            &["operation"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(db_pool_connections.clone())).unwrap();
+        registry.register(Box::new(db_pool_max.clone())).unwrap();
+        registry.register(Box::new(cache_requests.clone())).unwrap();
+        registry.register(Box::new(opensearch_duration.clone())).unwrap();
+        registry.register(Box::new(opensearch_errors.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            db_pool_connections,
+            db_pool_max,
+            cache_requests,
+            opensearch_duration,
+            opensearch_errors,
+        }
+    }
+}
+
+// Resultado de una lectura del caché
+#[derive(Debug, Clone, Copy)]
+pub enum CacheResult {
+    // Servida desde el L1 en memoria (sin ir a Redis)
+    L1Hit,
+    Hit,
+    // Vencida pero servida mientras se refresca (Cache::get_or_compute)
+    Stale,
+    Miss,
+    Error,
+}
+
+impl CacheResult {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheResult::L1Hit => "l1_hit",
+            CacheResult::Hit => "hit",
+            CacheResult::Stale => "stale",
+            CacheResult::Miss => "miss",
+            CacheResult::Error => "error",
+        }
+    }
+}
+
+// Familias de claves: las que llevan un id/sufijo variable terminan en `*`
+// (los listados se agrupan en una sola familia aunque cada página tenga su clave)
+const CACHE_FAMILIES: &[(&str, &str)] = &[
+    (Cache::KEY_BOOKS_LIST_PREFIX, "books:list"),
+    (Cache::KEY_BOOK_PREFIX, "books:id:*"),
+    (Cache::KEY_AUTHORS_LIST_PREFIX, "authors:list"),
+    (Cache::KEY_AUTHOR_PREFIX, "authors:id:*"),
+    (Cache::KEY_AUTHOR_DETAILS_PREFIX, "authors:details:*"),
+    (Cache::KEY_REVIEWS_PREFIX, "reviews:book:*"),
+    (Cache::KEY_SALES_PREFIX, "sales:book:*"),
+    (Cache::KEY_DASHBOARD_STATS, "dashboard:stats"),
+    (Cache::KEY_STATS_AUTHORS, "stats:authors"),
+    (Cache::KEY_STATS_TOP_RATED_PREFIX, "stats:top-rated:*"),
+    (Cache::KEY_STATS_TOP_SELLING_PREFIX, "stats:top-selling:*"),
+];
+
+// Familia de una clave del caché ("other" si no coincide con ningún prefijo conocido)
+pub fn cache_family(key: &str) -> &'static str {
+    CACHE_FAMILIES
+        .iter()
+        .find(|(prefix, _)| key.starts_with(prefix))
+        .map(|(_, family)| *family)
+        .unwrap_or("other")
+}
+
+// Registra una lectura del caché
+// Argumentos:
+// - key: &str - Clave leída (se agrupa por familia, ver `cache_family`)
+// - result: CacheResult - L1Hit, Hit, Stale, Miss o Error (Redis caído)
+pub fn record_cache(key: &str, result: CacheResult) {
+    METRICS.cache_requests.with_label_values(&[cache_family(key), result.as_str()]).inc();
+}
+
+// Registra un request a OpenSearch
+// Argumentos:
+// - operation: &str - Operación lógica (search, suggest, put_doc, bulk, ...)
+// - started: Instant - Inicio del request
+// - ok: bool - false si falló la conexión o respondió con status de error
+pub fn record_opensearch(operation: &str, started: Instant, ok: bool) {
+    METRICS
+        .opensearch_duration
+        .with_label_values(&[operation])
+        .observe(started.elapsed().as_secs_f64());
+    if !ok {
+        METRICS.opensearch_errors.with_label_values(&[operation]).inc();
+    }
+}
+
+// Inicio del request, guardado en el cache local del Request
+struct RequestStart(Option<Instant>);
+
+// Fairing que cuenta y cronometra cada request por ruta
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info { name: "Métricas HTTP", kind: Kind::Request | Kind::Response }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(started) = req.local_cache(|| RequestStart(None)).0 else {
+            return;
+        };
+        // La plantilla de la ruta mantiene acotada la cardinalidad de la etiqueta
+        let route = req.route().map(|r| r.uri.path()).unwrap_or("unmatched");
+        let method = req.method().as_str();
+        let status = res.status().code.to_string();
+        METRICS.http_requests.with_label_values(&[method, route, &status]).inc();
+        METRICS
+            .http_duration
+            .with_label_values(&[method, route])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+// GET /metrics
+// Formato de exposición de Prometheus; el uso del pool se toma al momento del scrape
+#[get("/metrics")]
+pub fn metrics(pool: &State<Db>) -> Result<(ContentType, String), Status> {
+    let idle = pool.0.num_idle() as i64;
+    let size = i64::from(pool.0.size());
+    METRICS.db_pool_connections.with_label_values(&["idle"]).set(idle);
+    METRICS.db_pool_connections.with_label_values(&["in_use"]).set((size - idle).max(0));
+    METRICS.db_pool_max.set(i64::from(pool.0.options().get_max_connections()));
+
+    let encoder = TextEncoder::new();
+    let body = encoder.encode_to_string(&METRICS.registry.gather()).map_err(|e| {
+        tracing::error!(error = %e, "Error generando métricas");
+        Status::InternalServerError
+    })?;
+    let content_type = ContentType::parse_flexible(encoder.format_type()).unwrap_or(ContentType::Plain);
+    Ok((content_type, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_family_groups_keys_by_prefix() {
+        assert_eq!(cache_family("books:list:page=2&per_page=20"), "books:list");
+        assert_eq!(cache_family("books:id:42"), "books:id:*");
+        assert_eq!(cache_family("authors:id:7"), "authors:id:*");
+        assert_eq!(cache_family("authors:details:7"), "authors:details:*");
+        assert_eq!(cache_family("reviews:book:3"), "reviews:book:*");
+        assert_eq!(cache_family("dashboard:stats"), "dashboard:stats");
+        assert_eq!(cache_family("stats:authors"), "stats:authors");
+        assert_eq!(cache_family("stats:top-rated:10"), "stats:top-rated:*");
+        assert_eq!(cache_family("stats:top-selling:5"), "stats:top-selling:*");
+    }
+
+    #[test]
+    fn unknown_keys_are_other() {
+        assert_eq!(cache_family("stats:otra"), "other");
+        assert_eq!(cache_family("books:"), "other");
+        assert_eq!(cache_family(""), "other");
+    }
+
+    #[test]
+    fn no_prefix_shadows_a_later_family() {
+        for (i, (prefix, family)) in CACHE_FAMILIES.iter().enumerate() {
+            assert_eq!(cache_family(prefix), *family, "el prefijo {prefix} cae en otra familia");
+            assert!(CACHE_FAMILIES[..i].iter().all(|(earlier, _)| !prefix.starts_with(earlier)));
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+
+use rocket::{Build, Rocket};
+use rocket_db_pools::{sqlx::{self, sqlite::SqliteConnectOptions, Row, SqlitePool}, Database};
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+
+use crate::Db;
+
+// Directorio por defecto (en Docker el WORKDIR es /app, ver Dockerfile)
+const DEFAULT_DIR: &str = "migrations";
+
+// Las BBDD creadas por el antiguo entrypoint.sh (sqlite3 < migrations/*.sql) no tienen
+// `schema_migrations`. Si ya tienen el esquema, se registran estas versiones sin ejecutarlas.
+const LEGACY_BASELINE_VERSION: i64 = 2;
+
+// La antigua `002_sample_data.sql` ahora vive en `seed/` y no forma parte de la cadena versionada:
+// las BBDD que ya la tienen registrada no deben avisar de que falta el archivo
+const SAMPLE_DATA_VERSION: i64 = 2;
+
+// Datos de ejemplo, relativos al directorio de migraciones. Solo se cargan con SEED_SAMPLE_DATA
+const SEED_FILE: &str = "seed/sample_data.sql";
+
+const CREATE_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        checksum TEXT NOT NULL,
+        applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        execution_ms INTEGER NOT NULL DEFAULT 0
+    )";
+
+// Archivo de migración: `NNN_descripcion.sql`
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub sql: String,
+}
+
+#[derive(Debug)]
+struct Applied {
+    version: i64,
+    name: String,
+    checksum: String,
+}
+
+// Qué haría (o hizo) el runner
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub baseline: Vec<i64>,
+    pub pending: Vec<i64>,
+    pub seeded: bool,
+}
+
+// Directorio de migraciones (configurable con MIGRATIONS_DIR)
+pub fn migrations_dir() -> PathBuf {
+    std::env::var("MIGRATIONS_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(DEFAULT_DIR))
+}
+
+// Lee y ordena por versión todos los `*.sql` del directorio
+// Argumentos: dir: &Path - Directorio de migraciones
+// Retorna: Result<Vec<Migration>, String> - Migraciones ordenadas o error (nombre inválido, versión duplicada)
+pub fn load(dir: &Path) -> Result<Vec<Migration>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("no se pudo leer {}: {e}", dir.display()))?;
+
+    let mut migrations = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("sql") {
+            continue;
+        }
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        let version = file_name
+            .split('_')
+            .next()
+            .and_then(|v| v.parse::<i64>().ok())
+            .ok_or_else(|| format!("nombre de migración inválido (se espera NNN_nombre.sql): {file_name}"))?;
+        let sql = std::fs::read_to_string(&path)
+            .map_err(|e| format!("no se pudo leer {}: {e}", path.display()))?;
+
+        migrations.push(Migration { version, name: file_name, checksum: checksum(&sql), sql });
+    }
+
+    migrations.sort_by_key(|m| m.version);
+    if let Some(w) = migrations.windows(2).find(|w| w[0].version == w[1].version) {
+        return Err(format!("versión de migración duplicada {}: {} y {}", w[0].version, w[0].name, w[1].name));
+    }
+    Ok(migrations)
+}
+
+fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes()).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Compara el directorio con `schema_migrations` y verifica checksums de lo ya aplicado
+fn plan(migrations: &[Migration], applied: &[Applied], legacy_schema: bool) -> Result<Plan, String> {
+    for a in applied {
+        match migrations.iter().find(|m| m.version == a.version) {
+            Some(m) if m.checksum != a.checksum => {
+                return Err(format!(
+                    "checksum distinto para la migración {} ({}): el archivo cambió después de aplicarse",
+                    a.version, m.name
+                ));
+            }
+            Some(_) => {}
+            None if a.version == SAMPLE_DATA_VERSION => {}
+            None => warn!(version = a.version, name = %a.name, "Migración aplicada pero el archivo ya no existe"),
+        }
+    }
+
+    let mut plan = Plan::default();
+    for m in migrations {
+        if applied.iter().any(|a| a.version == m.version) {
+            continue;
+        }
+        if legacy_schema && m.version <= LEGACY_BASELINE_VERSION {
+            plan.baseline.push(m.version);
+        } else {
+            plan.pending.push(m.version);
+        }
+    }
+    Ok(plan)
+}
+
+async fn table_exists(pool: &SqlitePool, name: &str) -> Result<bool, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+        .bind(name)
+        .fetch_one(pool)
+        .await?;
+    Ok(count > 0)
+}
+
+async fn applied(pool: &SqlitePool) -> Result<Vec<Applied>, sqlx::Error> {
+    if !table_exists(pool, "schema_migrations").await? {
+        return Ok(Vec::new());
+    }
+    let rows = sqlx::query("SELECT version, name, checksum FROM schema_migrations ORDER BY version")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(|row| Applied {
+        version: row.get("version"),
+        name: row.get("name"),
+        checksum: row.get("checksum"),
+    }).collect())
+}
+
+// Última versión aplicada (health check)
+// Argumentos: pool: &SqlitePool - Pool de la BD
+// Retorna: Result<Option<i64>, sqlx::Error> - None si todavía no hay `schema_migrations` o está vacía
+pub async fn current_version(pool: &SqlitePool) -> Result<Option<i64>, sqlx::Error> {
+    if !table_exists(pool, "schema_migrations").await? {
+        return Ok(None);
+    }
+    sqlx::query_scalar("SELECT MAX(version) FROM schema_migrations").fetch_one(pool).await
+}
+
+// SEED_SAMPLE_DATA=true: carga los datos de ejemplo si la BD no tiene autores (solo desarrollo/demo)
+fn seed_enabled() -> bool {
+    std::env::var("SEED_SAMPLE_DATA")
+        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+async fn is_empty(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    if !table_exists(pool, "authors").await? {
+        return Ok(true);
+    }
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM authors").fetch_one(pool).await?;
+    Ok(count == 0)
+}
+
+// Carga `seed/sample_data.sql` en una transacción si la BD está vacía
+// Argumentos:
+// - pool: &SqlitePool - Pool de la BD (ya migrada)
+// - dir: &Path - Directorio de migraciones
+// Retorna: Result<bool, String> - true si se cargaron los datos
+async fn seed(pool: &SqlitePool, dir: &Path) -> Result<bool, String> {
+    if !is_empty(pool).await.map_err(|e| e.to_string())? {
+        info!("SEED_SAMPLE_DATA activo pero la BD ya tiene datos; no se cargan los de ejemplo");
+        return Ok(false);
+    }
+    let path = dir.join(SEED_FILE);
+    let sql = std::fs::read_to_string(&path)
+        .map_err(|e| format!("no se pudo leer {}: {e}", path.display()))?;
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query(&sql)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("fallaron los datos de ejemplo: {e}"))?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    info!(file = %path.display(), "Datos de ejemplo cargados");
+    Ok(true)
+}
+
+// BD creada antes del runner: tiene el esquema pero no la tabla de control
+async fn is_legacy(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    Ok(!table_exists(pool, "schema_migrations").await? && table_exists(pool, "authors").await?)
+}
+
+// Aplica las migraciones pendientes, cada una en su propia transacción junto con su registro
+// Argumentos:
+// - pool: &SqlitePool - Pool de la BD
+// - dir: &Path - Directorio de migraciones
+// Retorna: Result<Plan, String> - Versiones registradas como baseline y aplicadas
+pub async fn run(pool: &SqlitePool, dir: &Path) -> Result<Plan, String> {
+    let migrations = load(dir)?;
+    let legacy = is_legacy(pool).await.map_err(|e| e.to_string())?;
+    let applied = applied(pool).await.map_err(|e| e.to_string())?;
+    let plan = plan(&migrations, &applied, legacy)?;
+
+    sqlx::query(CREATE_TABLE).execute(pool).await.map_err(|e| e.to_string())?;
+
+    for version in &plan.baseline {
+        let m = migrations.iter().find(|m| m.version == *version).expect("versión del plan");
+        sqlx::query("INSERT INTO schema_migrations (version, name, checksum) VALUES (?, ?, ?)")
+            .bind(m.version)
+            .bind(&m.name)
+            .bind(&m.checksum)
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        info!(migration = %m.name, "Migración registrada como baseline (BD existente)");
+    }
+
+    for version in &plan.pending {
+        let m = migrations.iter().find(|m| m.version == *version).expect("versión del plan");
+        let started = Instant::now();
+
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        // sqlx ejecuta todas las sentencias del archivo sobre la misma conexión
+        sqlx::query(&m.sql)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("falló la migración {}: {e}", m.name))?;
+        sqlx::query("INSERT INTO schema_migrations (version, name, checksum, execution_ms) VALUES (?, ?, ?, ?)")
+            .bind(m.version)
+            .bind(&m.name)
+            .bind(&m.checksum)
+            .bind(started.elapsed().as_millis() as i64)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        info!(migration = %m.name, elapsed_ms = started.elapsed().as_millis() as u64, "Migración aplicada");
+    }
+
+    let mut plan = plan;
+    if seed_enabled() {
+        plan.seeded = seed(pool, dir).await?;
+    }
+    Ok(plan)
+}
+
+// --dry-run: muestra qué se aplicaría sin escribir nada (abre la BD en solo lectura)
+// Argumentos: database_url: &str - URL de la BD (la misma de Rocket.toml)
+pub async fn dry_run(database_url: &str) -> Result<(), String> {
+    let dir = migrations_dir();
+    let migrations = load(&dir)?;
+
+    let options = SqliteConnectOptions::from_str(database_url)
+        .map_err(|e| e.to_string())?
+        .read_only(true)
+        .create_if_missing(false);
+
+    let (applied, legacy, empty) = match SqlitePool::connect_with(options).await {
+        Ok(pool) => {
+            let legacy = is_legacy(&pool).await.map_err(|e| e.to_string())?;
+            let empty = is_empty(&pool).await.map_err(|e| e.to_string())?;
+            (applied(&pool).await.map_err(|e| e.to_string())?, legacy, empty)
+        }
+        Err(e) => {
+            warn!(error = %e, "No se pudo abrir la BD; se asume BD nueva");
+            (Vec::new(), false, true)
+        }
+    };
+    let plan = plan(&migrations, &applied, legacy)?;
+
+    println!("Dry run de migraciones en {}", dir.display());
+    for m in &migrations {
+        let state = if applied.iter().any(|a| a.version == m.version) {
+            "aplicada"
+        } else if plan.baseline.contains(&m.version) {
+            "baseline (se registraría sin ejecutar)"
+        } else {
+            "pendiente (se aplicaría)"
+        };
+        println!("   {:>4}  {:<40} {}  {}", m.version, m.name, &m.checksum[..12], state);
+    }
+    println!("   {} pendientes, {} baseline", plan.pending.len(), plan.baseline.len());
+    if seed_enabled() {
+        let state = if empty { "se cargarían (BD vacía)" } else { "no se cargarían (la BD ya tiene datos)" };
+        println!("   datos de ejemplo ({}): {}", SEED_FILE, state);
+    }
+    Ok(())
+}
+
+// Fairing de ignición: migra antes de levantar las rutas. Si falla, Rocket no arranca.
+pub async fn run_fairing(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let Some(pool) = Db::fetch(&rocket).map(|db| db.0.clone()) else {
+        error!("Migraciones: la BD no está inicializada");
+        return Err(rocket);
+    };
+
+    match run(&pool, &migrations_dir()).await {
+        Ok(plan) if plan.pending.is_empty() && plan.baseline.is_empty() && !plan.seeded => {
+            info!("Esquema al día, sin migraciones pendientes");
+            Ok(rocket)
+        }
+        Ok(_) => Ok(rocket),
+        Err(e) => {
+            error!(error = %e, "Error de migraciones");
+            Err(rocket)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(version: i64, sql: &str) -> Migration {
+        Migration { version, name: format!("{version:03}_test.sql"), checksum: checksum(sql), sql: sql.to_string() }
+    }
+
+    fn applied(m: &Migration) -> Applied {
+        Applied { version: m.version, name: m.name.clone(), checksum: m.checksum.clone() }
+    }
+
+    #[test]
+    fn fresh_database_applies_everything() {
+        let migrations = [migration(1, "a"), migration(3, "b")];
+        let plan = plan(&migrations, &[], false).unwrap();
+        assert!(plan.baseline.is_empty());
+        assert_eq!(plan.pending, vec![1, 3]);
+    }
+
+    #[test]
+    fn applied_migrations_are_skipped() {
+        let migrations = [migration(1, "a"), migration(3, "b"), migration(4, "c")];
+        let plan = plan(&migrations, &[applied(&migrations[0]), applied(&migrations[1])], false).unwrap();
+        assert_eq!(plan.pending, vec![4]);
+    }
+
+    #[test]
+    fn checksum_mismatch_is_an_error() {
+        let migrations = [migration(1, "a"), migration(3, "b")];
+        let mut changed = applied(&migrations[1]);
+        changed.checksum = checksum("otro contenido");
+        let err = plan(&migrations, &[applied(&migrations[0]), changed], false).unwrap_err();
+        assert!(err.contains("checksum distinto para la migración 3"), "{err}");
+    }
+
+    #[test]
+    fn legacy_schema_baselines_old_versions() {
+        let migrations = [migration(1, "a"), migration(3, "b"), migration(4, "c")];
+        let plan = plan(&migrations, &[], true).unwrap();
+        assert_eq!(plan.baseline, vec![1]);
+        assert_eq!(plan.pending, vec![3, 4]);
+    }
+
+    #[test]
+    fn missing_applied_file_is_not_an_error() {
+        let migrations = [migration(1, "a")];
+        let retired = Applied { version: SAMPLE_DATA_VERSION, name: "002_sample_data.sql".into(), checksum: checksum("x") };
+        let plan = plan(&migrations, &[applied(&migrations[0]), retired], false).unwrap();
+        assert!(plan.pending.is_empty() && plan.baseline.is_empty());
+    }
+
+    #[test]
+    fn load_ignores_seed_directory() {
+        let migrations = load(Path::new(DEFAULT_DIR)).unwrap();
+        assert!(migrations.iter().all(|m| m.version != SAMPLE_DATA_VERSION));
+        assert!(Path::new(DEFAULT_DIR).join(SEED_FILE).exists());
+    }
+}
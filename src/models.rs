@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::validation::{Reference, Validate, Validator};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Author {
     pub id: Option<i32>,
@@ -7,16 +9,16 @@ pub struct Author {
     pub birth_date: String,
     pub country: String,
     pub description: Option<String>,
+    pub photo: Option<ImageRef>,
+    pub updated_at: Option<String>,
+    pub version: i32,
 }
 
+// Imagen subida (src/uploads.rs): URL del original y de la miniatura
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Book {
-    pub id: Option<i32>,
-    pub title: String,
-    pub summary: Option<String>,
-    pub publication_date: String,
-    pub sales_count: i32,
-    pub author_id: i32,
+pub struct ImageRef {
+    pub url: String,
+    pub thumbnail_url: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -26,19 +28,12 @@ pub struct BookWithAuthor {
     pub summary: Option<String>,
     pub publication_date: String,
     pub sales_count: i32,
+    pub cover: Option<ImageRef>,
+    pub updated_at: Option<String>,
+    pub version: i32,
     pub author: Author,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Review {
-    pub id: Option<i32>,
-    pub book_id: i32,
-    pub review_text: String,
-    pub rating: i32,
-    pub positive_votes: i32,
-    pub created_at: Option<String>,
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ReviewWithBook {
     pub id: Option<i32>,
@@ -48,23 +43,58 @@ pub struct ReviewWithBook {
     pub rating: i32,
     pub positive_votes: i32,
     pub created_at: Option<String>,
+    // Autor de la reseña (None en reseñas anteriores al sistema de usuarios)
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub updated_at: Option<String>,
+    pub version: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct YearlySales {
+pub struct YearlySalesWithBook {
     pub id: Option<i32>,
     pub book_id: i32,
+    pub book_title: String,
     pub year: i32,
     pub sales: i32,
+    pub version: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct YearlySalesWithBook {
-    pub id: Option<i32>,
+// Filas de exportación (GET /api/export/*): planas para que sirvan como columnas CSV
+#[derive(Debug, Serialize)]
+pub struct AuthorExportRow {
+    pub id: i32,
+    pub name: String,
+    pub birth_date: String,
+    pub country: String,
+    pub description: Option<String>,
+    pub books_count: i64,
+    pub total_sales: i64,
+    pub avg_rating: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BookExportRow {
+    pub id: i32,
+    pub title: String,
+    pub summary: Option<String>,
+    pub publication_date: String,
+    pub author_id: i32,
+    pub sales_count: i64,
+}
+
+// Libro con su autor, rating promedio y ventas totales (/api/export/catalog)
+#[derive(Debug, Serialize)]
+pub struct CatalogExportRow {
     pub book_id: i32,
-    pub book_title: String,
-    pub year: i32,
-    pub sales: i32,
+    pub title: String,
+    pub publication_date: String,
+    pub author_id: i32,
+    pub author_name: String,
+    pub author_country: String,
+    pub avg_rating: Option<f64>,
+    pub reviews_count: i64,
+    pub total_sales: i64,
 }
 
 // DTOs para crear/actualizar entidades
@@ -91,6 +121,77 @@ pub struct CreateReview {
     pub rating: i32,
 }
 
+// Estado de moderación de una reseña (src/moderation.rs); solo `Approved` es público
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ReviewStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "pending" => Some(Self::Pending),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+// Resultado de la moderación que se guarda junto a la reseña
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationResult {
+    pub status: ReviewStatus,
+    // Motivos de las reglas que marcaron la reseña (None si se aprobó)
+    pub reason: Option<String>,
+}
+
+// Reseña con sus datos de moderación (cola de /api/admin/reviews)
+#[derive(Debug, Serialize, Clone)]
+pub struct ModeratedReview {
+    pub id: i32,
+    pub book_id: i32,
+    pub book_title: String,
+    pub review_text: String,
+    pub rating: i32,
+    pub positive_votes: i32,
+    pub created_at: Option<String>,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub status: ReviewStatus,
+    pub moderation_reason: Option<String>,
+    pub moderated_at: Option<String>,
+    pub moderated_by: Option<i32>,
+    pub version: i32,
+}
+
+// PUT /api/admin/reviews/<id>/moderation
+#[derive(Debug, Deserialize)]
+pub struct ModerateReview {
+    pub status: ReviewStatus,
+    pub reason: Option<String>,
+}
+
+// Respuesta de POST/DELETE /api/reviews/<id>/vote
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VoteResult {
+    pub review_id: i32,
+    pub positive_votes: i32,
+    // Si el votante tiene su voto registrado tras la operación
+    pub voted: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateYearlySales {
     pub book_id: i32,
@@ -105,6 +206,8 @@ pub struct UpdateAuthor {
     pub birth_date: Option<String>,
     pub country: Option<String>,
     pub description: Option<String>,
+    // Versión leída (control de concurrencia optimista): si no es la actual, 412. La API la toma de `If-Match`
+    pub version: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -113,27 +216,283 @@ pub struct UpdateBook {
     pub summary: Option<String>,
     pub publication_date: Option<String>,
     pub author_id: Option<i32>,
+    // Versión leída (control de concurrencia optimista): si no es la actual, 412. La API la toma de `If-Match`
+    pub version: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateReview {
-    pub book_id: Option<i32>,
     pub review_text: Option<String>,
     pub rating: Option<i32>,
-    pub positive_votes: Option<i32>,
+    // positive_votes es de solo lectura: se modifica con POST/DELETE /api/reviews/<id>/vote
+    // Versión leída (control de concurrencia optimista): si no es la actual, 412. La API la toma de `If-Match`
+    pub version: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateYearlySales {
-    pub book_id: Option<i32>,
     pub year: Option<i32>,
     pub sales: Option<i32>,
+    // Versión leída (control de concurrencia optimista): si no es la actual, 412. La API la toma de `If-Match`
+    pub version: Option<i32>,
+}
+
+// Resultado de un UPDATE con control de concurrencia optimista (columna `version`)
+#[derive(Debug)]
+pub enum UpdateOutcome<T> {
+    Updated(T),
+    NotFound,
+    // Otro usuario la editó después de que el cliente la leyera: lleva la versión actual
+    Conflict { current: i32 },
+}
+
+// Reglas de validación (src/validation.rs), compartidas por la API, los formularios HTML
+// y la importación masiva. En los Update* sólo se validan los campos presentes.
+const MAX_NAME: usize = 200;
+const MAX_COUNTRY: usize = 100;
+const MAX_TITLE: usize = 300;
+const MAX_LONG_TEXT: usize = 5000;
+
+impl Validate for CreateAuthor {
+    fn rules(&self, v: &mut Validator) {
+        v.text("name", &self.name, MAX_NAME);
+        v.date("birth_date", &self.birth_date);
+        v.text("country", &self.country, MAX_COUNTRY);
+        if let Some(description) = &self.description {
+            v.max_chars("description", description, MAX_LONG_TEXT);
+        }
+    }
+}
+
+impl Validate for UpdateAuthor {
+    fn rules(&self, v: &mut Validator) {
+        v.opt_text("name", self.name.as_deref(), MAX_NAME);
+        v.opt_date("birth_date", self.birth_date.as_deref());
+        v.opt_text("country", self.country.as_deref(), MAX_COUNTRY);
+        if let Some(description) = &self.description {
+            v.max_chars("description", description, MAX_LONG_TEXT);
+        }
+    }
+}
+
+impl Validate for CreateBook {
+    fn rules(&self, v: &mut Validator) {
+        v.text("title", &self.title, MAX_TITLE);
+        if let Some(summary) = &self.summary {
+            v.max_chars("summary", summary, MAX_LONG_TEXT);
+        }
+        v.date("publication_date", &self.publication_date);
+    }
+
+    fn references(&self) -> Vec<(&'static str, Reference)> {
+        vec![("author_id", Reference::Author(self.author_id))]
+    }
+}
+
+impl Validate for UpdateBook {
+    fn rules(&self, v: &mut Validator) {
+        v.opt_text("title", self.title.as_deref(), MAX_TITLE);
+        if let Some(summary) = &self.summary {
+            v.max_chars("summary", summary, MAX_LONG_TEXT);
+        }
+        v.opt_date("publication_date", self.publication_date.as_deref());
+    }
+
+    fn references(&self) -> Vec<(&'static str, Reference)> {
+        self.author_id.map(|id| ("author_id", Reference::Author(id))).into_iter().collect()
+    }
+}
+
+impl Validate for CreateReview {
+    fn rules(&self, v: &mut Validator) {
+        v.text("review_text", &self.review_text, MAX_LONG_TEXT);
+        v.range("rating", self.rating, 1, 5);
+    }
+
+    fn references(&self) -> Vec<(&'static str, Reference)> {
+        vec![("book_id", Reference::Book(self.book_id))]
+    }
+}
+
+impl Validate for UpdateReview {
+    fn rules(&self, v: &mut Validator) {
+        v.opt_text("review_text", self.review_text.as_deref(), MAX_LONG_TEXT);
+        v.opt_range("rating", self.rating, 1, 5);
+    }
+}
+
+impl Validate for CreateYearlySales {
+    fn rules(&self, v: &mut Validator) {
+        v.range("year", self.year, 1, 9999);
+        v.non_negative("sales", self.sales);
+    }
+
+    fn references(&self) -> Vec<(&'static str, Reference)> {
+        vec![("book_id", Reference::Book(self.book_id))]
+    }
+}
+
+impl Validate for UpdateYearlySales {
+    fn rules(&self, v: &mut Validator) {
+        v.opt_range("year", self.year, 1, 9999);
+        if let Some(sales) = self.sales {
+            v.non_negative("sales", sales);
+        }
+    }
+}
+
+// Estadísticas agregadas (repository::stats, /api/stats/*, vista /tables)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuthorStats {
+    pub author_id: i32,
+    pub author_name: String,
+    pub books: i64,
+    pub avg_rating: Option<f64>,
+    pub total_sales: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReviewSnippet {
+    pub id: i32,
+    pub rating: i32,
+    pub positive_votes: i32,
+    pub review_text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopRatedBook {
+    pub book_id: i32,
+    pub title: String,
+    pub author_name: String,
+    pub avg_rating: f64,
+    pub review_count: i64,
+    // Reseña con rating más alto / más bajo; a igual rating, la más votada
+    pub highest: Option<ReviewSnippet>,
+    pub lowest: Option<ReviewSnippet>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopSellingBook {
+    pub book_id: i32,
+    pub title: String,
+    pub author_id: i32,
+    pub author_name: String,
+    pub publication_year: Option<i32>,
+    pub sales_count: i64,
+    pub author_total_sales: i64,
+    // Posición del libro entre los publicados el mismo año (por ventas)
+    pub rank_in_year: i64,
+    pub top5_in_year: bool,
+}
+
+// Filtros de listados (se aplican en SQL, ver repository::books::list_books / authors::list_authors)
+#[derive(Debug, Default, Clone)]
+pub struct BookFilter {
+    pub q: Option<String>,
+    pub author_id: Option<i32>,
+    pub country: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    pub min_rating: Option<f64>,
+    pub min_sales: Option<i64>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AuthorFilter {
+    pub q: Option<String>,
+    pub country: Option<String>,
+    pub min_rating: Option<f64>,
+    pub min_sales: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "asc" => Some(Self::Asc),
+            "desc" => Some(Self::Desc),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+}
+
+// Columnas por las que se puede ordenar el listado de libros
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSort {
+    Title,
+    PublicationDate,
+    Sales,
+    Rating,
+    Author,
+}
+
+impl BookSort {
+    pub const ALL: [Self; 5] = [Self::Title, Self::PublicationDate, Self::Sales, Self::Rating, Self::Author];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::PublicationDate => "publication_date",
+            Self::Sales => "sales",
+            Self::Rating => "rating",
+            Self::Author => "author",
+        }
+    }
+}
+
+// Columnas por las que se puede ordenar el listado de autores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorSort {
+    Name,
+    BirthDate,
+    Country,
+    Books,
+    Sales,
+    Rating,
+}
+
+impl AuthorSort {
+    pub const ALL: [Self; 6] = [Self::Name, Self::BirthDate, Self::Country, Self::Books, Self::Sales, Self::Rating];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::BirthDate => "birth_date",
+            Self::Country => "country",
+            Self::Books => "books",
+            Self::Sales => "sales",
+            Self::Rating => "rating",
+        }
+    }
 }
 
 // Respuestas de la API
+// `code` es estable y pensado para que los clientes hagan branching ("ok", "not_found", ...);
+// `message` es texto para humanos y puede cambiar.
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
+    pub code: &'static str,
     pub data: Option<T>,
     pub message: String,
 }
@@ -142,16 +501,87 @@ impl<T> ApiResponse<T> {
     pub fn success(data: T) -> Self {
         Self {
             success: true,
+            code: "ok",
             data: Some(data),
             message: "Operación exitosa".to_string(),
         }
     }
 
-    pub fn error(message: &str) -> Self {
+    pub fn error(code: &'static str, message: &str) -> Self {
         Self {
             success: false,
+            code,
             data: None,
             message: message.to_string(),
         }
     }
-} 
\ No newline at end of file
+}
+
+// Usuarios y roles (src/auth.rs). Cada rol incluye los permisos de los anteriores:
+// Reader < Editor < Admin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // Escribe reseñas y edita las propias
+    Reader,
+    // Gestiona autores, libros, ventas y cualquier reseña
+    Editor,
+    // Además administra usuarios y tareas de mantenimiento (/api/admin/*)
+    Admin,
+}
+
+impl Role {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "reader" => Some(Self::Reader),
+            "editor" => Some(Self::Editor),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reader => "reader",
+            Self::Editor => "editor",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+// Nunca incluye el hash de la contraseña
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiToken {
+    pub username: String,
+    pub password: String,
+    // Etiqueta para identificar el token (p.ej. "script de importación")
+    pub name: Option<String>,
+}
+
+// El token en claro solo se devuelve al crearlo
+#[derive(Debug, Serialize)]
+pub struct ApiTokenCreated {
+    pub id: i32,
+    pub name: String,
+    pub token: String,
+    pub user: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserRole {
+    pub role: Role,
+}
@@ -0,0 +1,317 @@
+use std::env;
+use std::fs;
+
+use rocket_db_pools::sqlx::{self, SqliteExecutor};
+use tracing::info;
+
+use crate::models::{ModerationResult, ReviewStatus};
+use crate::repository::reviews as reviews_repo;
+
+// Moderación de reseñas. Cada texto pasa por una lista de reglas (`Rule`) y por la detección
+// de duplicados del mismo libro; gana la marca más severa:
+// ninguna => approved, alguna `Pending` => pending (cola del admin), alguna `Rejected` => rejected.
+// Las reseñas siempre se guardan: un admin puede revertir la decisión desde /api/admin/reviews.
+//
+// Configuración (variables de entorno):
+// - MODERATION_BANNED_WORDS: palabras o frases separadas por coma (rechazan la reseña)
+// - MODERATION_BANNED_WORDS_FILE: archivo con una palabra/frase por línea (`#` = comentario)
+// - MODERATION_MAX_LINKS: links permitidos antes de mandar a revisión (por defecto 1)
+// - MODERATION_REQUIRE_APPROVAL: true => toda reseña nueva queda pendiente (pre-moderación)
+// - MODERATION_DUPLICATES: false => desactiva la detección de duplicados
+
+// Lo que una regla decide sobre el texto
+#[derive(Debug, Clone)]
+pub struct Flag {
+    // Pending o Rejected
+    pub status: ReviewStatus,
+    pub reason: String,
+}
+
+impl Flag {
+    fn pending(reason: impl Into<String>) -> Self {
+        Self { status: ReviewStatus::Pending, reason: reason.into() }
+    }
+
+    fn rejected(reason: impl Into<String>) -> Self {
+        Self { status: ReviewStatus::Rejected, reason: reason.into() }
+    }
+}
+
+// Regla de moderación sobre el texto. Para agregar una: implementar el trait y sumarla en `from_env`.
+pub trait Rule: Send + Sync {
+    fn check(&self, text: &str) -> Option<Flag>;
+}
+
+// Palabras o frases prohibidas (sin distinguir mayúsculas ni signos de puntuación)
+pub struct BannedWords {
+    phrases: Vec<String>,
+}
+
+impl BannedWords {
+    pub fn new(words: impl IntoIterator<Item = String>) -> Self {
+        let phrases = words
+            .into_iter()
+            .map(|w| normalize(&w))
+            .filter(|w| !w.is_empty())
+            .collect();
+        Self { phrases }
+    }
+}
+
+impl Rule for BannedWords {
+    fn check(&self, text: &str) -> Option<Flag> {
+        // Con espacios a los lados se comparan palabras completas ("as" no coincide con "casa")
+        let padded = format!(" {} ", normalize(text));
+        self.phrases
+            .iter()
+            .find(|phrase| padded.contains(&format!(" {phrase} ")))
+            .map(|phrase| Flag::rejected(format!("contiene lenguaje no permitido ('{phrase}')")))
+    }
+}
+
+// Demasiados links: típico de spam
+pub struct Links {
+    max_links: usize,
+}
+
+impl Rule for Links {
+    fn check(&self, text: &str) -> Option<Flag> {
+        let lower = text.to_lowercase();
+        let count = lower
+            .split_whitespace()
+            .filter(|word| word.contains("http://") || word.contains("https://") || word.starts_with("www."))
+            .count();
+        (count > self.max_links).then(|| Flag::pending(format!("contiene {count} links")))
+    }
+}
+
+// Heurísticas de spam: caracteres repetidos, texto en mayúsculas y palabras repetidas
+pub struct SpamHeuristics;
+
+impl SpamHeuristics {
+    const MAX_CHAR_RUN: usize = 10;
+    const MIN_LETTERS_FOR_CAPS: usize = 20;
+    const MAX_UPPERCASE_RATIO: f64 = 0.7;
+    const MIN_WORDS_FOR_REPEATS: usize = 10;
+    const MIN_UNIQUE_RATIO: f64 = 0.3;
+}
+
+impl Rule for SpamHeuristics {
+    fn check(&self, text: &str) -> Option<Flag> {
+        let mut run = 0;
+        let mut previous = None;
+        for ch in text.chars().filter(|c| !c.is_whitespace()) {
+            run = if Some(ch) == previous { run + 1 } else { 1 };
+            previous = Some(ch);
+            if run >= Self::MAX_CHAR_RUN {
+                return Some(Flag::pending(format!("caracteres repetidos ('{ch}')")));
+            }
+        }
+
+        let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+        if letters.len() >= Self::MIN_LETTERS_FOR_CAPS {
+            let upper = letters.iter().filter(|c| c.is_uppercase()).count();
+            if upper as f64 / letters.len() as f64 > Self::MAX_UPPERCASE_RATIO {
+                return Some(Flag::pending("escrita mayormente en mayúsculas"));
+            }
+        }
+
+        let normalized = normalize(text);
+        let words: Vec<&str> = normalized.split(' ').filter(|w| !w.is_empty()).collect();
+        if words.len() >= Self::MIN_WORDS_FOR_REPEATS {
+            let mut unique = words.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            if (unique.len() as f64 / words.len() as f64) < Self::MIN_UNIQUE_RATIO {
+                return Some(Flag::pending("palabras repetidas"));
+            }
+        }
+        None
+    }
+}
+
+// Pre-moderación: todo lo que pasa las demás reglas espera aprobación
+pub struct RequireApproval;
+
+impl Rule for RequireApproval {
+    fn check(&self, _text: &str) -> Option<Flag> {
+        Some(Flag::pending("requiere aprobación"))
+    }
+}
+
+pub struct Moderation {
+    rules: Vec<Box<dyn Rule>>,
+    detect_duplicates: bool,
+}
+
+impl Moderation {
+    pub fn new(rules: Vec<Box<dyn Rule>>, detect_duplicates: bool) -> Self {
+        Self { rules, detect_duplicates }
+    }
+
+    // Arma las reglas desde las variables de entorno (ver arriba)
+    // Retorna: Result<Moderation, String> - Err si no se puede leer MODERATION_BANNED_WORDS_FILE
+    pub fn from_env() -> Result<Self, String> {
+        let mut words: Vec<String> = env::var("MODERATION_BANNED_WORDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::to_string)
+            .collect();
+        if let Ok(path) = env::var("MODERATION_BANNED_WORDS_FILE") {
+            let content = fs::read_to_string(&path).map_err(|e| format!("No se pudo leer {path}: {e}"))?;
+            words.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+        let max_links = env::var("MODERATION_MAX_LINKS").ok().and_then(|v| v.parse().ok()).unwrap_or(1);
+
+        let banned = BannedWords::new(words);
+        info!(banned_words = banned.phrases.len(), max_links, "Moderación configurada");
+        let mut rules: Vec<Box<dyn Rule>> = vec![Box::new(banned), Box::new(Links { max_links }), Box::new(SpamHeuristics)];
+        if env_flag("MODERATION_REQUIRE_APPROVAL", false) {
+            info!("Moderación: las reseñas nuevas requieren aprobación");
+            rules.push(Box::new(RequireApproval));
+        }
+        Ok(Self::new(rules, env_flag("MODERATION_DUPLICATES", true)))
+    }
+
+    // Modera el texto de una reseña
+    // Argumentos:
+    // - db: impl SqliteExecutor - Conexión (o transacción de la importación) para buscar duplicados
+    // - book_id: i32 - Libro de la reseña
+    // - exclude_id: Option<i32> - La propia reseña al editarla
+    // - text: &str - Texto a moderar
+    // Retorna: Result<ModerationResult, sqlx::Error> - Estado y motivos a guardar
+    pub async fn review<'c>(
+        &self,
+        db: impl SqliteExecutor<'c>,
+        book_id: i32,
+        exclude_id: Option<i32>,
+        text: &str,
+    ) -> Result<ModerationResult, sqlx::Error> {
+        let mut flags: Vec<Flag> = self.rules.iter().filter_map(|rule| rule.check(text)).collect();
+        if self.detect_duplicates {
+            let normalized = normalize(text);
+            let existing = reviews_repo::get_review_texts_by_book(db, book_id, exclude_id).await?;
+            if existing.iter().any(|other| normalize(other) == normalized) {
+                flags.push(Flag::rejected("reseña duplicada para este libro"));
+            }
+        }
+
+        let status = if flags.iter().any(|f| f.status == ReviewStatus::Rejected) {
+            ReviewStatus::Rejected
+        } else if flags.is_empty() {
+            ReviewStatus::Approved
+        } else {
+            ReviewStatus::Pending
+        };
+        let reason = (!flags.is_empty()).then(|| {
+            flags.iter().map(|f| f.reason.as_str()).collect::<Vec<_>>().join("; ")
+        });
+        if let Some(reason) = &reason {
+            info!(book_id, status = status.as_str(), reason = %reason, "Reseña marcada por la moderación");
+        }
+        Ok(ModerationResult { status, reason })
+    }
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(value) => matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        Err(_) => default,
+    }
+}
+
+// Minúsculas, sin puntuación y con un solo espacio entre palabras
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket_db_pools::sqlx::sqlite::SqlitePoolOptions;
+
+    fn status(flag: Option<Flag>) -> Option<ReviewStatus> {
+        flag.map(|f| f.status)
+    }
+
+    #[test]
+    fn normalize_strips_case_and_punctuation() {
+        assert_eq!(normalize("  ¡Muy   BUENO!, de verdad... "), "muy bueno de verdad");
+        assert_eq!(normalize("Ñandú-Águila"), "ñandú águila");
+        assert_eq!(normalize("?!"), "");
+    }
+
+    #[test]
+    fn banned_words_match_whole_words_and_phrases() {
+        let rule = BannedWords::new(["as".to_string(), "Muy Malo".to_string(), " , ".to_string()]);
+        assert_eq!(rule.phrases, ["as", "muy malo"]);
+
+        assert_eq!(status(rule.check("Una casa bonita")), None);
+        assert_eq!(status(rule.check("Es un AS.")), Some(ReviewStatus::Rejected));
+        assert_eq!(status(rule.check("muy... malo!")), Some(ReviewStatus::Rejected));
+        assert_eq!(status(rule.check("muy maloso")), None);
+    }
+
+    #[test]
+    fn links_over_the_limit_go_to_review() {
+        let rule = Links { max_links: 1 };
+        assert!(rule.check("ver https://a.com").is_none());
+        let flag = rule.check("ver HTTPS://a.com y www.b.com").unwrap();
+        assert_eq!(flag.status, ReviewStatus::Pending);
+        assert_eq!(flag.reason, "contiene 2 links");
+    }
+
+    #[test]
+    fn spam_heuristics() {
+        let rule = SpamHeuristics;
+        assert!(rule.check("Un libro entretenido, lo recomiendo").is_none());
+        assert!(rule.check("Genial!!!!!!!!!").is_none());
+        assert_eq!(status(rule.check("Genial!!!!!!!!!!")), Some(ReviewStatus::Pending));
+        assert_eq!(status(rule.check("ESTE LIBRO ES LO MEJOR DEL AÑO")), Some(ReviewStatus::Pending));
+        assert!(rule.check("ME GUSTÓ").is_none());
+        assert_eq!(status(rule.check(&"compra ya ".repeat(6))), Some(ReviewStatus::Pending));
+    }
+
+    #[rocket::async_test]
+    async fn review_keeps_the_most_severe_flag() {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE reviews (id INTEGER PRIMARY KEY, book_id INTEGER, review_text TEXT, status TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO reviews (id, book_id, review_text, status) VALUES (1, 7, 'Muy bueno.', 'approved')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let rules: Vec<Box<dyn Rule>> = vec![Box::new(BannedWords::new(["malo".to_string()])), Box::new(Links { max_links: 0 })];
+        let moderation = Moderation::new(rules, true);
+
+        let ok = moderation.review(&pool, 7, None, "Me gustó").await.unwrap();
+        assert_eq!((ok.status, ok.reason), (ReviewStatus::Approved, None));
+
+        let pending = moderation.review(&pool, 7, None, "ver www.a.com").await.unwrap();
+        assert_eq!(pending.status, ReviewStatus::Pending);
+
+        let rejected = moderation.review(&pool, 7, None, "malo, ver www.a.com").await.unwrap();
+        assert_eq!(rejected.status, ReviewStatus::Rejected);
+        assert_eq!(rejected.reason.as_deref(), Some("contiene lenguaje no permitido ('malo'); contiene 1 links"));
+
+        let duplicate = moderation.review(&pool, 7, None, "¡MUY bueno!").await.unwrap();
+        assert_eq!(duplicate.status, ReviewStatus::Rejected);
+        let editing_itself = moderation.review(&pool, 7, Some(1), "¡MUY bueno!").await.unwrap();
+        assert_eq!(editing_itself.status, ReviewStatus::Approved);
+    }
+}
@@ -1,24 +1,121 @@
-use rocket_db_pools::sqlx::{self, SqlitePool, Row};
+use rocket_db_pools::sqlx::{self, QueryBuilder, Sqlite, SqlitePool, SqliteExecutor, Row};
+use rocket_db_pools::sqlx::sqlite::SqliteRow;
 use crate::models::*;
+use crate::uploads::{self, ImageKind};
+use super::books::escape_like;
 
-pub async fn get_all_authors(pool: &SqlitePool) -> Result<Vec<Author>, sqlx::Error> {
-    let rows = sqlx::query("SELECT id, name, birth_date, country, description FROM authors ORDER BY name")
-        .fetch_all(pool)
-        .await?;
-    
+// FROM + agregados por autor (cantidad de libros, ventas totales y rating promedio de sus libros)
+const LIST_FROM: &str = "
+    FROM authors a
+    LEFT JOIN (
+        SELECT author_id, COUNT(*) AS books_count, SUM(sales_count) AS total_sales
+        FROM books GROUP BY author_id
+    ) bs ON bs.author_id = a.id
+    LEFT JOIN (
+        SELECT b.author_id, AVG(r.rating) AS avg_rating
+        FROM reviews r JOIN books b ON b.id = r.book_id
+        WHERE r.status = 'approved'
+        GROUP BY b.author_id
+    ) rs ON rs.author_id = a.id
+    WHERE 1 = 1";
+
+fn push_author_filters(qb: &mut QueryBuilder<'_, Sqlite>, filter: &AuthorFilter) {
+    if let Some(q) = &filter.q {
+        qb.push(" AND LOWER(a.name) LIKE ")
+            .push_bind(format!("%{}%", escape_like(&q.to_lowercase())))
+            .push(" ESCAPE '\\'");
+    }
+    if let Some(country) = &filter.country {
+        qb.push(" AND LOWER(a.country) = LOWER(").push_bind(country.clone()).push(")");
+    }
+    if let Some(min_rating) = filter.min_rating {
+        qb.push(" AND rs.avg_rating >= ").push_bind(min_rating);
+    }
+    if let Some(min_sales) = filter.min_sales {
+        qb.push(" AND COALESCE(bs.total_sales, 0) >= ").push_bind(min_sales);
+    }
+}
+
+fn author_sort_column(sort: AuthorSort) -> &'static str {
+    match sort {
+        AuthorSort::Name => "a.name COLLATE NOCASE",
+        AuthorSort::BirthDate => "a.birth_date",
+        AuthorSort::Country => "a.country COLLATE NOCASE",
+        AuthorSort::Books => "COALESCE(bs.books_count, 0)",
+        AuthorSort::Sales => "COALESCE(bs.total_sales, 0)",
+        AuthorSort::Rating => "COALESCE(rs.avg_rating, 0)",
+    }
+}
+
+// Listado paginado de autores con filtros y orden aplicados en SQL
+// Argumentos:
+// - pool: &SqlitePool - Pool de conexiones a la BD
+// - filter: &AuthorFilter - Filtros opcionales
+// - sort: AuthorSort / order: SortOrder - Columna y dirección (desempate por id)
+// - limit / offset: i64 - Ventana de la página
+// Retorna: Result<(Vec<Author>, i64), sqlx::Error> - Autores de la página y total que cumple los filtros
+pub async fn list_authors(
+    pool: &SqlitePool,
+    filter: &AuthorFilter,
+    sort: AuthorSort,
+    order: SortOrder,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Author>, i64), sqlx::Error> {
+    let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*)");
+    count.push(LIST_FROM);
+    push_author_filters(&mut count, filter);
+    let total: i64 = count.build_query_scalar().fetch_one(pool).await?;
+
+    let mut qb = QueryBuilder::<Sqlite>::new("SELECT a.id, a.name, a.birth_date, a.country, a.description, a.photo, a.updated_at, a.version");
+    qb.push(LIST_FROM);
+    push_author_filters(&mut qb, filter);
+    qb.push(format_args!(" ORDER BY {} {}, a.id ASC", author_sort_column(sort), order.as_str()));
+    qb.push(" LIMIT ").push_bind(limit).push(" OFFSET ").push_bind(offset);
+
+    let rows = qb.build().fetch_all(pool).await?;
     let authors = rows.iter().map(|row| Author {
         id: Some(row.get("id")),
         name: row.get("name"),
         birth_date: row.get("birth_date"),
         country: row.get("country"),
         description: row.get("description"),
+        photo: photo_ref(row),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
     }).collect();
-    
-    Ok(authors)
+
+    Ok((authors, total))
+}
+
+// Autores con los mismos filtros y orden que el listado, sin paginar (GET /api/export/authors)
+pub fn export_authors_query(filter: &AuthorFilter, sort: AuthorSort, order: SortOrder) -> QueryBuilder<'static, Sqlite> {
+    let mut qb = QueryBuilder::<Sqlite>::new(
+        "SELECT a.id, a.name, a.birth_date, a.country, a.description,
+                COALESCE(bs.books_count, 0) AS books_count, COALESCE(bs.total_sales, 0) AS total_sales,
+                ROUND(rs.avg_rating, 2) AS avg_rating"
+    );
+    qb.push(LIST_FROM);
+    push_author_filters(&mut qb, filter);
+    qb.push(format_args!(" ORDER BY {} {}, a.id ASC", author_sort_column(sort), order.as_str()));
+    qb
+}
+
+pub fn author_export_from_row(row: &SqliteRow) -> AuthorExportRow {
+    AuthorExportRow {
+        id: row.get("id"),
+        name: row.get("name"),
+        birth_date: row.get("birth_date"),
+        country: row.get("country"),
+        description: row.get("description"),
+        books_count: row.get("books_count"),
+        total_sales: row.get("total_sales"),
+        avg_rating: row.get("avg_rating"),
+    }
 }
 
 pub async fn get_author_by_id(pool: &SqlitePool, id: i32) -> Result<Option<Author>, sqlx::Error> {
-    let row = sqlx::query("SELECT id, name, birth_date, country, description FROM authors WHERE id = ?")
+    let row = sqlx::query("SELECT id, name, birth_date, country, description, photo, updated_at, version FROM authors WHERE id = ?")
         .bind(id)
         .fetch_optional(pool)
         .await?;
@@ -29,10 +126,21 @@ pub async fn get_author_by_id(pool: &SqlitePool, id: i32) -> Result<Option<Autho
         birth_date: row.get("birth_date"),
         country: row.get("country"),
         description: row.get("description"),
+        photo: photo_ref(&row),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
     }))
 }
 
-pub async fn create_author(pool: &SqlitePool, author: &CreateAuthor) -> Result<i32, sqlx::Error> {
+// Para validar `author_id` de libros (validation::check)
+pub async fn author_exists(pool: &SqlitePool, id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM authors WHERE id = ?)")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn create_author(db: impl SqliteExecutor<'_>, author: &CreateAuthor) -> Result<i32, sqlx::Error> {
     let id = sqlx::query_scalar(
         "INSERT INTO authors (name, birth_date, country, description) VALUES (?, ?, ?, ?) RETURNING id"
     )
@@ -40,40 +148,60 @@ pub async fn create_author(pool: &SqlitePool, author: &CreateAuthor) -> Result<i
     .bind(&author.birth_date)
     .bind(&author.country)
     .bind(&author.description)
-    .fetch_one(pool)
+    .fetch_one(db)
     .await?;
     
     Ok(id)
 }
 
-pub async fn update_author(pool: &SqlitePool, id: i32, author: &UpdateAuthor) -> Result<Option<Author>, sqlx::Error> {
-    let row = sqlx::query(
+// Autores con ese nombre exacto (sin distinguir mayúsculas); más de uno = nombre ambiguo
+pub async fn find_author_ids_by_name(db: impl SqliteExecutor<'_>, name: &str) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM authors WHERE name = ? COLLATE NOCASE ORDER BY id LIMIT 2")
+        .bind(name.trim())
+        .fetch_all(db)
+        .await
+}
+
+// Solo se aplica si `author.version` (cuando viene) es la versión actual
+pub async fn update_author(pool: &SqlitePool, id: i32, author: &UpdateAuthor) -> Result<UpdateOutcome<Author>, sqlx::Error> {
+    // fetch_all corre el UPDATE hasta el final: con fetch_optional sqlx puede dejarlo sin confirmar
+    // hasta que la conexión se reusa, y otra conexión leería el valor anterior
+    let rows = sqlx::query(
         "UPDATE authors
          SET name = COALESCE(?, name),
              birth_date = COALESCE(?, birth_date),
              country = COALESCE(?, country),
-             description = COALESCE(?, description)
-         WHERE id = ?
-         RETURNING id, name, birth_date, country, description"
+             description = COALESCE(?, description),
+             updated_at = CURRENT_TIMESTAMP,
+             version = version + 1
+         WHERE id = ? AND version = COALESCE(?, version)
+         RETURNING id, name, birth_date, country, description, photo, updated_at, version"
     )
     .bind(&author.name)
     .bind(&author.birth_date)
     .bind(&author.country)
     .bind(&author.description)
     .bind(id)
-    .fetch_optional(pool)
+    .bind(author.version)
+    .fetch_all(pool)
     .await?;
 
-    Ok(row.map(|row| Author {
+    let Some(row) = rows.first() else {
+        return super::missed_update(pool, "authors", id).await;
+    };
+    Ok(UpdateOutcome::Updated(Author {
         id: Some(row.get("id")),
         name: row.get("name"),
         birth_date: row.get("birth_date"),
         country: row.get("country"),
         description: row.get("description"),
+        photo: photo_ref(row),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
     }))
 }
 
-pub async fn delete_author(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error> {
+pub async fn delete_author(pool: &SqlitePool, id: i32) -> Result<bool, sqlx::Error> {
     // Primero obtener todos los libros de este autor
     let book_ids: Vec<i32> = sqlx::query("SELECT id FROM books WHERE author_id = ?")
         .bind(id)
@@ -101,10 +229,48 @@ pub async fn delete_author(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error
         .await?;
 
     // Finalmente borrar el autor
-    sqlx::query("DELETE FROM authors WHERE id = ?")
+    let result = sqlx::query("DELETE FROM authors WHERE id = ?")
         .bind(id)
         .execute(pool)
         .await?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
+}
+
+fn photo_ref(row: &SqliteRow) -> Option<ImageRef> {
+    row.get::<Option<String>, _>("photo").map(|name| uploads::image_ref(ImageKind::AuthorPhoto, &name))
+}
+
+// Foto actual: None si el autor no existe, Some(None) si no tiene foto
+pub async fn get_author_photo(pool: &SqlitePool, id: i32) -> Result<Option<Option<String>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT photo FROM authors WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+// Cambia la foto solo si sigue siendo `previous` (compare-and-set); es una edición más: sube `version`
+// Argumentos:
+// - previous: Option<&str> - Foto que se leyó antes (None = sin foto)
+// - photo: Option<&str> - Nombre devuelto por uploads::store, o None para quitarla
+// Retorna: Result<bool, sqlx::Error> - false si el autor no existe o su foto ya cambió
+pub async fn set_author_photo(pool: &SqlitePool, id: i32, previous: Option<&str>, photo: Option<&str>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE authors SET photo = ?, updated_at = CURRENT_TIMESTAMP, version = version + 1
+         WHERE id = ? AND photo IS ?"
+    )
+        .bind(photo)
+        .bind(id)
+        .bind(previous)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Cuántos autores usan un archivo (las imágenes se comparten si tienen el mismo contenido)
+pub async fn count_photo_uses(pool: &SqlitePool, photo: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM authors WHERE photo = ?")
+        .bind(photo)
+        .fetch_one(pool)
+        .await
 }
@@ -1,66 +1,215 @@
-use rocket_db_pools::sqlx::{self, SqlitePool, Row};
+use rocket_db_pools::sqlx::{self, QueryBuilder, Sqlite, SqlitePool, SqliteExecutor, Row};
+use rocket_db_pools::sqlx::sqlite::SqliteRow;
 use crate::models::*;
+use crate::uploads::{self, ImageKind};
 
-pub async fn get_all_books(pool: &SqlitePool) -> Result<Vec<BookWithAuthor>, sqlx::Error> {
-    let rows = sqlx::query(
-        "SELECT b.id, b.title, b.summary, b.publication_date, b.sales_count, b.author_id,
-                a.id as author_id, a.name as author_name, a.birth_date as author_birth_date, 
-                a.country as author_country, a.description as author_description
-         FROM books b
-         JOIN authors a ON b.author_id = a.id
-         ORDER BY b.title"
-    )
-    .fetch_all(pool)
-    .await?;
-    
-    let books = rows.iter().map(|row| BookWithAuthor {
+// Columnas de libro + autor que lee `book_from_row`
+const BOOK_COLUMNS: &str = "b.id, b.title, b.summary, b.publication_date, b.sales_count, b.cover, b.updated_at, b.version,
+                a.id as author_id, a.name as author_name, a.birth_date as author_birth_date,
+                a.country as author_country, a.description as author_description, a.photo as author_photo,
+                a.updated_at as author_updated_at, a.version as author_version";
+
+fn book_from_row(row: &SqliteRow) -> BookWithAuthor {
+    BookWithAuthor {
         id: Some(row.get("id")),
         title: row.get("title"),
         summary: row.get("summary"),
         publication_date: row.get("publication_date"),
         sales_count: row.get("sales_count"),
+        cover: row.get::<Option<String>, _>("cover").map(|name| uploads::image_ref(ImageKind::BookCover, &name)),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
         author: Author {
             id: Some(row.get("author_id")),
             name: row.get("author_name"),
             birth_date: row.get("author_birth_date"),
             country: row.get("author_country"),
             description: row.get("author_description"),
+            photo: row.get::<Option<String>, _>("author_photo").map(|name| uploads::image_ref(ImageKind::AuthorPhoto, &name)),
+            updated_at: row.get("author_updated_at"),
+            version: row.get("author_version"),
         },
-    }).collect();
-    
-    Ok(books)
+    }
+}
+
+pub async fn get_all_books(pool: &SqlitePool) -> Result<Vec<BookWithAuthor>, sqlx::Error> {
+    let sql = format!(
+        "SELECT {BOOK_COLUMNS}
+         FROM books b
+         JOIN authors a ON b.author_id = a.id
+         ORDER BY b.title"
+    );
+    let rows = sqlx::query(&sql).fetch_all(pool).await?;
+    Ok(rows.iter().map(book_from_row).collect())
 }
 
 pub async fn get_book_by_id(pool: &SqlitePool, id: i32) -> Result<Option<BookWithAuthor>, sqlx::Error> {
-    let row = sqlx::query(
-        "SELECT b.id, b.title, b.summary, b.publication_date, b.sales_count, b.author_id,
-                a.id as author_id, a.name as author_name, a.birth_date as author_birth_date, 
-                a.country as author_country, a.description as author_description
+    let sql = format!(
+        "SELECT {BOOK_COLUMNS}
          FROM books b
          JOIN authors a ON b.author_id = a.id
          WHERE b.id = ?"
-    )
-    .bind(id)
-    .fetch_optional(pool)
-    .await?;
-    
-    Ok(row.map(|row| BookWithAuthor {
-        id: Some(row.get("id")),
+    );
+    let row = sqlx::query(&sql)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(book_from_row))
+}
+
+// Para validar `book_id` de reseñas y ventas (validation::check)
+pub async fn book_exists(pool: &SqlitePool, id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM books WHERE id = ?)")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+}
+
+// FROM + JOINs comunes a listado y conteo (rating promedio precalculado por libro)
+const LIST_FROM: &str = "
+    FROM books b
+    JOIN authors a ON b.author_id = a.id
+    LEFT JOIN (SELECT book_id, AVG(rating) AS avg_rating FROM reviews WHERE status = 'approved' GROUP BY book_id) r ON r.book_id = b.id
+    WHERE 1 = 1";
+
+fn push_book_filters(qb: &mut QueryBuilder<'_, Sqlite>, filter: &BookFilter) {
+    if let Some(q) = &filter.q {
+        qb.push(" AND LOWER(b.title) LIKE ")
+            .push_bind(format!("%{}%", escape_like(&q.to_lowercase())))
+            .push(" ESCAPE '\\'");
+    }
+    if let Some(author_id) = filter.author_id {
+        qb.push(" AND b.author_id = ").push_bind(author_id);
+    }
+    if let Some(country) = &filter.country {
+        qb.push(" AND LOWER(a.country) = LOWER(").push_bind(country.clone()).push(")");
+    }
+    if let Some(year_from) = filter.year_from {
+        qb.push(" AND CAST(strftime('%Y', b.publication_date) AS INTEGER) >= ").push_bind(year_from);
+    }
+    if let Some(year_to) = filter.year_to {
+        qb.push(" AND CAST(strftime('%Y', b.publication_date) AS INTEGER) <= ").push_bind(year_to);
+    }
+    if let Some(min_rating) = filter.min_rating {
+        qb.push(" AND r.avg_rating >= ").push_bind(min_rating);
+    }
+    if let Some(min_sales) = filter.min_sales {
+        qb.push(" AND b.sales_count >= ").push_bind(min_sales);
+    }
+}
+
+fn book_sort_column(sort: BookSort) -> &'static str {
+    match sort {
+        BookSort::Title => "b.title COLLATE NOCASE",
+        BookSort::PublicationDate => "b.publication_date",
+        BookSort::Sales => "b.sales_count",
+        BookSort::Rating => "COALESCE(r.avg_rating, 0)",
+        BookSort::Author => "a.name COLLATE NOCASE",
+    }
+}
+
+// Listado paginado de libros con filtros y orden aplicados en SQL
+// Argumentos:
+// - pool: &SqlitePool - Pool de conexiones a la BD
+// - filter: &BookFilter - Filtros opcionales
+// - sort: BookSort / order: SortOrder - Columna y dirección (desempate por id)
+// - limit / offset: i64 - Ventana de la página
+// Retorna: Result<(Vec<BookWithAuthor>, i64), sqlx::Error> - Libros de la página y total que cumple los filtros
+pub async fn list_books(
+    pool: &SqlitePool,
+    filter: &BookFilter,
+    sort: BookSort,
+    order: SortOrder,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<BookWithAuthor>, i64), sqlx::Error> {
+    let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*)");
+    count.push(LIST_FROM);
+    push_book_filters(&mut count, filter);
+    let total: i64 = count.build_query_scalar().fetch_one(pool).await?;
+
+    let mut qb = QueryBuilder::<Sqlite>::new(format!("SELECT {BOOK_COLUMNS}"));
+    qb.push(LIST_FROM);
+    push_book_filters(&mut qb, filter);
+    qb.push(format_args!(" ORDER BY {} {}, b.id ASC", book_sort_column(sort), order.as_str()));
+    qb.push(" LIMIT ").push_bind(limit).push(" OFFSET ").push_bind(offset);
+
+    let rows = qb.build().fetch_all(pool).await?;
+    let books = rows.iter().map(book_from_row).collect();
+
+    Ok((books, total))
+}
+
+// ===== Exportación (GET /api/export/books y /api/export/catalog) =====
+// Devuelven la consulta armada; el handler la recorre como stream sin cargar toda la tabla.
+
+// Libros con los mismos filtros y orden que el listado, sin paginar
+pub fn export_books_query(filter: &BookFilter, sort: BookSort, order: SortOrder) -> QueryBuilder<'static, Sqlite> {
+    let mut qb = QueryBuilder::<Sqlite>::new(
+        "SELECT b.id, b.title, b.summary, b.publication_date, b.author_id, COALESCE(b.sales_count, 0) AS sales_count"
+    );
+    qb.push(LIST_FROM);
+    push_book_filters(&mut qb, filter);
+    qb.push(format_args!(" ORDER BY {} {}, b.id ASC", book_sort_column(sort), order.as_str()));
+    qb
+}
+
+pub fn book_export_from_row(row: &SqliteRow) -> BookExportRow {
+    BookExportRow {
+        id: row.get("id"),
         title: row.get("title"),
         summary: row.get("summary"),
         publication_date: row.get("publication_date"),
+        author_id: row.get("author_id"),
         sales_count: row.get("sales_count"),
-        author: Author {
-            id: Some(row.get("author_id")),
-            name: row.get("author_name"),
-            birth_date: row.get("author_birth_date"),
-            country: row.get("author_country"),
-            description: row.get("author_description"),
-        },
-    }))
+    }
+}
+
+// Libro + autor + rating promedio + ventas, con los filtros del listado de libros
+pub fn export_catalog_query(filter: &BookFilter, sort: BookSort, order: SortOrder) -> QueryBuilder<'static, Sqlite> {
+    let mut qb = QueryBuilder::<Sqlite>::new(
+        "SELECT b.id AS book_id, b.title, b.publication_date, a.id AS author_id, a.name AS author_name,
+                a.country AS author_country, ROUND(r.avg_rating, 2) AS avg_rating,
+                COALESCE(r.reviews_count, 0) AS reviews_count, COALESCE(b.sales_count, 0) AS total_sales
+         FROM books b
+         JOIN authors a ON b.author_id = a.id
+         LEFT JOIN (
+             SELECT book_id, AVG(rating) AS avg_rating, COUNT(*) AS reviews_count FROM reviews WHERE status = 'approved' GROUP BY book_id
+         ) r ON r.book_id = b.id
+         WHERE 1 = 1"
+    );
+    push_book_filters(&mut qb, filter);
+    qb.push(format_args!(" ORDER BY {} {}, b.id ASC", book_sort_column(sort), order.as_str()));
+    qb
+}
+
+pub fn catalog_export_from_row(row: &SqliteRow) -> CatalogExportRow {
+    CatalogExportRow {
+        book_id: row.get("book_id"),
+        title: row.get("title"),
+        publication_date: row.get("publication_date"),
+        author_id: row.get("author_id"),
+        author_name: row.get("author_name"),
+        author_country: row.get("author_country"),
+        avg_rating: row.get("avg_rating"),
+        reviews_count: row.get("reviews_count"),
+        total_sales: row.get("total_sales"),
+    }
+}
+
+pub async fn count_books(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM books").fetch_one(pool).await
 }
 
-pub async fn create_book(pool: &SqlitePool, book: &CreateBook) -> Result<i32, sqlx::Error> {
+// IDs de los libros de un autor (para cascadas de caché e índice de búsqueda)
+pub async fn get_book_ids_by_author(pool: &SqlitePool, author_id: i32) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM books WHERE author_id = ?")
+        .bind(author_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn create_book(db: impl SqliteExecutor<'_>, book: &CreateBook) -> Result<i32, sqlx::Error> {
     let id = sqlx::query_scalar(
         "INSERT INTO books (title, summary, publication_date, author_id) VALUES (?, ?, ?, ?) RETURNING id"
     )
@@ -68,44 +217,40 @@ pub async fn create_book(pool: &SqlitePool, book: &CreateBook) -> Result<i32, sq
     .bind(&book.summary)
     .bind(&book.publication_date)
     .bind(book.author_id)
-    .fetch_one(pool)
+    .fetch_one(db)
     .await?;
     
     Ok(id)
 }
 
-pub async fn update_book(pool: &SqlitePool, id: i32, book: &UpdateBook) -> Result<Option<BookWithAuthor>, sqlx::Error> {
-    let row = sqlx::query(
+// Solo se aplica si `book.version` (cuando viene) es la versión actual
+pub async fn update_book(pool: &SqlitePool, id: i32, book: &UpdateBook) -> Result<UpdateOutcome<BookWithAuthor>, sqlx::Error> {
+    let result = sqlx::query(
         "UPDATE books
          SET title = COALESCE(?, title),
              summary = COALESCE(?, summary),
              publication_date = COALESCE(?, publication_date),
-             author_id = COALESCE(?, author_id)
-         WHERE id = ?
-         RETURNING id, title, summary, publication_date, sales_count, author_id"
+             author_id = COALESCE(?, author_id),
+             version = version + 1
+         WHERE id = ? AND version = COALESCE(?, version)"
     )
     .bind(&book.title)
     .bind(&book.summary)
     .bind(&book.publication_date)
-    .bind(&book.author_id)
+    .bind(book.author_id)
     .bind(id)
-    .fetch_optional(pool)
+    .bind(book.version)
+    .execute(pool)
     .await?;
+    if result.rows_affected() == 0 {
+        return super::missed_update(pool, "books", id).await;
+    }
 
-    Ok(row.map(|row| BookWithAuthor {
-        id: Some(row.get("id")),
-        title: row.get("title"),
-        summary: row.get("summary"),
-        publication_date: row.get("publication_date"),
-        sales_count: row.get("sales_count"),
-        author: Author {
-            id: Some(row.get("author_id")),
-            name: String::new(),          // ⚠️ Podés hacer join si querés traer el autor completo
-            birth_date: String::new(),
-            country: String::new(),
-            description: None,
-        },
-    }))
+    // Se relee con el JOIN para devolver el autor completo
+    Ok(match get_book_by_id(pool, id).await? {
+        Some(book) => UpdateOutcome::Updated(book),
+        None => UpdateOutcome::NotFound,
+    })
 }
 
 pub async fn delete_book(pool: &SqlitePool, id: i32) -> Result<bool, sqlx::Error> {
@@ -130,7 +275,7 @@ pub async fn delete_book(pool: &SqlitePool, id: i32) -> Result<bool, sqlx::Error
     Ok(result.rows_affected() > 0)
 }
 
-fn escape_like(input: &str) -> String {
+pub(crate) fn escape_like(input: &str) -> String {
     let mut out = String::with_capacity(input.len());
     for ch in input.chars() {
         match ch {
@@ -157,6 +302,20 @@ pub async fn search_books_by_description(
     page: i64,
     per_page: i64,
 ) -> Result<(Vec<BookWithAuthor>, i64), sqlx::Error> {
+    let offset = (page.max(1) - 1).saturating_mul(per_page.max(1));
+    let (items, total) = search_books_range(pool, q, offset, per_page).await?;
+    Ok((items.into_iter().map(|(book, _)| book).collect(), total))
+}
+
+/// Igual que `search_books_by_description` pero con offset/limit directos
+/// (lo usa el fallback SQLite de /search, que recibe `from`/`size`).
+/// Cada libro viene con su rating promedio (reseñas aprobadas), calculado en la misma consulta.
+pub async fn search_books_range(
+    pool: &SqlitePool,
+    q: &str,
+    offset: i64,
+    limit: i64,
+) -> Result<(Vec<(BookWithAuthor, Option<f64>)>, i64), sqlx::Error> {
     let q = q.trim();
     if q.is_empty() {
         return Ok((Vec::new(), 0));
@@ -195,7 +354,7 @@ pub async fn search_books_by_description(
         "#
     );
 
-    let mut tq = sqlx::query_scalar::<_, i64>(&total_sql)
+    let tq = sqlx::query_scalar::<_, i64>(&total_sql)
         // contigua
         .bind(&like_any).bind(&like_any).bind(&like_any)
         // subsecuencia
@@ -210,17 +369,8 @@ pub async fn search_books_by_description(
     // ----- datos paginados -----
     let data_sql = format!(
         r#"
-        SELECT
-            b.id               AS book_id,
-            b.title            AS book_title,
-            b.summary          AS book_summary,
-            b.publication_date AS book_publication_date,
-            b.sales_count      AS book_sales_count,
-            a.id               AS author_id,
-            a.name             AS author_name,
-            a.birth_date       AS author_birth_date,
-            a.country          AS author_country,
-            a.description      AS author_description
+        SELECT {BOOK_COLUMNS},
+               (SELECT AVG(r.rating) FROM reviews r WHERE r.book_id = b.id AND r.status = 'approved') AS avg_rating
         FROM books b
         JOIN authors a ON b.author_id = a.id
         WHERE {where_clause}
@@ -229,32 +379,93 @@ pub async fn search_books_by_description(
         "#
     );
 
-    let offset = (page.max(1) - 1) * per_page.max(1);
-
     let rows = sqlx::query(&data_sql)
         // contigua
         .bind(&like_any).bind(&like_any).bind(&like_any)
         // subsecuencia
         .bind(&like_subseq).bind(&like_subseq).bind(&like_subseq)
-        .bind(per_page)
-        .bind(offset)
+        .bind(limit.max(1))
+        .bind(offset.max(0))
         .fetch_all(pool)
         .await?;
 
-    let items = rows.into_iter().map(|row| BookWithAuthor {
-        id: Some(row.get::<i64, _>("book_id") as i32),
-        title: row.get::<String, _>("book_title"),
-        summary: row.get::<Option<String>, _>("book_summary"),
-        publication_date: row.get::<String, _>("book_publication_date"),
-        sales_count: (row.get::<i64, _>("book_sales_count") as i32),
-        author: Author {
-            id: Some(row.get::<i64, _>("author_id") as i32),
-            name: row.get::<String, _>("author_name"),
-            birth_date: row.get::<String, _>("author_birth_date"),
-            country: row.get::<String, _>("author_country"),
-            description: row.get::<Option<String>, _>("author_description"),
-        },
-    }).collect();
+    let items = rows.iter().map(|row| (book_from_row(row), row.get("avg_rating"))).collect();
 
     Ok((items, total))
-}
\ No newline at end of file
+}
+
+/// Sugerencias para autocompletar sin OpenSearch: títulos y autores cuyo
+/// inicio (o el inicio de alguna palabra) coincide con el prefijo.
+pub async fn suggest_titles(pool: &SqlitePool, prefix: &str, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+    let prefix = prefix.trim().to_lowercase();
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+    let esc = escape_like(&prefix);
+    let starts = format!("{}%", esc);
+    let word_starts = format!("% {}%", esc);
+
+    sqlx::query_scalar(
+        r#"
+        SELECT text FROM (
+            SELECT title AS text, CASE WHEN LOWER(title) LIKE ?1 ESCAPE '\' THEN 0 ELSE 1 END AS rank
+              FROM books
+             WHERE LOWER(title) LIKE ?1 ESCAPE '\' OR LOWER(title) LIKE ?2 ESCAPE '\'
+            UNION
+            SELECT name AS text, CASE WHEN LOWER(name) LIKE ?1 ESCAPE '\' THEN 2 ELSE 3 END AS rank
+              FROM authors
+             WHERE LOWER(name) LIKE ?1 ESCAPE '\' OR LOWER(name) LIKE ?2 ESCAPE '\'
+        )
+        GROUP BY text
+        ORDER BY MIN(rank), text
+        LIMIT ?3
+        "#,
+    )
+    .bind(&starts)
+    .bind(&word_starts)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+// Portada actual: None si el libro no existe, Some(None) si no tiene portada
+pub async fn get_book_cover(pool: &SqlitePool, id: i32) -> Result<Option<Option<String>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT cover FROM books WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+// Portadas de los libros de un autor (se borran en cascada con él)
+pub async fn get_covers_by_author(pool: &SqlitePool, author_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT cover FROM books WHERE author_id = ? AND cover IS NOT NULL")
+        .bind(author_id)
+        .fetch_all(pool)
+        .await
+}
+
+// Igual que `set_author_photo`: compare-and-set sobre la portada anterior, sube `version`
+// Argumentos:
+// - previous: Option<&str> - Portada que se leyó antes (None = sin portada)
+// - cover: Option<&str> - Nombre devuelto por uploads::store, o None para quitarla
+// Retorna: Result<bool, sqlx::Error> - false si el libro no existe o su portada ya cambió
+pub async fn set_book_cover(pool: &SqlitePool, id: i32, previous: Option<&str>, cover: Option<&str>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE books SET cover = ?, updated_at = CURRENT_TIMESTAMP, version = version + 1
+         WHERE id = ? AND cover IS ?"
+    )
+        .bind(cover)
+        .bind(id)
+        .bind(previous)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Cuántos libros usan un archivo (las imágenes se comparten si tienen el mismo contenido)
+pub async fn count_cover_uses(pool: &SqlitePool, cover: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM books WHERE cover = ?")
+        .bind(cover)
+        .fetch_one(pool)
+        .await
+}
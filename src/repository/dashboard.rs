@@ -1,13 +1,13 @@
 use rocket_db_pools::sqlx::{self, SqlitePool};
-use crate::models::*;
 
 pub async fn get_dashboard_stats(pool: &SqlitePool) -> Result<serde_json::Value, sqlx::Error> {
     let total_authors: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM authors").fetch_one(pool).await?;
     let total_books: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books").fetch_one(pool).await?;
-    let total_reviews: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reviews").fetch_one(pool).await?;
-    let total_sales: i64 = sqlx::query_scalar("SELECT SUM(sales) FROM yearly_sales").fetch_one(pool).await?;
+    let total_reviews: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reviews WHERE status = 'approved'").fetch_one(pool).await?;
+    let total_sales: i64 = sqlx::query_scalar("SELECT COALESCE(SUM(sales), 0) FROM yearly_sales").fetch_one(pool).await?;
     
-    let avg_rating: f64 = sqlx::query_scalar("SELECT AVG(rating) FROM reviews").fetch_one(pool).await?;
+    // Sin reseñas aprobadas (BD nueva o pre-moderación) AVG/SUM dan NULL: el dashboard muestra 0
+    let avg_rating: f64 = sqlx::query_scalar("SELECT COALESCE(AVG(rating), 0.0) FROM reviews WHERE status = 'approved'").fetch_one(pool).await?;
     
     Ok(serde_json::json!({
         "total_authors": total_authors,
@@ -16,4 +16,22 @@ pub async fn get_dashboard_stats(pool: &SqlitePool) -> Result<serde_json::Value,
         "total_sales": total_sales,
         "average_rating": avg_rating
     }))
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket_db_pools::sqlx::sqlite::SqlitePoolOptions;
+    use std::path::Path;
+
+    #[rocket::async_test]
+    async fn empty_database_reports_zeros() {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        crate::migrations::run(&pool, Path::new("migrations")).await.unwrap();
+
+        let stats = get_dashboard_stats(&pool).await.unwrap();
+        assert_eq!(stats["total_books"], 0);
+        assert_eq!(stats["total_sales"], 0);
+        assert_eq!(stats["average_rating"], 0.0);
+    }
+}
@@ -1,14 +1,35 @@
 // src/repository/mod.rs
-pub mod init;
 pub mod authors;
 pub mod books;
 pub mod reviews;
 pub mod sales;
 pub mod dashboard;
+pub mod stats;
+pub mod users;
 
 // Re-exports para mantener el API anterior:
-pub use authors::{get_all_authors, get_author_by_id, create_author, update_author, delete_author};
+pub use authors::{get_author_by_id, create_author, update_author, delete_author};
 pub use books::{get_all_books, get_book_by_id, create_book, update_book, delete_book};
 pub use reviews::{get_reviews_by_book, create_review, update_review, delete_review};
 pub use sales::{get_yearly_sales_by_book, create_yearly_sales, update_yearly_sales, delete_yearly_sales};
 pub use dashboard::get_dashboard_stats;
+
+use rocket_db_pools::sqlx::{self, SqlitePool};
+use crate::models::UpdateOutcome;
+
+// `version` actual de un registro (None si no existe)
+// Argumentos: table: &'static str - Tabla de la entidad (constante, nunca viene del cliente)
+pub(crate) async fn current_version(pool: &SqlitePool, table: &'static str, id: i32) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar(&format!("SELECT version FROM {table} WHERE id = ?"))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+// Por qué un UPDATE con `version` no tocó filas: el registro no existe o otro usuario lo editó antes
+pub(crate) async fn missed_update<T>(pool: &SqlitePool, table: &'static str, id: i32) -> Result<UpdateOutcome<T>, sqlx::Error> {
+    Ok(match current_version(pool, table, id).await? {
+        Some(current) => UpdateOutcome::Conflict { current },
+        None => UpdateOutcome::NotFound,
+    })
+}
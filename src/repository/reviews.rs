@@ -1,12 +1,16 @@
-use rocket_db_pools::sqlx::{self, SqlitePool, Row};
+use rocket_db_pools::sqlx::{self, QueryBuilder, Sqlite, SqlitePool, SqliteExecutor, Row};
+use rocket_db_pools::sqlx::sqlite::SqliteRow;
 use crate::models::*;
 
+// Solo reseñas aprobadas (las pendientes/rechazadas se ven en /api/admin/reviews)
 pub async fn get_reviews_by_book(pool: &SqlitePool, book_id: i32) -> Result<Vec<ReviewWithBook>, sqlx::Error> {
     let rows = sqlx::query(
-        "SELECT r.id, r.book_id, b.title as book_title, r.review_text, r.rating, r.positive_votes, r.created_at
+        "SELECT r.id, r.book_id, b.title as book_title, r.review_text, r.rating, r.positive_votes, r.created_at,
+                r.user_id, u.username, r.updated_at, r.version
          FROM reviews r
          JOIN books b ON r.book_id = b.id
-         WHERE r.book_id = ?
+         LEFT JOIN users u ON r.user_id = u.id
+         WHERE r.book_id = ? AND r.status = 'approved'
          ORDER BY r.created_at DESC"
     )
     .bind(book_id)
@@ -21,41 +25,114 @@ pub async fn get_reviews_by_book(pool: &SqlitePool, book_id: i32) -> Result<Vec<
         rating: row.get("rating"),
         positive_votes: row.get("positive_votes"),
         created_at: row.get("created_at"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
     }).collect();
     
     Ok(reviews)
 }
 
-pub async fn create_review(pool: &SqlitePool, review: &CreateReview) -> Result<i32, sqlx::Error> {
+// Reseña aprobada (None si no existe o no es pública)
+pub async fn get_review_by_id(pool: &SqlitePool, id: i32) -> Result<Option<ReviewWithBook>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT r.id, r.book_id, b.title as book_title, r.review_text, r.rating, r.positive_votes, r.created_at,
+                r.user_id, u.username, r.updated_at, r.version
+         FROM reviews r
+         JOIN books b ON r.book_id = b.id
+         LEFT JOIN users u ON r.user_id = u.id
+         WHERE r.id = ? AND r.status = 'approved'"
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| ReviewWithBook {
+        id: Some(row.get("id")),
+        book_id: row.get("book_id"),
+        book_title: row.get("book_title"),
+        review_text: row.get("review_text"),
+        rating: row.get("rating"),
+        positive_votes: row.get("positive_votes"),
+        created_at: row.get("created_at"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
+    }))
+}
+
+// Autor de una reseña: None si no existe, Some(None) si es anterior al sistema de usuarios
+pub async fn get_review_owner(pool: &SqlitePool, id: i32) -> Result<Option<Option<i32>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT user_id FROM reviews WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+// Libro al que pertenece una reseña
+pub async fn get_review_book_id(pool: &SqlitePool, id: i32) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT book_id FROM reviews WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+// Argumentos:
+// - review: &CreateReview - Datos de la reseña
+// - user_id: Option<i32> - Usuario autenticado que la escribe (None en la importación masiva)
+// - moderation: &ModerationResult - Estado decidido por src/moderation.rs
+pub async fn create_review(db: impl SqliteExecutor<'_>, review: &CreateReview, user_id: Option<i32>, moderation: &ModerationResult) -> Result<i32, sqlx::Error> {
     let id = sqlx::query_scalar(
-        "INSERT INTO reviews (book_id, review_text, rating) VALUES (?, ?, ?) RETURNING id"
+        "INSERT INTO reviews (book_id, review_text, rating, user_id, status, moderation_reason)
+         VALUES (?, ?, ?, ?, ?, ?) RETURNING id"
     )
     .bind(review.book_id)
     .bind(&review.review_text)
     .bind(review.rating)
-    .fetch_one(pool)
+    .bind(user_id)
+    .bind(moderation.status.as_str())
+    .bind(&moderation.reason)
+    .fetch_one(db)
     .await?;
     
     Ok(id)
 }
 
-pub async fn update_review(pool: &SqlitePool, id: i32, review: &UpdateReview) -> Result<Option<ReviewWithBook>, sqlx::Error> {
-    let row = sqlx::query(
+// Solo se aplica si `review.version` (cuando viene) es la versión actual
+// Argumentos: moderation: Option<&ModerationResult> - Nuevo estado si cambió el texto (None lo conserva)
+pub async fn update_review(pool: &SqlitePool, id: i32, review: &UpdateReview, moderation: Option<&ModerationResult>) -> Result<UpdateOutcome<ReviewWithBook>, sqlx::Error> {
+    // fetch_all: ver authors::update_author (el UPDATE queda confirmado antes de responder)
+    let rows = sqlx::query(
         "UPDATE reviews
          SET review_text = COALESCE(?, review_text),
              rating = COALESCE(?, rating),
-             positive_votes = COALESCE(?, positive_votes)
-         WHERE id = ?
-         RETURNING id, book_id, review_text, rating, positive_votes, created_at"
+             status = COALESCE(?, status),
+             moderation_reason = CASE WHEN ? THEN ? ELSE moderation_reason END,
+             moderated_at = CASE WHEN ? THEN NULL ELSE moderated_at END,
+             moderated_by = CASE WHEN ? THEN NULL ELSE moderated_by END,
+             updated_at = CURRENT_TIMESTAMP,
+             version = version + 1
+         WHERE id = ? AND version = COALESCE(?, version)
+         RETURNING id, book_id, review_text, rating, positive_votes, created_at, user_id, NULL AS username, updated_at, version"
     )
     .bind(&review.review_text)
-    .bind(&review.rating)
-    .bind(&review.positive_votes)
+    .bind(review.rating)
+    .bind(moderation.map(|m| m.status.as_str()))
+    .bind(moderation.is_some())
+    .bind(moderation.and_then(|m| m.reason.as_deref()))
+    .bind(moderation.is_some())
+    .bind(moderation.is_some())
     .bind(id)
-    .fetch_optional(pool)
+    .bind(review.version)
+    .fetch_all(pool)
     .await?;
 
-    Ok(row.map(|row| ReviewWithBook {
+    let Some(row) = rows.first() else {
+        return super::missed_update(pool, "reviews", id).await;
+    };
+    Ok(UpdateOutcome::Updated(ReviewWithBook {
         id: Some(row.get("id")),
         book_id: row.get("book_id"),
         book_title: String::new(), // si querés traete el título con un JOIN
@@ -63,9 +140,61 @@ pub async fn update_review(pool: &SqlitePool, id: i32, review: &UpdateReview) ->
         rating: row.get("rating"),
         positive_votes: row.get("positive_votes"),
         created_at: row.get("created_at"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
     }))
 }
 
+// ===== Votos (tabla review_votes; los triggers mantienen reviews.positive_votes) =====
+
+// Registra el voto; votar dos veces no cuenta doble. Solo se votan reseñas aprobadas
+// Argumentos:
+// - review_id: i32 - Reseña votada
+// - voter: &str - Clave del votante (`user:<id>` o `anon:<hash>`)
+// - user_id: Option<i32> - Usuario autenticado, si lo hay
+// Retorna: Result<Option<(bool, i32)>, sqlx::Error> - None si la reseña no existe;
+// si existe, (si el voto es nuevo, votos positivos actuales)
+pub async fn add_vote(pool: &SqlitePool, review_id: i32, voter: &str, user_id: Option<i32>) -> Result<Option<(bool, i32)>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query(
+        "INSERT INTO review_votes (review_id, voter, user_id)
+         SELECT id, ?, ? FROM reviews WHERE id = ? AND status = 'approved'
+         ON CONFLICT (review_id, voter) DO NOTHING"
+    )
+    .bind(voter)
+    .bind(user_id)
+    .bind(review_id)
+    .execute(&mut *tx)
+    .await?;
+    let votes = positive_votes(&mut tx, review_id).await?;
+    tx.commit().await?;
+    Ok(votes.map(|votes| (result.rows_affected() > 0, votes)))
+}
+
+// Retira el voto (si no había, no hace nada)
+// Retorna: Result<Option<(bool, i32)>, sqlx::Error> - None si la reseña no existe;
+// si existe, (si se borró un voto, votos positivos actuales)
+pub async fn remove_vote(pool: &SqlitePool, review_id: i32, voter: &str) -> Result<Option<(bool, i32)>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query("DELETE FROM review_votes WHERE review_id = ? AND voter = ?")
+        .bind(review_id)
+        .bind(voter)
+        .execute(&mut *tx)
+        .await?;
+    let votes = positive_votes(&mut tx, review_id).await?;
+    tx.commit().await?;
+    Ok(votes.map(|votes| (result.rows_affected() > 0, votes)))
+}
+
+async fn positive_votes(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, review_id: i32) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT COALESCE(positive_votes, 0) FROM reviews WHERE id = ? AND status = 'approved'")
+        .bind(review_id)
+        .fetch_optional(&mut **tx)
+        .await
+}
+
 pub async fn delete_review(pool: &SqlitePool, id: i32) -> Result<bool, sqlx::Error> {
     let result = sqlx::query("DELETE FROM reviews WHERE id = ?")
         .bind(id)
@@ -73,3 +202,187 @@ pub async fn delete_review(pool: &SqlitePool, id: i32) -> Result<bool, sqlx::Err
         .await?;
     Ok(result.rows_affected() > 0)
 }
+
+// Todas las reseñas aprobadas con el título del libro (usado por la reindexación en OpenSearch)
+pub async fn get_all_reviews(pool: &SqlitePool) -> Result<Vec<ReviewWithBook>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT r.id, r.book_id, b.title as book_title, r.review_text, r.rating, r.positive_votes, r.created_at,
+                r.user_id, u.username, r.updated_at, r.version
+         FROM reviews r
+         JOIN books b ON r.book_id = b.id
+         LEFT JOIN users u ON r.user_id = u.id
+         WHERE r.status = 'approved'
+         ORDER BY r.id"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let reviews = rows.iter().map(|row| ReviewWithBook {
+        id: Some(row.get("id")),
+        book_id: row.get("book_id"),
+        book_title: row.get("book_title"),
+        review_text: row.get("review_text"),
+        rating: row.get("rating"),
+        positive_votes: row.get("positive_votes"),
+        created_at: row.get("created_at"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
+    }).collect();
+
+    Ok(reviews)
+}
+
+// Rating promedio de un libro (None si no tiene reseñas aprobadas)
+pub async fn get_average_rating(pool: &SqlitePool, book_id: i32) -> Result<Option<f64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT AVG(rating) FROM reviews WHERE book_id = ? AND status = 'approved'")
+        .bind(book_id)
+        .fetch_one(pool)
+        .await
+}
+
+// Rating promedio por libro: (book_id, promedio). Solo libros con reseñas aprobadas.
+pub async fn get_average_ratings(pool: &SqlitePool) -> Result<Vec<(i32, f64)>, sqlx::Error> {
+    let rows = sqlx::query("SELECT book_id, AVG(rating) AS avg_rating FROM reviews WHERE status = 'approved' GROUP BY book_id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.iter().map(|row| (row.get("book_id"), row.get("avg_rating"))).collect())
+}
+
+// Reseñas aprobadas para GET /api/export/reviews (filtros opcionales), en orden de id
+pub fn export_reviews_query(book_id: Option<i32>, min_rating: Option<i32>) -> QueryBuilder<'static, Sqlite> {
+    let mut qb = QueryBuilder::<Sqlite>::new(
+        "SELECT r.id, r.book_id, b.title as book_title, r.review_text, r.rating, COALESCE(r.positive_votes, 0) AS positive_votes,
+                r.created_at, r.user_id, u.username, r.updated_at, r.version
+         FROM reviews r
+         JOIN books b ON r.book_id = b.id
+         LEFT JOIN users u ON r.user_id = u.id
+         WHERE r.status = 'approved'"
+    );
+    if let Some(book_id) = book_id {
+        qb.push(" AND r.book_id = ").push_bind(book_id);
+    }
+    if let Some(min_rating) = min_rating {
+        qb.push(" AND r.rating >= ").push_bind(min_rating);
+    }
+    qb.push(" ORDER BY r.id");
+    qb
+}
+
+pub fn review_export_from_row(row: &SqliteRow) -> ReviewWithBook {
+    ReviewWithBook {
+        id: Some(row.get("id")),
+        book_id: row.get("book_id"),
+        book_title: row.get("book_title"),
+        review_text: row.get("review_text"),
+        rating: row.get("rating"),
+        positive_votes: row.get("positive_votes"),
+        created_at: row.get("created_at"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        updated_at: row.get("updated_at"),
+        version: row.get("version"),
+    }
+}
+
+// ===== Moderación (src/moderation.rs, /api/admin/reviews) =====
+
+// Textos de las reseñas no rechazadas de un libro (detección de duplicados)
+// Argumentos: exclude_id: Option<i32> - Reseña que se está editando
+pub async fn get_review_texts_by_book(db: impl SqliteExecutor<'_>, book_id: i32, exclude_id: Option<i32>) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT review_text FROM reviews WHERE book_id = ? AND status != 'rejected' AND id != COALESCE(?, -1)")
+        .bind(book_id)
+        .bind(exclude_id)
+        .fetch_all(db)
+        .await
+}
+
+const MODERATED_COLUMNS: &str =
+    "r.id, r.book_id, b.title AS book_title, r.review_text, r.rating, COALESCE(r.positive_votes, 0) AS positive_votes,
+     r.created_at, r.user_id, u.username, r.status, r.moderation_reason, r.moderated_at, r.moderated_by, r.version";
+
+fn moderated_from_row(row: &SqliteRow) -> ModeratedReview {
+    let status: String = row.get("status");
+    ModeratedReview {
+        id: row.get("id"),
+        book_id: row.get("book_id"),
+        book_title: row.get("book_title"),
+        review_text: row.get("review_text"),
+        rating: row.get("rating"),
+        positive_votes: row.get("positive_votes"),
+        created_at: row.get("created_at"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        status: ReviewStatus::parse(&status).unwrap_or(ReviewStatus::Pending),
+        moderation_reason: row.get("moderation_reason"),
+        moderated_at: row.get("moderated_at"),
+        moderated_by: row.get("moderated_by"),
+        version: row.get("version"),
+    }
+}
+
+// Reseña en cualquier estado (cola de moderación y formulario de edición)
+pub async fn get_moderated_review(pool: &SqlitePool, id: i32) -> Result<Option<ModeratedReview>, sqlx::Error> {
+    let sql = format!(
+        "SELECT {MODERATED_COLUMNS}
+         FROM reviews r
+         JOIN books b ON r.book_id = b.id
+         LEFT JOIN users u ON r.user_id = u.id
+         WHERE r.id = ?"
+    );
+    let row = sqlx::query(&sql).bind(id).fetch_optional(pool).await?;
+    Ok(row.as_ref().map(moderated_from_row))
+}
+
+// Cola de moderación: reseñas en `status`, las más antiguas primero
+pub async fn list_reviews_by_status(pool: &SqlitePool, status: ReviewStatus, limit: i64, offset: i64) -> Result<Vec<ModeratedReview>, sqlx::Error> {
+    let sql = format!(
+        "SELECT {MODERATED_COLUMNS}
+         FROM reviews r
+         JOIN books b ON r.book_id = b.id
+         LEFT JOIN users u ON r.user_id = u.id
+         WHERE r.status = ?
+         ORDER BY r.created_at, r.id
+         LIMIT ? OFFSET ?"
+    );
+    let rows = sqlx::query(&sql)
+        .bind(status.as_str())
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(moderated_from_row).collect())
+}
+
+pub async fn count_reviews_by_status(pool: &SqlitePool, status: ReviewStatus) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM reviews WHERE status = ?")
+        .bind(status.as_str())
+        .fetch_one(pool)
+        .await
+}
+
+// Decisión del admin
+// Argumentos:
+// - status: ReviewStatus - Nuevo estado
+// - reason: Option<&str> - Motivo (reemplaza al de las reglas automáticas)
+// - moderated_by: i32 - Admin que decide
+// Retorna: Result<Option<ModeratedReview>, sqlx::Error> - None si la reseña no existe
+pub async fn set_review_status(pool: &SqlitePool, id: i32, status: ReviewStatus, reason: Option<&str>, moderated_by: i32) -> Result<Option<ModeratedReview>, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE reviews
+         SET status = ?, moderation_reason = ?, moderated_at = CURRENT_TIMESTAMP, moderated_by = ?
+         WHERE id = ?"
+    )
+    .bind(status.as_str())
+    .bind(reason)
+    .bind(moderated_by)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    get_moderated_review(pool, id).await
+}
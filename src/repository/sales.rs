@@ -1,9 +1,10 @@
-use rocket_db_pools::sqlx::{self, SqlitePool, Row};
+use rocket_db_pools::sqlx::{self, QueryBuilder, Sqlite, SqlitePool, SqliteExecutor, Row};
+use rocket_db_pools::sqlx::sqlite::SqliteRow;
 use crate::models::*;
 
 pub async fn get_yearly_sales_by_book(pool: &SqlitePool, book_id: i32) -> Result<Vec<YearlySalesWithBook>, sqlx::Error> {
     let rows = sqlx::query(
-        "SELECT ys.id, ys.book_id, b.title as book_title, ys.year, ys.sales
+        "SELECT ys.id, ys.book_id, b.title as book_title, ys.year, ys.sales, ys.version
          FROM yearly_sales ys
          JOIN books b ON ys.book_id = b.id
          WHERE ys.book_id = ?
@@ -19,44 +20,53 @@ pub async fn get_yearly_sales_by_book(pool: &SqlitePool, book_id: i32) -> Result
         book_title: row.get("book_title"),
         year: row.get("year"),
         sales: row.get("sales"),
+        version: row.get("version"),
     }).collect();
     
     Ok(sales)
 }
 
-pub async fn create_yearly_sales(pool: &SqlitePool, sales: &CreateYearlySales) -> Result<i32, sqlx::Error> {
+pub async fn create_yearly_sales(db: impl SqliteExecutor<'_>, sales: &CreateYearlySales) -> Result<i32, sqlx::Error> {
     let id = sqlx::query_scalar(
         "INSERT INTO yearly_sales (book_id, year, sales) VALUES (?, ?, ?) RETURNING id"
     )
     .bind(sales.book_id)
     .bind(sales.year)
     .bind(sales.sales)
-    .fetch_one(pool)
+    .fetch_one(db)
     .await?;
     
     Ok(id)
 }
 
-pub async fn update_yearly_sales(pool: &SqlitePool, id: i32, sales: &UpdateYearlySales) -> Result<Option<YearlySalesWithBook>, sqlx::Error> {
-    let row = sqlx::query(
+// Solo se aplica si `sales.version` (cuando viene) es la versión actual
+pub async fn update_yearly_sales(pool: &SqlitePool, id: i32, sales: &UpdateYearlySales) -> Result<UpdateOutcome<YearlySalesWithBook>, sqlx::Error> {
+    // fetch_all: ver authors::update_author (el UPDATE queda confirmado antes de responder)
+    let rows = sqlx::query(
         "UPDATE yearly_sales
          SET year = COALESCE(?, year),
-             sales = COALESCE(?, sales)
-         WHERE id = ?
-         RETURNING id, book_id, year, sales"
+             sales = COALESCE(?, sales),
+             version = version + 1
+         WHERE id = ? AND version = COALESCE(?, version)
+         RETURNING id, book_id, year, sales, version"
     )
-    .bind(&sales.year)
-    .bind(&sales.sales)
+    .bind(sales.year)
+    .bind(sales.sales)
     .bind(id)
-    .fetch_optional(pool)
+    .bind(sales.version)
+    .fetch_all(pool)
     .await?;
 
-    Ok(row.map(|row| YearlySalesWithBook {
+    let Some(row) = rows.first() else {
+        return super::missed_update(pool, "yearly_sales", id).await;
+    };
+    Ok(UpdateOutcome::Updated(YearlySalesWithBook {
         id: Some(row.get("id")),
         book_id: row.get("book_id"),
         book_title: String::new(), // podés hacer join a books si querés
         year: row.get("year"),
         sales: row.get("sales"),
+        version: row.get("version"),
     }))
 }
 
@@ -67,3 +77,93 @@ pub async fn delete_yearly_sales(pool: &SqlitePool, id: i32) -> Result<bool, sql
         .await?;
     Ok(result.rows_affected() > 0)
 }
+
+
+// Libro cuyo `sales_count` no coincide con la suma de sus ventas anuales
+#[derive(Debug, serde::Serialize)]
+pub struct SalesDrift {
+    pub book_id: i32,
+    pub title: String,
+    pub stored: i64,
+    pub computed: i64,
+}
+
+pub async fn get_yearly_sales_book_id(pool: &SqlitePool, id: i32) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT book_id FROM yearly_sales WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+// Compara books.sales_count con SUM(yearly_sales.sales) y, si `apply`, corrige las diferencias.
+// Los triggers de 003_sales_count_triggers.sql mantienen el valor al día; esto repara datos
+// anteriores a la migración o modificados a mano.
+// Argumentos:
+// - pool: &SqlitePool - Pool de conexiones a la BD
+// - apply: bool - false = solo reporta
+// Retorna: Result<Vec<SalesDrift>, sqlx::Error> - Libros con diferencias (antes de corregir)
+pub async fn recompute_sales_counts(pool: &SqlitePool, apply: bool) -> Result<Vec<SalesDrift>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let rows = sqlx::query(
+        "SELECT b.id, b.title, COALESCE(b.sales_count, 0) AS stored, COALESCE(s.total, 0) AS computed
+         FROM books b
+         LEFT JOIN (SELECT book_id, SUM(sales) AS total FROM yearly_sales GROUP BY book_id) s ON s.book_id = b.id
+         WHERE COALESCE(b.sales_count, 0) <> COALESCE(s.total, 0)
+         ORDER BY b.id"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let drift: Vec<SalesDrift> = rows.iter().map(|row| SalesDrift {
+        book_id: row.get("id"),
+        title: row.get("title"),
+        stored: row.get("stored"),
+        computed: row.get("computed"),
+    }).collect();
+
+    if apply && !drift.is_empty() {
+        sqlx::query(
+            "UPDATE books
+             SET sales_count = (SELECT COALESCE(SUM(sales), 0) FROM yearly_sales WHERE book_id = books.id)
+             WHERE COALESCE(sales_count, 0) <> (SELECT COALESCE(SUM(sales), 0) FROM yearly_sales WHERE book_id = books.id)"
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(drift)
+}
+
+// Ventas para GET /api/export/sales (filtros opcionales), por libro y año
+pub fn export_sales_query(book_id: Option<i32>, year_from: Option<i32>, year_to: Option<i32>) -> QueryBuilder<'static, Sqlite> {
+    let mut qb = QueryBuilder::<Sqlite>::new(
+        "SELECT ys.id, ys.book_id, b.title as book_title, ys.year, ys.sales, ys.version
+         FROM yearly_sales ys
+         JOIN books b ON ys.book_id = b.id
+         WHERE 1 = 1"
+    );
+    if let Some(book_id) = book_id {
+        qb.push(" AND ys.book_id = ").push_bind(book_id);
+    }
+    if let Some(year_from) = year_from {
+        qb.push(" AND ys.year >= ").push_bind(year_from);
+    }
+    if let Some(year_to) = year_to {
+        qb.push(" AND ys.year <= ").push_bind(year_to);
+    }
+    qb.push(" ORDER BY ys.book_id, ys.year");
+    qb
+}
+
+pub fn sales_export_from_row(row: &SqliteRow) -> YearlySalesWithBook {
+    YearlySalesWithBook {
+        id: Some(row.get("id")),
+        book_id: row.get("book_id"),
+        book_title: row.get("book_title"),
+        year: row.get("year"),
+        sales: row.get("sales"),
+        version: row.get("version"),
+    }
+}
@@ -0,0 +1,127 @@
+use rocket_db_pools::sqlx::{self, SqlitePool, Row};
+use crate::models::*;
+
+// Autores con cantidad de libros, rating promedio (de las reseñas aprobadas de sus libros) y ventas totales
+pub async fn get_author_stats(pool: &SqlitePool) -> Result<Vec<AuthorStats>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT a.id AS author_id, a.name AS author_name,
+                COALESCE(bs.books, 0) AS books,
+                rs.avg_rating,
+                COALESCE(bs.total_sales, 0) AS total_sales
+         FROM authors a
+         LEFT JOIN (
+             SELECT author_id, COUNT(*) AS books, SUM(sales_count) AS total_sales
+             FROM books GROUP BY author_id
+         ) bs ON bs.author_id = a.id
+         LEFT JOIN (
+             SELECT b.author_id, AVG(r.rating) AS avg_rating
+             FROM reviews r JOIN books b ON b.id = r.book_id
+             WHERE r.status = 'approved'
+             GROUP BY b.author_id
+         ) rs ON rs.author_id = a.id
+         ORDER BY total_sales DESC, a.name"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(|row| AuthorStats {
+        author_id: row.get("author_id"),
+        author_name: row.get("author_name"),
+        books: row.get("books"),
+        avg_rating: row.get("avg_rating"),
+        total_sales: row.get("total_sales"),
+    }).collect())
+}
+
+// Top N libros por rating promedio, con la reseña más alta y más baja (a igual rating, la más votada).
+// ROW_NUMBER() elige ambas reseñas por libro en la misma consulta.
+pub async fn get_top_rated_books(pool: &SqlitePool, limit: i64) -> Result<Vec<TopRatedBook>, sqlx::Error> {
+    let rows = sqlx::query(
+        "WITH ranked AS (
+             SELECT r.id, r.book_id, r.rating, COALESCE(r.positive_votes, 0) AS positive_votes, r.review_text,
+                    AVG(r.rating) OVER (PARTITION BY r.book_id) AS avg_rating,
+                    COUNT(*) OVER (PARTITION BY r.book_id) AS review_count,
+                    ROW_NUMBER() OVER (PARTITION BY r.book_id ORDER BY r.rating DESC, r.positive_votes DESC, r.id) AS hi_rank,
+                    ROW_NUMBER() OVER (PARTITION BY r.book_id ORDER BY r.rating ASC, r.positive_votes DESC, r.id) AS lo_rank
+             FROM reviews r
+             WHERE r.status = 'approved'
+         ),
+         top AS (
+             SELECT DISTINCT book_id, avg_rating, review_count FROM ranked
+             ORDER BY avg_rating DESC, review_count DESC, book_id
+             LIMIT ?
+         )
+         SELECT t.book_id, b.title, a.name AS author_name, t.avg_rating, t.review_count,
+                hi.id AS hi_id, hi.rating AS hi_rating, hi.positive_votes AS hi_votes, hi.review_text AS hi_text,
+                lo.id AS lo_id, lo.rating AS lo_rating, lo.positive_votes AS lo_votes, lo.review_text AS lo_text
+         FROM top t
+         JOIN books b ON b.id = t.book_id
+         JOIN authors a ON a.id = b.author_id
+         LEFT JOIN ranked hi ON hi.book_id = t.book_id AND hi.hi_rank = 1
+         LEFT JOIN ranked lo ON lo.book_id = t.book_id AND lo.lo_rank = 1
+         ORDER BY t.avg_rating DESC, t.review_count DESC, t.book_id"
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    let snippet = |row: &sqlx::sqlite::SqliteRow, prefix: &str| -> Option<ReviewSnippet> {
+        let id: Option<i32> = row.get(format!("{prefix}_id").as_str());
+        id.map(|id| ReviewSnippet {
+            id,
+            rating: row.get(format!("{prefix}_rating").as_str()),
+            positive_votes: row.get(format!("{prefix}_votes").as_str()),
+            review_text: row.get(format!("{prefix}_text").as_str()),
+        })
+    };
+
+    Ok(rows.iter().map(|row| TopRatedBook {
+        book_id: row.get("book_id"),
+        title: row.get("title"),
+        author_name: row.get("author_name"),
+        avg_rating: row.get("avg_rating"),
+        review_count: row.get("review_count"),
+        highest: snippet(row, "hi"),
+        lowest: snippet(row, "lo"),
+    }).collect())
+}
+
+// Top N libros por ventas históricas, con ventas totales del autor (SUM OVER autor)
+// y posición entre los libros del mismo año de publicación (RANK OVER año)
+pub async fn get_top_selling_books(pool: &SqlitePool, limit: i64) -> Result<Vec<TopSellingBook>, sqlx::Error> {
+    let rows = sqlx::query(
+        "WITH ranked AS (
+             SELECT b.id AS book_id, b.title, b.author_id, a.name AS author_name,
+                    CAST(strftime('%Y', b.publication_date) AS INTEGER) AS publication_year,
+                    COALESCE(b.sales_count, 0) AS sales_count,
+                    SUM(COALESCE(b.sales_count, 0)) OVER (PARTITION BY b.author_id) AS author_total_sales,
+                    RANK() OVER (
+                        PARTITION BY strftime('%Y', b.publication_date)
+                        ORDER BY COALESCE(b.sales_count, 0) DESC
+                    ) AS rank_in_year
+             FROM books b
+             JOIN authors a ON a.id = b.author_id
+         )
+         SELECT * FROM ranked
+         ORDER BY sales_count DESC, book_id
+         LIMIT ?"
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(|row| {
+        let rank_in_year: i64 = row.get("rank_in_year");
+        TopSellingBook {
+            book_id: row.get("book_id"),
+            title: row.get("title"),
+            author_id: row.get("author_id"),
+            author_name: row.get("author_name"),
+            publication_year: row.get("publication_year"),
+            sales_count: row.get("sales_count"),
+            author_total_sales: row.get("author_total_sales"),
+            rank_in_year,
+            top5_in_year: rank_in_year <= 5,
+        }
+    }).collect())
+}
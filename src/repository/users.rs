@@ -0,0 +1,142 @@
+use rocket_db_pools::sqlx::{self, SqlitePool, Row};
+use rocket_db_pools::sqlx::sqlite::SqliteRow;
+use crate::models::*;
+
+fn user_from_row(row: &SqliteRow) -> User {
+    let role: String = row.get("role");
+    User {
+        id: row.get("id"),
+        username: row.get("username"),
+        // El CHECK de la tabla garantiza un rol válido
+        role: Role::parse(&role).unwrap_or(Role::Reader),
+        created_at: row.get("created_at"),
+    }
+}
+
+// Argumentos:
+// - username: &str - Nombre de usuario (único, sin distinguir mayúsculas)
+// - password_hash: &str - Hash PHC de la contraseña (auth::hash_password)
+// - role: Role - Rol inicial
+// Retorna: Result<i32, sqlx::Error> - ID del usuario creado
+pub async fn create_user(pool: &SqlitePool, username: &str, password_hash: &str, role: Role) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar("INSERT INTO users (username, password_hash, role) VALUES (?, ?, ?) RETURNING id")
+        .bind(username)
+        .bind(password_hash)
+        .bind(role.as_str())
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn get_user_by_id(pool: &SqlitePool, id: i32) -> Result<Option<User>, sqlx::Error> {
+    let row = sqlx::query("SELECT id, username, role, created_at FROM users WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+// Usuario junto con su hash para verificar la contraseña en el login
+pub async fn get_credentials(pool: &SqlitePool, username: &str) -> Result<Option<(User, String)>, sqlx::Error> {
+    let row = sqlx::query("SELECT id, username, role, created_at, password_hash FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|row| (user_from_row(&row), row.get("password_hash"))))
+}
+
+pub async fn list_users(pool: &SqlitePool) -> Result<Vec<User>, sqlx::Error> {
+    let rows = sqlx::query("SELECT id, username, role, created_at FROM users ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(user_from_row).collect())
+}
+
+pub async fn update_user_role(pool: &SqlitePool, id: i32, role: Role) -> Result<Option<User>, sqlx::Error> {
+    let row = sqlx::query("UPDATE users SET role = ? WHERE id = ? RETURNING id, username, role, created_at")
+        .bind(role.as_str())
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+pub async fn count_admins(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = 'admin'")
+        .fetch_one(pool)
+        .await
+}
+
+// ===== Sesiones (cookie del HTML) =====
+
+// Registra una sesión nueva y de paso elimina las vencidas
+// Argumentos:
+// - token_hash: &str - SHA-256 del token que va en la cookie
+// - user_id: i32 - Usuario que inició sesión
+// - days: i64 - Vigencia de la sesión
+pub async fn create_session(pool: &SqlitePool, token_hash: &str, user_id: i32, days: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM sessions WHERE expires_at <= datetime('now')")
+        .execute(pool)
+        .await?;
+    sqlx::query("INSERT INTO sessions (token_hash, user_id, expires_at) VALUES (?, ?, datetime('now', ?))")
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(format!("+{days} days"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_user_by_session(pool: &SqlitePool, token_hash: &str) -> Result<Option<User>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT u.id, u.username, u.role, u.created_at
+         FROM sessions s
+         JOIN users u ON s.user_id = u.id
+         WHERE s.token_hash = ? AND s.expires_at > datetime('now')"
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+pub async fn delete_session(pool: &SqlitePool, token_hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM sessions WHERE token_hash = ?")
+        .bind(token_hash)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ===== Tokens de la API (Authorization: Bearer) =====
+
+pub async fn create_api_token(pool: &SqlitePool, user_id: i32, name: &str, token_hash: &str) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar("INSERT INTO api_tokens (user_id, name, token_hash) VALUES (?, ?, ?) RETURNING id")
+        .bind(user_id)
+        .bind(name)
+        .bind(token_hash)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn get_user_by_api_token(pool: &SqlitePool, token_hash: &str) -> Result<Option<User>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT u.id, u.username, u.role, u.created_at
+         FROM api_tokens t
+         JOIN users u ON t.user_id = u.id
+         WHERE t.token_hash = ?"
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+// Solo el dueño puede revocar sus tokens
+pub async fn delete_api_token(pool: &SqlitePool, id: i32, user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM api_tokens WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
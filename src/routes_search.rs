@@ -1,8 +1,12 @@
 use rocket::serde::json::Json;
+use rocket::State;
+use rocket_db_pools::sqlx::SqlitePool;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use opensearch::SearchParts;
-use crate::opensearch_client::os_client;
+use crate::models::BookWithAuthor;
+use crate::repository::books as books_repo;
+use crate::search::Search;
+use crate::Db;
 
 #[derive(Serialize)]
 struct HitItem {
@@ -18,9 +22,11 @@ struct HitItem {
 }
 
 #[derive(Serialize)]
-struct SearchResponse {
+pub struct SearchResponse {
     total: u64,
     items: Vec<HitItem>,
+    // Qué motor respondió: "opensearch" o "sqlite"
+    backend: &'static str,
 }
 
 #[derive(Deserialize)]
@@ -44,17 +50,47 @@ struct OSHit {
     highlight: Option<serde_json::Value>,
 }
 
+// GET /search
+// Usa OpenSearch si USE_OPENSEARCH está activo; si está deshabilitado o falla,
+// responde con la búsqueda de SQLite (mismo formato, `backend` indica cuál respondió)
 #[rocket::get("/search?<q>&<from>&<size>")]
-pub async fn search(q: String, from: Option<u32>, size: Option<u32>) -> Json<SearchResponse> {
-    let idx = std::env::var("OS_INDEX_BOOKS").unwrap_or_else(|_| "books".into());
+pub async fn search(
+    q: String,
+    from: Option<u32>,
+    size: Option<u32>,
+    search: &State<Search>,
+    pool: &State<Db>,
+) -> Json<SearchResponse> {
+    let from = from.unwrap_or(0);
+    let size = size.unwrap_or(10);
 
+    if search.enabled() {
+        match search_opensearch(search, &q, from, size).await {
+            Ok(out) => return Json(out),
+            Err(e) => tracing::warn!(error = %e, "OpenSearch no disponible para /search, se usa SQLite"),
+        }
+    }
+
+    match search_sqlite(&pool.0, &q, from, size).await {
+        Ok(out) => Json(out),
+        Err(e) => {
+            tracing::error!(error = %e, "Error en búsqueda SQLite");
+            Json(SearchResponse { total: 0, items: vec![], backend: BACKEND_SQLITE })
+        }
+    }
+}
+
+const BACKEND_OPENSEARCH: &str = "opensearch";
+const BACKEND_SQLITE: &str = "sqlite";
+
+async fn search_opensearch(search: &Search, q: &str, from: u32, size: u32) -> anyhow::Result<SearchResponse> {
     if q.trim().is_empty() {
-        return Json(SearchResponse { total: 0, items: vec![] });
+        return Ok(SearchResponse { total: 0, items: vec![], backend: BACKEND_OPENSEARCH });
     }
 
     let body = json!({
-        "from": from.unwrap_or(0),
-        "size": size.unwrap_or(10),
+        "from": from,
+        "size": size,
         "_source": ["id","title","author","description","genres","rating","published_year"],
         "query": {
             "bool": {
@@ -88,57 +124,177 @@ pub async fn search(q: String, from: Option<u32>, size: Option<u32>) -> Json<Sea
         });
 
 
-    let resp = os_client()
-        .search(SearchParts::Index(&[idx.as_str()]))
-        .body(body)
-        .send().await;
-
-    // Por defecto, respuesta vacía si hay error
-    let mut out = SearchResponse { total: 0, items: vec![] };
-
-    if let Ok(ok) = resp {
-        if let Ok(v) = ok.json::<OSResp>().await {
-            // total puede venir como objeto {"value":N} o número
-            let total = v.hits.total.get("value")
-                .and_then(|x| x.as_u64())
-                .or_else(|| v.hits.total.as_u64())
-                .unwrap_or(0);
-            out.total = total;
-
-            for h in v.hits.hits {
-                let s = &h._source;
-
-                // id puede venir como string o número; si no está, probamos _id numérico
-                let id = s.get("id")
-                    .and_then(|x| x.as_i64())
-                    .map(|n| n as i32)
-                    .or_else(|| s.get("id").and_then(|x| x.as_str())?.parse::<i32>().ok())
-                    .or_else(|| h._id.parse::<i32>().ok());
-
-                let title = s.get("title").and_then(|x| x.as_str()).map(|s| s.to_string());
-                let author = s.get("author").and_then(|x| x.as_str()).map(|s| s.to_string());
-                let description = s.get("description").and_then(|x| x.as_str()).map(|s| s.to_string());
-                let published_year = s.get("published_year").and_then(|x| x.as_i64()).map(|n| n as i32);
-                let rating = s.get("rating").and_then(|x| x.as_f64()).map(|f| f as f32);
-                let genres = s.get("genres")
-                    .and_then(|x| x.as_array())
-                    .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect::<Vec<_>>())
-                    .unwrap_or_default();
-
-                out.items.push(HitItem {
-                    id,
-                    title,
-                    author,
-                    description,
-                    genres,
-                    published_year,
-                    rating,
-                    score: h._score,
-                    highlight: h.highlight,
-                });
+    let v: OSResp = serde_json::from_value(search.search_books(&body).await?)?;
+    let mut out = SearchResponse { total: 0, items: vec![], backend: BACKEND_OPENSEARCH };
+
+    // total puede venir como objeto {"value":N} o número
+    let total = v.hits.total.get("value")
+        .and_then(|x| x.as_u64())
+        .or_else(|| v.hits.total.as_u64())
+        .unwrap_or(0);
+    out.total = total;
+
+    for h in v.hits.hits {
+        let s = &h._source;
+
+        // id puede venir como string o número; si no está, probamos _id numérico
+        let id = s.get("id")
+            .and_then(|x| x.as_i64())
+            .map(|n| n as i32)
+            .or_else(|| s.get("id").and_then(|x| x.as_str())?.parse::<i32>().ok())
+            .or_else(|| h._id.parse::<i32>().ok());
+
+        let title = s.get("title").and_then(|x| x.as_str()).map(|s| s.to_string());
+        let author = s.get("author").and_then(|x| x.as_str()).map(|s| s.to_string());
+        let description = s.get("description").and_then(|x| x.as_str()).map(|s| s.to_string());
+        let published_year = s.get("published_year").and_then(|x| x.as_i64()).map(|n| n as i32);
+        let rating = s.get("rating").and_then(|x| x.as_f64()).map(|f| f as f32);
+        let genres = s.get("genres")
+            .and_then(|x| x.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        out.items.push(HitItem {
+            id,
+            title,
+            author,
+            description,
+            genres,
+            published_year,
+            rating,
+            score: h._score,
+            highlight: h.highlight,
+        });
+    }
+
+    Ok(out)
+}
+
+// Fallback: búsqueda por subcadena/subsecuencia en SQLite (repository::books)
+async fn search_sqlite(pool: &SqlitePool, q: &str, from: u32, size: u32) -> Result<SearchResponse, rocket_db_pools::sqlx::Error> {
+    let (books, total) = books_repo::search_books_range(pool, q, i64::from(from), i64::from(size)).await?;
+    let items = books.into_iter().map(|(b, rating)| hit_from_book(b, q, rating.map(|r| r as f32))).collect();
+
+    Ok(SearchResponse { total: total.max(0) as u64, items, backend: BACKEND_SQLITE })
+}
+
+fn hit_from_book(b: BookWithAuthor, q: &str, rating: Option<f32>) -> HitItem {
+    let published_year = b.publication_date.get(0..4).and_then(|y| y.parse::<i32>().ok());
+
+    // Mismo formato que el highlight de OpenSearch: { campo: ["...<mark>x</mark>..."] }
+    let mut highlight = serde_json::Map::new();
+    let fields = [("title", Some(&b.title)), ("author", Some(&b.author.name)), ("description", b.summary.as_ref())];
+    for (field, text) in fields {
+        if let Some(marked) = text.and_then(|t| highlight_contiguous(t, q)) {
+            highlight.insert(field.to_string(), json!([marked]));
+        }
+    }
+    // Si no hubo coincidencia contigua, el match fue por subsecuencia
+    if highlight.is_empty() {
+        for (field, text) in fields {
+            if let Some(marked) = text.and_then(|t| highlight_subsequence(t, q)) {
+                highlight.insert(field.to_string(), json!([marked]));
+                break;
             }
         }
     }
 
-    Json(out)
+    HitItem {
+        id: b.id,
+        title: Some(b.title),
+        author: Some(b.author.name),
+        description: b.summary,
+        genres: vec![],
+        published_year,
+        rating,
+        score: None,
+        highlight: (!highlight.is_empty()).then_some(serde_json::Value::Object(highlight)),
+    }
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// Marca todas las apariciones de `q` (sin distinguir mayúsculas)
+fn highlight_contiguous(text: &str, q: &str) -> Option<String> {
+    let needle: Vec<char> = q.trim().chars().map(fold).collect();
+    if needle.is_empty() {
+        return None;
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut found = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let matches = i + needle.len() <= chars.len()
+            && chars[i..i + needle.len()].iter().zip(&needle).all(|(a, b)| fold(*a) == *b);
+        if matches {
+            out.push_str("<mark>");
+            out.extend(&chars[i..i + needle.len()]);
+            out.push_str("</mark>");
+            i += needle.len();
+            found = true;
+        } else {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+    found.then_some(out)
+}
+
+// Marca los caracteres de `q` en orden (coincidencia por subsecuencia)
+fn highlight_subsequence(text: &str, q: &str) -> Option<String> {
+    let mut needle = q.trim().chars().map(fold).peekable();
+    needle.peek()?;
+    let mut out = String::with_capacity(text.len() + 32);
+    for c in text.chars() {
+        if needle.peek() == Some(&fold(c)) {
+            needle.next();
+            out.push_str("<mark>");
+            out.push(c);
+            out.push_str("</mark>");
+        } else {
+            out.push(c);
+        }
+    }
+    needle.peek().is_none().then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contiguous_marks_every_occurrence_ignoring_case() {
+        assert_eq!(
+            highlight_contiguous("La Casa de la casa", "casa").as_deref(),
+            Some("La <mark>Casa</mark> de la <mark>casa</mark>")
+        );
+    }
+
+    #[test]
+    fn contiguous_handles_non_ascii() {
+        assert_eq!(highlight_contiguous("Ñandú", "ñan").as_deref(), Some("<mark>Ñan</mark>dú"));
+    }
+
+    #[test]
+    fn contiguous_without_match_or_query() {
+        assert_eq!(highlight_contiguous("Rayuela", "xyz"), None);
+        assert_eq!(highlight_contiguous("Rayuela", "  "), None);
+    }
+
+    #[test]
+    fn subsequence_marks_chars_in_order() {
+        assert_eq!(
+            highlight_subsequence("Rayuela", "rla").as_deref(),
+            Some("<mark>R</mark>ayue<mark>l</mark><mark>a</mark>")
+        );
+    }
+
+    #[test]
+    fn subsequence_requires_all_chars() {
+        assert_eq!(highlight_subsequence("Rayuela", "rz"), None);
+        assert_eq!(highlight_subsequence("Rayuela", ""), None);
+    }
 }
@@ -1,58 +1,42 @@
 use rocket::serde::json::Json;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use opensearch::SearchParts;
-use crate::opensearch_client::os_client;
+use rocket::State;
+use serde::Serialize;
+use crate::repository::books as books_repo;
+use crate::search::Search;
+use crate::Db;
+use tracing::{error, warn};
+
+const SUGGEST_LIMIT: u32 = 10;
 
 #[derive(Serialize)]
-struct SuggestResponse {
+pub struct SuggestResponse {
     suggestions: Vec<String>,
+    // Qué motor respondió: "opensearch" o "sqlite"
+    backend: &'static str,
 }
 
-#[derive(Deserialize)]
-struct OSugg {
-    suggest: serde_json::Value,
-}
-
+// GET /suggest
+// Completion suggester de OpenSearch; si está deshabilitado o falla, prefijos en SQLite
 #[rocket::get("/suggest?<q>")]
-pub async fn suggest(q: String) -> Json<SuggestResponse> {
-    let idx = std::env::var("OS_INDEX_BOOKS").unwrap_or_else(|_| "books".into());
-
+pub async fn suggest(q: String, search: &State<Search>, pool: &State<Db>) -> Json<SuggestResponse> {
     // Sin query => vacío
     if q.trim().is_empty() {
-        return Json(SuggestResponse { suggestions: vec![] });
+        let backend = if search.enabled() { "opensearch" } else { "sqlite" };
+        return Json(SuggestResponse { suggestions: vec![], backend });
     }
 
-    let body = json!({
-      "size": 0,
-      "suggest": {
-        "s": {
-          "prefix": q,
-          "completion": { "field": "suggest", "skip_duplicates": true, "fuzzy": { "fuzziness": 1 }, "size": 10 }
-        }
-      }
-    });
-
-    let resp = os_client()
-        .search(SearchParts::Index(&[idx.as_str()]))
-        .body(body)
-        .send().await;
-
-    let mut out: Vec<String> = Vec::new();
-
-    if let Ok(ok) = resp {
-        if let Ok(v) = ok.json::<OSugg>().await {
-            if let Some(arr) = v.suggest
-                .get("s").and_then(|s| s.get(0))
-                .and_then(|z| z.get("options")).and_then(|o| o.as_array()) {
-                for o in arr {
-                    if let Some(t) = o.get("text").and_then(|t| t.as_str()) {
-                        out.push(t.to_string());
-                    }
-                }
-            }
+    if search.enabled() {
+        match search.suggest_titles(&q, SUGGEST_LIMIT).await {
+            Ok(suggestions) => return Json(SuggestResponse { suggestions, backend: "opensearch" }),
+            Err(e) => warn!(error = %e, "OpenSearch no disponible para /suggest, se usa SQLite"),
         }
     }
 
-    Json(SuggestResponse { suggestions: out })
+    let suggestions = books_repo::suggest_titles(&pool.0, &q, i64::from(SUGGEST_LIMIT))
+        .await
+        .unwrap_or_else(|e| {
+            error!(error = %e, "Error en sugerencias SQLite");
+            vec![]
+        });
+    Json(SuggestResponse { suggestions, backend: "sqlite" })
 }
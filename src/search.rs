@@ -1,8 +1,21 @@
 use anyhow::{anyhow, Result};
-use reqwest::{Client, Url};
-use serde::Deserialize;
+use reqwest::{Client, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::env;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::logging;
+use crate::metrics;
+use crate::models::{BookWithAuthor, ReviewWithBook};
+
+// Cantidad de documentos por request a _bulk
+const BULK_CHUNK: usize = 500;
+
+// Correlación con el request HTTP que originó la llamada (src/logging.rs)
+pub const OPAQUE_ID_HEADER: &str = "X-Opaque-Id";
 
 #[derive(Clone)]
 pub struct Search {
@@ -39,11 +52,16 @@ impl Search {
         // Cliente que acepta cert self-signed (SOLO DEV). En prod, validar cert.
         let client = reqwest::Client::builder()
             .danger_accept_invalid_certs(true)
+            .timeout(Duration::from_secs(10))
             .build()?;
 
         Ok(Self { enabled, client, base, idx_books, idx_reviews, user, pass })
     }
 
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
     fn auth(&self, rb: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
         match (&self.user, &self.pass) {
             (Some(u), Some(p)) => rb.basic_auth(u, Some(p)),
@@ -51,15 +69,191 @@ impl Search {
         }
     }
 
+    // Envía el request (con auth) y registra latencia y errores de `operation` en /metrics.
+    // Un 404 no cuenta como error: varios llamadores lo usan como "no existe".
+    // Dentro de un request HTTP manda su id como X-Opaque-Id (aparece en tasks y slow logs de OpenSearch).
+    async fn send(&self, operation: &'static str, rb: reqwest::RequestBuilder) -> reqwest::Result<Response> {
+        let rb = match logging::current_request_id() {
+            Some(id) => rb.header(OPAQUE_ID_HEADER, id),
+            None => rb,
+        };
+        let started = Instant::now();
+        let result = self.auth(rb).send().await;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let ok = match &result {
+            Ok(res) => {
+                let status = res.status();
+                debug!(operation, status = status.as_u16(), latency_ms, "Request a OpenSearch");
+                status == StatusCode::NOT_FOUND || !(status.is_client_error() || status.is_server_error())
+            }
+            Err(e) => {
+                warn!(operation, latency_ms, error = %e, "Request a OpenSearch fallido");
+                false
+            }
+        };
+        metrics::record_opensearch(operation, started, ok);
+        result
+    }
+
     fn url_index_search(&self, index: &str) -> Result<Url> {
         let url = format!("{}/{}/_search", self.base, index);
         Ok(Url::parse(&url)?)
     }
 
+    // === ÍNDICES: crea `books` y `reviews` con sus mappings si no existen ===
+    pub async fn ensure_indices(&self) -> Result<()> {
+        self.ensure_index(&self.idx_books, books_index_definition()).await?;
+        self.ensure_index(&self.idx_reviews, reviews_index_definition()).await
+    }
+
+    async fn ensure_index(&self, index: &str, definition: Value) -> Result<()> {
+        let url = Url::parse(&format!("{}/{}", self.base, index))?;
+
+        let res = self.send("index_exists", self.client.head(url.clone())).await?;
+        if res.status() != StatusCode::NOT_FOUND {
+            res.error_for_status()?;
+            return Ok(());
+        }
+
+        let res = self.send("create_index", self.client.put(url).json(&definition)).await?;
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            // Otra réplica pudo crearlo entre el HEAD y el PUT
+            if !body.contains("resource_already_exists_exception") {
+                return Err(anyhow!("no se pudo crear el índice {index} ({status}): {body}"));
+            }
+        }
+        Ok(())
+    }
+
+    // === INDEXACIÓN BULK: libros (con autor desnormalizado y rating promedio) ===
+    pub async fn bulk_index_books(&self, books: &[BookWithAuthor], ratings: &HashMap<i32, f64>) -> Result<BulkOutcome> {
+        let docs = books.iter().filter_map(|b| {
+            let id = b.id?;
+            Some((id.to_string(), book_document(b, ratings.get(&id).copied())))
+        });
+        self.bulk_index(&self.idx_books, docs).await
+    }
+
+    // === INDEXACIÓN BULK: reseñas ===
+    pub async fn bulk_index_reviews(&self, reviews: &[ReviewWithBook]) -> Result<BulkOutcome> {
+        let docs = reviews.iter().filter_map(|r| Some((r.id?.to_string(), review_document(r))));
+        self.bulk_index(&self.idx_reviews, docs).await
+    }
+
+    // Envía los documentos a `_bulk` en bloques de BULK_CHUNK y junta los fallos por documento
+    async fn bulk_index(&self, index: &str, docs: impl Iterator<Item = (String, Value)>) -> Result<BulkOutcome> {
+        let url = Url::parse(&format!("{}/_bulk", self.base))?;
+        let mut outcome = BulkOutcome::default();
+        let docs: Vec<(String, Value)> = docs.collect();
+
+        for chunk in docs.chunks(BULK_CHUNK) {
+            // NDJSON: una línea de acción + una línea de documento, terminando en \n
+            let mut body = String::new();
+            for (id, doc) in chunk {
+                body.push_str(&json!({ "index": { "_index": index, "_id": id } }).to_string());
+                body.push('\n');
+                body.push_str(&doc.to_string());
+                body.push('\n');
+            }
+
+            let rb = self.client
+                .post(url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+                .body(body);
+            let res: BulkResp = self.send("bulk", rb).await?.error_for_status()?.json().await?;
+
+            for item in res.items {
+                let Some(result) = item.get("index") else { continue };
+                let ok = result.get("status").and_then(|s| s.as_u64()).is_some_and(|s| (200..300).contains(&s));
+                if ok {
+                    outcome.indexed += 1;
+                } else {
+                    outcome.failures.push(BulkFailure {
+                        index: index.to_string(),
+                        id: result.get("_id").and_then(|x| x.as_str()).unwrap_or_default().to_string(),
+                        reason: result.get("error")
+                            .and_then(|e| e.get("reason"))
+                            .and_then(|r| r.as_str())
+                            .unwrap_or("error desconocido")
+                            .to_string(),
+                    });
+                }
+            }
+        }
+        Ok(outcome)
+    }
+
+    // === WRITE-THROUGH: operaciones por documento (las usa indexer.rs) ===
+
+    // Crea o reemplaza el documento de un libro
+    pub async fn index_book(&self, book: &BookWithAuthor, rating: Option<f64>) -> Result<()> {
+        let id = book.id.ok_or_else(|| anyhow!("libro sin id"))?;
+        self.put_doc(&self.idx_books, id, &book_document(book, rating)).await
+    }
+
+    // Elimina el documento de un libro y todas sus reseñas indexadas
+    pub async fn delete_book(&self, id: i32) -> Result<()> {
+        self.delete_doc(&self.idx_books, id).await?;
+
+        let url = Url::parse(&format!("{}/{}/_delete_by_query?conflicts=proceed", self.base, self.idx_reviews))?;
+        let body = json!({ "query": { "term": { "book_id": id } } });
+        let res = self.send("delete_by_query", self.client.post(url).json(&body)).await?;
+        // Si el índice de reseñas aún no existe no hay nada que borrar
+        if res.status() != StatusCode::NOT_FOUND {
+            res.error_for_status()?;
+        }
+        Ok(())
+    }
+
+    pub async fn index_review(&self, review: &ReviewWithBook) -> Result<()> {
+        let id = review.id.ok_or_else(|| anyhow!("reseña sin id"))?;
+        self.put_doc(&self.idx_reviews, id, &review_document(review)).await
+    }
+
+    pub async fn delete_review(&self, id: i32) -> Result<()> {
+        self.delete_doc(&self.idx_reviews, id).await
+    }
+
+    async fn put_doc(&self, index: &str, id: i32, doc: &Value) -> Result<()> {
+        let url = Url::parse(&format!("{}/{}/_doc/{}", self.base, index, id))?;
+        self.send("put_doc", self.client.put(url).json(doc)).await?.error_for_status()?;
+        Ok(())
+    }
+
+    // DELETE idempotente: un 404 significa que ya no estaba indexado
+    async fn delete_doc(&self, index: &str, id: i32) -> Result<()> {
+        let url = Url::parse(&format!("{}/{}/_doc/{}", self.base, index, id))?;
+        let res = self.send("delete_doc", self.client.delete(url)).await?;
+        if res.status() != StatusCode::NOT_FOUND {
+            res.error_for_status()?;
+        }
+        Ok(())
+    }
+
+    // Estado del cluster (green / yellow / red), para /health/ready
+    pub async fn cluster_health(&self) -> Result<String> {
+        let url = Url::parse(&format!("{}/_cluster/health", self.base))?;
+        let res = self.send("cluster_health", self.client.get(url)).await?.error_for_status()?;
+        let v: Value = res.json().await?;
+        v.get("status")
+            .and_then(|s| s.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("respuesta de _cluster/health sin status"))
+    }
+
+    // === BÚSQUEDA: /search arma la consulta (con highlight) y lee los hits de la respuesta ===
+    pub async fn search_books(&self, body: &Value) -> Result<Value> {
+        let url = self.url_index_search(&self.idx_books)?;
+        let res = self.send("search", self.client.post(url).json(body)).await?.error_for_status()?;
+        Ok(res.json().await?)
+    }
+
     // === AUTOCOMPLETE: sugiere títulos (o frases) mientras escribes ===
     pub async fn suggest_titles(&self, prefix: &str, limit: u32) -> Result<Vec<String>> {
         if !self.enabled {
-            // Modo local: sin OS → devolvemos vacío (o llama a tu lógica local si la tienes)
+            // Modo local: sin OS → vacío; /suggest usa repository::books::suggest_titles
             return Ok(vec![]);
         }
         if prefix.trim().is_empty() { return Ok(vec![]); }
@@ -81,7 +275,7 @@ impl Search {
         });
 
         let rb = self.client.post(url).json(&body);
-        let res = self.auth(rb).send().await?.error_for_status()?;
+        let res = self.send("suggest", rb).await?.error_for_status()?;
         let v: Value = res.json().await?;
 
         // Ruta: suggest.s[0].options[].text
@@ -95,85 +289,116 @@ impl Search {
         }
         Ok(out)
     }
+}
 
-    // === BÚSQUEDA: devuelve IDs de libros para que luego hagas fetch a tu DB ===
-    pub async fn search_book_ids(&self, q: &str, from: u32, size: u32) -> Result<Vec<i32>> {
-        if !self.enabled {
-            // Modo local: llama a tu búsqueda actual (si la tienes):
-            // return self.search_book_ids_local(q, from, size).await;
-            return Ok(vec![]);
-        }
-        if q.trim().is_empty() { return Ok(vec![]); }
+// Resultado de una indexación bulk
+#[derive(Debug, Default, Serialize)]
+pub struct BulkOutcome {
+    pub indexed: usize,
+    pub failures: Vec<BulkFailure>,
+}
 
-        let url = self.url_index_search(&self.idx_books)?;
-        let body = json!({
-            "from": from, "size": size,
-            "_source": ["id","book_id"], // por si tu _source tiene 'id' o 'book_id'
-            "query": {
-              "multi_match": {
-                "query": q,
-                "type": "best_fields",
-                "fields": ["title^4","title.ac^5","author^3","author.ac^3","description"],
-                "fuzziness": "AUTO",
-                "operator": "and"
-              }
-            }
-        });
+#[derive(Debug, Serialize)]
+pub struct BulkFailure {
+    pub index: String,
+    pub id: String,
+    pub reason: String,
+}
 
-        let rb = self.client.post(url).json(&body);
-        let res = self.auth(rb).send().await?.error_for_status()?;
-        let v: OSResp = res.json().await?;
-
-        // Intentamos 3 caminos para sacar el ID entero:
-        // 1) _source.book_id (i32)
-        // 2) _source.id (string o número)
-        // 3) _id (string numérica)
-        let mut ids: Vec<i32> = Vec::new();
-        for h in v.hits.hits.into_iter() {
-            let mut push_id = |candidate: Option<i32>| {
-                if let Some(id) = candidate {
-                    if !ids.contains(&id) { ids.push(id); }
+// Documento de libro tal como lo consultan /search y /suggest
+fn book_document(b: &BookWithAuthor, rating: Option<f64>) -> Value {
+    let published_year = b.publication_date.get(0..4).and_then(|y| y.parse::<i32>().ok());
+    json!({
+        "id": b.id,
+        "title": b.title,
+        "author": b.author.name,
+        "author_id": b.author.id,
+        "description": b.summary,
+        "genres": [],
+        "publication_date": b.publication_date,
+        "published_year": published_year,
+        "sales_count": b.sales_count,
+        "rating": rating,
+        "suggest": { "input": [&b.title, &b.author.name] }
+    })
+}
+
+fn review_document(r: &ReviewWithBook) -> Value {
+    json!({
+        "id": r.id,
+        "book_id": r.book_id,
+        "book_title": r.book_title,
+        "review_text": r.review_text,
+        "rating": r.rating,
+        "positive_votes": r.positive_votes,
+        "created_at": r.created_at
+    })
+}
+
+// Subcampo `.ac` con edge n-grams para búsquedas por prefijo (`title.ac`, `author.ac`)
+fn autocomplete_text() -> Value {
+    json!({
+        "type": "text",
+        "fields": {
+            "ac": { "type": "text", "analyzer": "autocomplete", "search_analyzer": "autocomplete_search" }
+        }
+    })
+}
+
+fn books_index_definition() -> Value {
+    json!({
+        "settings": {
+            "analysis": {
+                "tokenizer": {
+                    "autocomplete": {
+                        "type": "edge_ngram", "min_gram": 2, "max_gram": 15,
+                        "token_chars": ["letter", "digit"]
+                    }
+                },
+                "analyzer": {
+                    "autocomplete": { "tokenizer": "autocomplete", "filter": ["lowercase"] },
+                    "autocomplete_search": { "tokenizer": "lowercase" }
                 }
-            };
-            // _source.book_id
-            if let Some(id) = h._source.get("book_id").and_then(json_to_i32) {
-                push_id(Some(id));
-                continue;
             }
-            // _source.id
-            if let Some(id) = h._source.get("id").and_then(json_to_i32) {
-                push_id(Some(id));
-                continue;
+        },
+        "mappings": {
+            "properties": {
+                "id": { "type": "integer" },
+                "title": autocomplete_text(),
+                "author": autocomplete_text(),
+                "author_id": { "type": "integer" },
+                "description": { "type": "text" },
+                "genres": { "type": "keyword" },
+                "publication_date": { "type": "date", "format": "yyyy-MM-dd||strict_date_optional_time", "ignore_malformed": true },
+                "published_year": { "type": "integer" },
+                "sales_count": { "type": "integer" },
+                "rating": { "type": "float" },
+                "suggest": { "type": "completion" }
             }
-            // _id
-            if let Ok(id) = h._id.parse::<i32>() {
-                push_id(Some(id));
+        }
+    })
+}
+
+fn reviews_index_definition() -> Value {
+    json!({
+        "mappings": {
+            "properties": {
+                "id": { "type": "integer" },
+                "book_id": { "type": "integer" },
+                "book_title": { "type": "text" },
+                "review_text": { "type": "text" },
+                "rating": { "type": "integer" },
+                "positive_votes": { "type": "integer" },
+                "created_at": { "type": "date", "format": "yyyy-MM-dd HH:mm:ss||strict_date_optional_time", "ignore_malformed": true }
             }
         }
-        Ok(ids)
-    }
+    })
 }
 
 // Helpers
 
 #[derive(Deserialize)]
-struct OSResp {
-    hits: OSHits,
-}
-#[derive(Deserialize)]
-struct OSHits {
-    hits: Vec<OSHit>,
-}
-#[derive(Deserialize)]
-struct OSHit {
-    #[serde(default)]
-    _id: String,
+struct BulkResp {
     #[serde(default)]
-    _source: Value,
-}
-
-fn json_to_i32(v: &Value) -> Option<i32> {
-    if let Some(n) = v.as_i64() { return i32::try_from(n).ok(); }
-    if let Some(s) = v.as_str() { return s.parse::<i32>().ok(); }
-    None
+    items: Vec<Value>,
 }
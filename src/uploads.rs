@@ -0,0 +1,192 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use image::{ImageFormat, ImageReader, Limits};
+use rocket::form::FromForm;
+use rocket::fs::TempFile;
+use rocket::tokio::io::AsyncReadExt;
+use rocket_db_pools::sqlx::SqlitePool;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::models::ImageRef;
+use crate::repository::{authors as authors_repo, books as books_repo};
+
+// Directorio por defecto (mismo valor que UPLOAD_DIR en los docker-compose)
+const DEFAULT_DIR: &str = "public/uploads";
+// URL pública bajo la que se sirve UPLOAD_DIR (FileServer en main.rs o nginx en modo proxy)
+const PUBLIC_PREFIX: &str = "/uploads";
+
+// Tamaño máximo aceptado. Rocket corta antes según `limits.file` de Rocket.toml (debe ser mayor).
+pub const MAX_BYTES: u64 = 5 * 1024 * 1024;
+// Dimensiones máximas: evita "bombas de descompresión" (archivos chicos que ocupan GBs al decodificar)
+const MAX_DIMENSION: u32 = 8000;
+// Lado mayor de la miniatura
+const THUMBNAIL_SIZE: u32 = 256;
+
+// Formulario multipart de subida: un campo `file`
+#[derive(FromForm)]
+pub struct ImageUpload<'r> {
+    pub file: TempFile<'r>,
+}
+
+// A qué entidad pertenece la imagen: define el subdirectorio y la columna en la BD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    AuthorPhoto,
+    BookCover,
+}
+
+impl ImageKind {
+    fn dir(&self) -> &'static str {
+        match self {
+            Self::AuthorPhoto => "authors",
+            Self::BookCover => "books",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UploadError {
+    // Supera MAX_BYTES
+    TooLarge,
+    // El contenido no es JPEG, PNG, GIF ni WebP (se ignora el Content-Type del cliente)
+    UnsupportedType,
+    // Parece imagen pero no se puede decodificar o supera MAX_DIMENSION
+    InvalidImage(String),
+    // Error escribiendo/leyendo en disco
+    Io(String),
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge => write!(f, "la imagen supera el máximo de {} MiB", MAX_BYTES / 1024 / 1024),
+            Self::UnsupportedType => write!(f, "formato no soportado (se aceptan JPEG, PNG, GIF y WebP)"),
+            Self::InvalidImage(e) => write!(f, "imagen inválida: {e}"),
+            Self::Io(e) => write!(f, "error de disco: {e}"),
+        }
+    }
+}
+
+// Directorio de subidas (configurable con UPLOAD_DIR)
+pub fn upload_dir() -> PathBuf {
+    std::env::var("UPLOAD_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(DEFAULT_DIR))
+}
+
+// Miniatura: `<hash>_thumb.jpg` para JPEG; el resto en PNG porque JPEG no admite transparencia
+fn thumbnail_name(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, "jpg" | "jpeg")) => format!("{stem}_thumb.jpg"),
+        Some((stem, _)) => format!("{stem}_thumb.png"),
+        None => format!("{name}_thumb.png"),
+    }
+}
+
+// URLs públicas de una imagen guardada
+// Argumentos:
+// - kind: ImageKind - Tipo de imagen (subdirectorio)
+// - name: &str - Nombre guardado en la BD
+pub fn image_ref(kind: ImageKind, name: &str) -> ImageRef {
+    ImageRef {
+        url: format!("{PUBLIC_PREFIX}/{}/{name}", kind.dir()),
+        thumbnail_url: format!("{PUBLIC_PREFIX}/{}/{}", kind.dir(), thumbnail_name(name)),
+    }
+}
+
+// Solo formatos web; el tipo se decide por los bytes (magic numbers), no por la extensión ni el Content-Type
+fn sniff(bytes: &[u8]) -> Option<ImageFormat> {
+    match image::guess_format(bytes).ok()? {
+        format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Gif | ImageFormat::WebP) => Some(format),
+        _ => None,
+    }
+}
+
+// Lee el archivo temporal que dejó Rocket respetando MAX_BYTES
+pub async fn read_upload(file: &TempFile<'_>) -> Result<Vec<u8>, UploadError> {
+    if file.len() > MAX_BYTES {
+        return Err(UploadError::TooLarge);
+    }
+    let mut bytes = Vec::with_capacity(file.len() as usize);
+    file.open()
+        .await
+        .map_err(|e| UploadError::Io(e.to_string()))?
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|e| UploadError::Io(e.to_string()))?;
+    Ok(bytes)
+}
+
+// Valida la imagen, genera la miniatura y guarda ambas con nombre = SHA-256 del contenido.
+// Subir dos veces la misma imagen reutiliza el archivo.
+// Argumentos:
+// - kind: ImageKind - Tipo de imagen (subdirectorio)
+// - bytes: Vec<u8> - Contenido subido
+// Retorna: Result<String, UploadError> - Nombre a guardar en la BD (`<hash>.<ext>`)
+pub async fn store(kind: ImageKind, bytes: Vec<u8>) -> Result<String, UploadError> {
+    if bytes.len() as u64 > MAX_BYTES {
+        return Err(UploadError::TooLarge);
+    }
+    let format = sniff(&bytes).ok_or(UploadError::UnsupportedType)?;
+    let dir = upload_dir().join(kind.dir());
+
+    // Decodificar y redimensionar es CPU intensivo: fuera del runtime async
+    rocket::tokio::task::spawn_blocking(move || {
+        let mut reader = ImageReader::with_format(Cursor::new(&bytes), format);
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_DIMENSION);
+        limits.max_image_height = Some(MAX_DIMENSION);
+        reader.limits(limits);
+        let image = reader.decode().map_err(|e| UploadError::InvalidImage(e.to_string()))?;
+
+        let hash: String = Sha256::digest(&bytes).iter().map(|b| format!("{b:02x}")).collect();
+        let ext = format.extensions_str().first().copied().unwrap_or("img");
+        let name = format!("{hash}.{ext}");
+
+        std::fs::create_dir_all(&dir).map_err(|e| UploadError::Io(e.to_string()))?;
+        let original = dir.join(&name);
+        if !original.exists() {
+            let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+            let mut encoded = Vec::new();
+            let written = if format == ImageFormat::Jpeg {
+                thumbnail.to_rgb8().write_to(&mut Cursor::new(&mut encoded), ImageFormat::Jpeg)
+            } else {
+                thumbnail.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+            };
+            written.map_err(|e| UploadError::InvalidImage(e.to_string()))?;
+
+            // Primero la miniatura: si existe el original, la miniatura también
+            std::fs::write(dir.join(thumbnail_name(&name)), encoded).map_err(|e| UploadError::Io(e.to_string()))?;
+            std::fs::write(&original, &bytes).map_err(|e| UploadError::Io(e.to_string()))?;
+        }
+        Ok(name)
+    })
+    .await
+    .map_err(|e| UploadError::Io(e.to_string()))?
+}
+
+// Borra original y miniatura si ninguna fila sigue usando ese archivo
+// (como el nombre es el hash, dos autores/libros pueden compartir imagen)
+pub async fn remove_if_unused(pool: &SqlitePool, kind: ImageKind, name: &str) {
+    let uses = match kind {
+        ImageKind::AuthorPhoto => authors_repo::count_photo_uses(pool, name).await,
+        ImageKind::BookCover => books_repo::count_cover_uses(pool, name).await,
+    };
+    match uses {
+        Ok(0) => {}
+        Ok(_) => return,
+        Err(e) => {
+            warn!(image = %name, error = %e, "No se pudo verificar el uso de la imagen, se conserva");
+            return;
+        }
+    }
+
+    let dir = upload_dir().join(kind.dir());
+    for file in [name.to_string(), thumbnail_name(name)] {
+        match rocket::tokio::fs::remove_file(dir.join(&file)).await {
+            Ok(()) => info!(dir = kind.dir(), file = %file, "Imagen eliminada"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(dir = kind.dir(), file = %file, error = %e, "No se pudo borrar la imagen"),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use rocket_db_pools::sqlx::{self, SqlitePool};
+use serde::Serialize;
+
+use crate::repository::{authors as authors_repo, books as books_repo};
+
+// Validación de los DTOs de alta/edición (models::Create* / Update*).
+// Cada DTO declara sus reglas en `Validate::rules`; los errores se acumulan por campo
+// para devolverlos todos juntos (API: `data` del 422, formularios HTML: junto a cada input).
+// Las referencias a otras tablas (author_id, book_id) se comprueban aparte con `check`,
+// porque necesitan la BD.
+
+// Error de un campo concreto
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+// Tablas a las que puede apuntar un DTO
+#[derive(Debug, Clone, Copy)]
+pub enum Reference {
+    Author(i32),
+    Book(i32),
+}
+
+pub trait Validate {
+    // Reglas sin acceso a la BD
+    fn rules(&self, v: &mut Validator);
+
+    // Claves foráneas del DTO: (campo, referencia)
+    fn references(&self) -> Vec<(&'static str, Reference)> {
+        Vec::new()
+    }
+
+    // Retorna: Result<(), Vec<FieldError>> - Todos los campos inválidos (sin consultar la BD)
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::default();
+        self.rules(&mut v);
+        v.finish()
+    }
+}
+
+// Acumula los errores de cada regla
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn error(&mut self, field: &'static str, message: impl Into<String>) {
+        self.errors.push(FieldError { field, message: message.into() });
+    }
+
+    // Texto obligatorio, no vacío y con largo máximo
+    pub fn text(&mut self, field: &'static str, value: &str, max_chars: usize) {
+        if value.trim().is_empty() {
+            self.error(field, "es obligatorio");
+        } else {
+            self.max_chars(field, value, max_chars);
+        }
+    }
+
+    // Edición: si el campo viene, se valida como en el alta
+    pub fn opt_text(&mut self, field: &'static str, value: Option<&str>, max_chars: usize) {
+        if let Some(value) = value {
+            self.text(field, value, max_chars);
+        }
+    }
+
+    pub fn max_chars(&mut self, field: &'static str, value: &str, max_chars: usize) {
+        if value.chars().count() > max_chars {
+            self.error(field, format!("admite como máximo {max_chars} caracteres"));
+        }
+    }
+
+    // Fecha YYYY-MM-DD existente en el calendario (como las guarda la BD)
+    pub fn date(&mut self, field: &'static str, value: &str) {
+        if !is_iso_date(value) {
+            self.error(field, "debe ser una fecha válida con formato YYYY-MM-DD");
+        }
+    }
+
+    pub fn opt_date(&mut self, field: &'static str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.date(field, value);
+        }
+    }
+
+    pub fn range(&mut self, field: &'static str, value: i32, min: i32, max: i32) {
+        if !(min..=max).contains(&value) {
+            self.error(field, format!("debe estar entre {min} y {max}"));
+        }
+    }
+
+    pub fn opt_range(&mut self, field: &'static str, value: Option<i32>, min: i32, max: i32) {
+        if let Some(value) = value {
+            self.range(field, value, min, max);
+        }
+    }
+
+    pub fn non_negative(&mut self, field: &'static str, value: i32) {
+        if value < 0 {
+            self.error(field, "no puede ser negativo");
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+// Reglas del DTO más la existencia de sus referencias (todos los campos de una vez)
+// Argumentos:
+// - pool: &SqlitePool - Conexión a la BD
+// - dto: &T - DTO a validar
+// Retorna: Result<Vec<FieldError>, sqlx::Error> - Lista vacía si el DTO es válido
+pub async fn check<T: Validate + ?Sized>(pool: &SqlitePool, dto: &T) -> Result<Vec<FieldError>, sqlx::Error> {
+    let mut v = Validator::default();
+    dto.rules(&mut v);
+    for (field, reference) in dto.references() {
+        let exists = match reference {
+            Reference::Author(id) => authors_repo::author_exists(pool, id).await?,
+            Reference::Book(id) => books_repo::book_exists(pool, id).await?,
+        };
+        if !exists {
+            let message = match reference {
+                Reference::Author(id) => format!("no existe el autor {id}"),
+                Reference::Book(id) => format!("no existe el libro {id}"),
+            };
+            v.error(field, message);
+        }
+    }
+    Ok(v.finish().err().unwrap_or_default())
+}
+
+// Texto de una línea con todos los errores: "`title` es obligatorio; `rating` debe estar entre 1 y 5"
+pub fn summary(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("`{}` {}", e.field, e.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+// Mensaje del primer error de `field` (formularios HTML)
+pub fn message_for<'a>(errors: &'a [FieldError], field: &str) -> Option<&'a str> {
+    errors.iter().find(|e| e.field == field).map(|e| e.message.as_str())
+}
+
+pub fn is_iso_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+    // `parse` admite un `+` inicial ("2020-+1-01")
+    if !parts.iter().all(|p| p.bytes().all(|b| b.is_ascii_digit())) {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<u32>(), month.parse::<u32>(), day.parse::<u32>()) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days_in_month).contains(&day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateAuthor;
+
+    #[test]
+    fn iso_dates() {
+        assert!(is_iso_date("2024-02-29"));
+        assert!(is_iso_date("2000-02-29"));
+        assert!(is_iso_date("0800-01-01"));
+        assert!(!is_iso_date("1900-02-29"));
+        assert!(!is_iso_date("2023-02-29"));
+        assert!(!is_iso_date("2024-04-31"));
+        assert!(!is_iso_date("2024-13-01"));
+        assert!(!is_iso_date("2024-00-10"));
+        assert!(!is_iso_date("2024-01-00"));
+        assert!(!is_iso_date("2024-1-01"));
+        assert!(!is_iso_date("2020-+1-01"));
+        assert!(!is_iso_date("2024-01-01T00:00"));
+        assert!(!is_iso_date(""));
+    }
+
+    #[test]
+    fn validator_collects_every_error() {
+        let mut v = Validator::default();
+        v.text("name", "   ", 10);
+        v.text("country", "España", 3);
+        v.range("rating", 6, 1, 5);
+        v.non_negative("sales", -1);
+        v.opt_text("summary", None, 1);
+        v.opt_range("year", Some(2000), 1900, 2100);
+
+        let errors = v.finish().unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|e| e.field).collect();
+        assert_eq!(fields, ["name", "country", "rating", "sales"]);
+        assert_eq!(message_for(&errors, "rating"), Some("debe estar entre 1 y 5"));
+        assert_eq!(message_for(&errors, "year"), None);
+        assert!(summary(&errors).starts_with("`name` es obligatorio; `country` admite como máximo 3 caracteres"));
+    }
+
+    #[test]
+    fn max_chars_counts_characters_not_bytes() {
+        let mut v = Validator::default();
+        v.max_chars("name", "ñññ", 3);
+        assert!(v.finish().is_ok());
+    }
+
+    #[test]
+    fn dto_rules() {
+        let author = CreateAuthor {
+            name: "Homer".into(),
+            birth_date: "0800-01-01".into(),
+            country: "Greece".into(),
+            description: None,
+        };
+        assert!(author.validate().is_ok());
+
+        let author = CreateAuthor { birth_date: "01/01/0800".into(), country: String::new(), ..author };
+        let errors = author.validate().unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|e| e.field).collect();
+        assert_eq!(fields, ["birth_date", "country"]);
+    }
+}
@@ -0,0 +1,200 @@
+use rocket::form::{Form, FromForm};
+use rocket::http::{CookieJar, Status};
+use rocket::request::Request;
+use rocket::response::{content::RawHtml, status, Redirect};
+use rocket::State;
+use crate::Db;
+use crate::auth::{self, Reader};
+use crate::models::Role;
+use crate::repository::users as users_repo;
+use super::layout::{escape_html, render_page};
+use tracing::{error, info};
+
+#[derive(FromForm)]
+pub struct LoginForm {
+    pub username: String,
+    pub password: String,
+    pub next: Option<String>,
+}
+
+// Solo redirige a rutas locales (evita open redirects con `next=//otro-sitio`).
+// Los navegadores leen `\` como `/` e ignoran tabs y saltos de línea en la URL
+// (`/\otro-sitio`, `/\t/otro-sitio`): una ruta con backslash o caracteres de control se descarta
+fn safe_next(next: Option<&str>) -> String {
+    match next {
+        Some(path)
+            if path.starts_with('/')
+                && !path.starts_with("//")
+                && !path.contains('\\')
+                && !path.chars().any(char::is_control) =>
+        {
+            path.to_string()
+        }
+        _ => "/books".to_string(),
+    }
+}
+
+fn login_body(next: &str, error: Option<&str>) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p style="color:#b00;">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+    format!(r#"
+        {error_html}
+        <form method="post" action="/login">
+            <input type="hidden" name="next" value="{next}">
+            <label>Usuario: <input type="text" name="username" required autofocus></label><br>
+            <label>Contraseña: <input type="password" name="password" required></label><br>
+            <button type="submit">Entrar</button>
+        </form>
+        <p>¿No tienes cuenta? <a href="/register">Regístrate</a></p>
+    "#, next = escape_html(next))
+}
+
+#[get("/login?<next>")]
+pub async fn login_form(next: Option<&str>, user: Option<Reader>) -> RawHtml<String> {
+    let body = match user {
+        Some(Reader(user)) => format!(r#"
+            <p>Sesión iniciada como <strong>{}</strong> ({})</p>
+            <form method="post" action="/logout"><button type="submit">Cerrar sesión</button></form>
+        "#, escape_html(&user.username), user.role.as_str()),
+        None => login_body(&safe_next(next), None),
+    };
+    RawHtml(render_page("Iniciar sesión", &body))
+}
+
+#[post("/login", data = "<form_data>")]
+pub async fn login(pool: &State<Db>, cookies: &CookieJar<'_>, form_data: Form<LoginForm>) -> Result<Redirect, status::Custom<RawHtml<String>>> {
+    let next = safe_next(form_data.next.as_deref());
+    let failed = |status: Status, message: &str| {
+        status::Custom(status, RawHtml(render_page("Iniciar sesión", &login_body(&next, Some(message)))))
+    };
+
+    match auth::authenticate(&pool.0, &form_data.username, &form_data.password).await {
+        Ok(Some(user)) => {
+            if let Err(e) = auth::start_session(&pool.0, cookies, user.id).await {
+                error!(error = %e, "Error creando sesión");
+                return Err(failed(Status::InternalServerError, "No se pudo iniciar la sesión"));
+            }
+            info!(username = %user.username, "Sesión iniciada");
+            Ok(Redirect::to(next.clone()))
+        }
+        Ok(None) => Err(failed(Status::Unauthorized, "Usuario o contraseña incorrectos")),
+        Err(e) => {
+            error!(error = %e, "Error en login");
+            Err(failed(Status::InternalServerError, "No se pudo iniciar la sesión"))
+        }
+    }
+}
+
+#[post("/logout")]
+pub async fn logout(pool: &State<Db>, cookies: &CookieJar<'_>) -> Redirect {
+    auth::end_session(&pool.0, cookies).await;
+    Redirect::to("/books")
+}
+
+fn register_body(error: Option<&str>) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p style="color:#b00;">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+    format!(r#"
+        {error_html}
+        <form method="post" action="/register">
+            <label>Usuario: <input type="text" name="username" required minlength="3" maxlength="32"></label><br>
+            <label>Contraseña: <input type="password" name="password" required minlength="8"></label><br>
+            <button type="submit">Crear cuenta</button>
+        </form>
+        <p>¿Ya tienes cuenta? <a href="/login">Inicia sesión</a></p>
+    "#)
+}
+
+#[get("/register")]
+pub async fn register_form() -> RawHtml<String> {
+    RawHtml(render_page("Crear cuenta", &register_body(None)))
+}
+
+// Las cuentas nuevas son Reader; un Admin puede cambiar el rol (PUT /api/admin/users/<id>/role)
+#[post("/register", data = "<form_data>")]
+pub async fn register(pool: &State<Db>, cookies: &CookieJar<'_>, form_data: Form<LoginForm>) -> Result<Redirect, status::Custom<RawHtml<String>>> {
+    let failed = |status: Status, message: &str| {
+        status::Custom(status, RawHtml(render_page("Crear cuenta", &register_body(Some(message)))))
+    };
+    let username = form_data.username.trim();
+    if let Err(message) = auth::validate_credentials(username, &form_data.password) {
+        return Err(failed(Status::UnprocessableEntity, &message));
+    }
+
+    let hash = auth::hash_password(form_data.password.clone())
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Error generando hash");
+            failed(Status::InternalServerError, "No se pudo crear la cuenta")
+        })?;
+    let user_id = match users_repo::create_user(&pool.0, username, &hash, Role::Reader).await {
+        Ok(id) => id,
+        Err(e) if e.as_database_error().is_some_and(|db| db.is_unique_violation()) => {
+            return Err(failed(Status::Conflict, "Ese nombre de usuario ya existe"));
+        }
+        Err(e) => {
+            error!(error = %e, "Error creando usuario");
+            return Err(failed(Status::InternalServerError, "No se pudo crear la cuenta"));
+        }
+    };
+
+    if let Err(e) = auth::start_session(&pool.0, cookies, user_id).await {
+        error!(error = %e, "Error creando sesión");
+        return Ok(Redirect::to("/login"));
+    }
+    info!(username, "Usuario registrado");
+    Ok(Redirect::to("/books"))
+}
+
+// Catchers de las vistas: sin sesión se envía al login y se vuelve a la página pedida
+#[catch(401)]
+pub fn unauthorized(req: &Request<'_>) -> Redirect {
+    let next = req.uri().to_string();
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("next", &next)
+        .finish();
+    Redirect::to(format!("/login?{query}"))
+}
+
+#[catch(403)]
+pub fn forbidden() -> RawHtml<String> {
+    RawHtml(render_page("Sin permiso", r#"
+        <p>Tu usuario no tiene permiso para esta acción.</p>
+        <p><a href="/login">Cambiar de usuario</a> · <a href="/books">Volver</a></p>
+    "#))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::safe_next;
+
+    #[test]
+    fn keeps_local_paths() {
+        assert_eq!(safe_next(Some("/books/3/reviews/new")), "/books/3/reviews/new");
+        assert_eq!(safe_next(Some("/books?q=a%2F%2Fb")), "/books?q=a%2F%2Fb");
+    }
+
+    #[test]
+    fn defaults_without_next() {
+        assert_eq!(safe_next(None), "/books");
+        assert_eq!(safe_next(Some("")), "/books");
+    }
+
+    #[test]
+    fn rejects_external_targets() {
+        for next in [
+            "https://evil.com",
+            "evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "\\\\evil.com",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            "/books\\..\\..\\evil",
+        ] {
+            assert_eq!(safe_next(Some(next)), "/books", "{next:?}");
+        }
+    }
+}
@@ -54,26 +54,41 @@ pub async fn authors_index(_pool: &State<Db>) -> RawHtml<String> {
       </div>
 
       <ul id="list"></ul>
+      <div id="pager">
+        <button id="prev" class="btn" disabled>← Anterior</button>
+        <span id="pageInfo" class="muted"></span>
+        <button id="next" class="btn" disabled>Siguiente →</button>
+      </div>
 
       <script>
         const loadingEl = document.querySelector('.loading');
         const list = document.getElementById('list');
         const searchInput = document.getElementById('searchInput');
-        let authorsData = [];
+        const prevBtn = document.getElementById('prev');
+        const nextBtn = document.getElementById('next');
+        let links = {};
 
-        async function load() {
+        // Paginación y búsqueda por nombre se resuelven en el servidor (GET /api/authors)
+        async function load(url = '/api/authors') {
           try {
-            const res = await fetch('/api/authors');
+            const res = await fetch(url);
             if (!res.ok) throw new Error(res.statusText);
             const wrapper = await res.json();
             if (!wrapper || !wrapper.success) throw new Error((wrapper && wrapper.message) || 'Respuesta inválida');
-            authorsData = wrapper.data || [];
-            render(authorsData);
+            const page = wrapper.data || {};
+            links = page.links || {};
+            prevBtn.disabled = !links.prev;
+            nextBtn.disabled = !links.next;
+            document.getElementById('pageInfo').textContent =
+              `Página ${page.page ?? 1} de ${page.total_pages ?? 1} (${page.total ?? 0} autores)`;
+            render(page.items || []);
             loadingEl.style.display = 'none';
           } catch (err) {
             loadingEl.textContent = 'Error cargando autores: ' + err;
           }
         }
+        prevBtn.addEventListener('click', () => links.prev && load(links.prev));
+        nextBtn.addEventListener('click', () => links.next && load(links.next));
 
         function render(data) {
           list.innerHTML = '';
@@ -98,11 +113,14 @@ pub async fn authors_index(_pool: &State<Db>) -> RawHtml<String> {
           }
         }
 
-        // Filtro de búsqueda
+        // Filtro de búsqueda (con debounce para no consultar en cada tecla)
+        let searchTimer = null;
         searchInput.addEventListener('input', () => {
-          const q = searchInput.value.toLowerCase();
-          const filtered = authorsData.filter(a => (a.name || '').toLowerCase().includes(q));
-          render(filtered);
+          clearTimeout(searchTimer);
+          searchTimer = setTimeout(() => {
+            const q = searchInput.value.trim();
+            load(q ? '/api/authors?q=' + encodeURIComponent(q) : '/api/authors');
+          }, 250);
         });
 
         // Toggle del formulario de alta
@@ -175,6 +193,7 @@ pub async fn authors_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
       </style>
 
       <div id="view">
+        <img id="photo" class="hidden" alt="Foto del autor" style="max-width:160px; border-radius:4px;">
         <h2 id="name"></h2>
         <p><b>País:</b> <span id="country"></span></p>
         <p><b>Nacimiento:</b> <span id="birth"></span></p>
@@ -210,6 +229,10 @@ pub async fn authors_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
           <label for="f_desc">Descripción</label>
           <textarea id="f_desc" rows="4"></textarea>
         </div>
+        <div class="row">
+          <label for="f_photo">Foto (JPEG, PNG, GIF o WebP, máx. 5 MiB)</label>
+          <input id="f_photo" type="file" accept="image/jpeg,image/png,image/gif,image/webp" />
+        </div>
         <div class="actions">
           <button type="submit">Guardar</button>
           <button type="button" id="cancelBtn">Cancelar</button>
@@ -237,6 +260,13 @@ pub async fn authors_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
           document.getElementById('country').textContent = a.country || '—';
           document.getElementById('birth').textContent = fmt(a.birth_date);
           document.getElementById('desc').textContent = a.description || '—';
+          const photo = document.getElementById('photo');
+          if (a.photo) {
+            photo.src = a.photo.thumbnail_url;
+            photo.classList.remove('hidden');
+          } else {
+            photo.classList.add('hidden');
+          }
 
           // Prefill form
           document.getElementById('f_name').value = a.name || '';
@@ -301,6 +331,20 @@ pub async fn authors_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
             formMsg.textContent = 'Error al actualizar: ' + (ans.message || res.statusText);
             return;
           }
+
+          // La foto va aparte, como multipart
+          const file = document.getElementById('f_photo').files[0];
+          if (file) {
+            const data = new FormData();
+            data.append('file', file);
+            const up = await fetch('/api/authors/' + id + '/photo', { method: 'POST', body: data });
+            const upAns = await up.json().catch(() => ({ success:false, message:'Error desconocido' }));
+            if (!up.ok || !upAns.success) {
+              formMsg.textContent = 'Error al subir la foto: ' + (upAns.message || up.statusText);
+              return;
+            }
+            document.getElementById('f_photo').value = '';
+          }
           await load();
           form.classList.add('hidden');
           view.classList.remove('hidden');
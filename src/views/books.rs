@@ -16,21 +16,55 @@ pub async fn books_index(_pool: &State<Db>) -> RawHtml<String> {
         <button style="margin-bottom: 1em;">Crear libro</button>
       </a>
 
+      <div id="controls" style="margin-bottom: 1em;">
+        <label>Ordenar por
+          <select id="sort">
+            <option value="title">Título</option>
+            <option value="publication_date">Fecha de publicación</option>
+            <option value="sales">Ventas</option>
+            <option value="rating">Rating</option>
+            <option value="author">Autor</option>
+          </select>
+        </label>
+        <select id="order">
+          <option value="asc">Asc</option>
+          <option value="desc">Desc</option>
+        </select>
+      </div>
+
       <p class="loading">Cargando libros...</p>
       <ul id="list"></ul>
+      <div id="pager">
+        <button id="prev" disabled>← Anterior</button>
+        <span id="pageInfo"></span>
+        <button id="next" disabled>Siguiente →</button>
+      </div>
       <script>
-        async function load() {
+        // Paginación, orden y filtros se resuelven en el servidor (GET /api/books)
+        const prevBtn = document.getElementById('prev');
+        const nextBtn = document.getElementById('next');
+        const sortSel = document.getElementById('sort');
+        const orderSel = document.getElementById('order');
+        let links = {};
+
+        async function load(url) {
           try {
-            const res = await fetch('/api/books');
+            const res = await fetch(url);
             if (!res.ok) throw new Error(res.statusText);
             const wrapper = await res.json();
             if (!wrapper || !wrapper.success) throw new Error(wrapper?.message || 'Respuesta inválida');
-            const data = wrapper.data || [];
+            const page = wrapper.data || {};
+            const data = page.items || [];
+            links = page.links || {};
             const list = document.getElementById('list');
             list.innerHTML = '';
+            prevBtn.disabled = !links.prev;
+            nextBtn.disabled = !links.next;
+            document.getElementById('pageInfo').textContent =
+              `Página ${page.page ?? 1} de ${page.total_pages ?? 1} (${page.total ?? 0} libros)`;
+            document.querySelector('.loading').style.display = 'none';
             if (!Array.isArray(data) || data.length === 0) {
               list.innerHTML = '<li>No hay libros</li>';
-              document.querySelector('.loading').style.display = 'none';
               return;
             }
             for (const b of data) {
@@ -41,12 +75,19 @@ pub async fn books_index(_pool: &State<Db>) -> RawHtml<String> {
                 + ` <a href="/books/${b.id}/sales" style="margin-left:.25rem;"><button>Ventas</button></a>`;
               list.appendChild(li);
             }
-            document.querySelector('.loading').style.display = 'none';
           } catch (err) {
             document.querySelector('.loading').textContent = 'Error cargando libros: ' + err;
           }
         }
-        load();
+
+        function reload() {
+          load(`/api/books?sort=${sortSel.value}&order=${orderSel.value}`);
+        }
+        prevBtn.addEventListener('click', () => links.prev && load(links.prev));
+        nextBtn.addEventListener('click', () => links.next && load(links.next));
+        sortSel.addEventListener('change', reload);
+        orderSel.addEventListener('change', reload);
+        reload();
       </script>
     "#;
     RawHtml(render_page("Libros", body))
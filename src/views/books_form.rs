@@ -1,10 +1,17 @@
 use rocket::form::{Form, FromForm};
-use rocket::response::{content::RawHtml, Redirect};
+use rocket::http::Status;
+use rocket::response::{content::RawHtml, status, Redirect};
 use rocket::State;
 use crate::Db;
-use super::layout::render_page;
-use crate::models::{CreateBook, UpdateBook};
+use crate::auth::Editor;
+use crate::cache::Cache;
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::indexer::{IndexOp, Indexer};
+use crate::validation::{self, FieldError};
+use super::layout::{escape_html, field_error_html, render_page};
+use crate::models::{CreateBook, UpdateBook, UpdateOutcome};
 use crate::repository::books as books_repo; // 👈 usar repository
+use tracing::error;
 
 #[derive(FromForm)]
 pub struct BookForm {
@@ -12,72 +19,151 @@ pub struct BookForm {
     pub summary: Option<String>,
     pub publication_date: String,
     pub author_id: i32,
+    // Versión del libro al abrir la edición (campo oculto); None en el alta
+    pub version: Option<i32>,
 }
 
-#[get("/books/new")]
-pub async fn new_book_form() -> RawHtml<String> {
-    let body = r#"
-        <h2>Crear Libro</h2>
-        <form method="post" action="/books/create">
-            <label>Título: <input type="text" name="title" required></label><br>
-            <label>Resumen: <textarea name="summary"></textarea></label><br>
-            <label>Fecha publicación: <input type="date" name="publication_date" required></label><br>
-            <label>Autor ID: <input type="number" name="author_id" required></label><br>
-            <button type="submit">Crear</button>
+// Respuesta de los POST: redirección si se guardó, el formulario con los errores si no
+type FormResult = Result<Redirect, status::Custom<RawHtml<String>>>;
+
+// Formulario de alta/edición; `errors` se muestran junto a cada campo
+fn book_form_body(heading: &str, action: &str, submit: &str, form: &BookForm, errors: &[FieldError], general: Option<&str>) -> String {
+    let general_html = general
+        .map(|e| format!(r#"<p style="color:#b00;">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+    let author_id = if form.author_id > 0 { form.author_id.to_string() } else { String::new() };
+    let version_html = form.version
+        .map(|v| format!(r#"<input type="hidden" name="version" value="{v}">"#))
+        .unwrap_or_default();
+    format!(r#"
+        <h2>{heading}</h2>
+        {general_html}
+        <form method="post" action="{action}">
+            {version_html}
+            <label>Título: <input type="text" name="title" value="{title}" required maxlength="300"></label>{title_error}<br>
+            <label>Resumen: <textarea name="summary" maxlength="5000">{summary}</textarea></label>{summary_error}<br>
+            <label>Fecha publicación: <input type="date" name="publication_date" value="{date}" required></label>{date_error}<br>
+            <label>Autor ID: <input type="number" name="author_id" value="{author_id}" min="1" required></label>{author_error}<br>
+            <button type="submit">{submit}</button>
         </form>
         <p><a href="/books">Volver</a></p>
-    "#;
-    RawHtml(render_page("Nuevo Libro", body))
+    "#,
+        title = escape_html(&form.title),
+        summary = escape_html(form.summary.as_deref().unwrap_or_default()),
+        date = escape_html(&form.publication_date),
+        title_error = field_error_html(errors, "title"),
+        summary_error = field_error_html(errors, "summary"),
+        date_error = field_error_html(errors, "publication_date"),
+        author_error = field_error_html(errors, "author_id"),
+    )
+}
+
+#[get("/books/new")]
+pub async fn new_book_form(_editor: Editor) -> RawHtml<String> {
+    let empty = BookForm { title: String::new(), summary: None, publication_date: String::new(), author_id: 0, version: None };
+    RawHtml(render_page("Nuevo Libro", &book_form_body("Crear Libro", "/books/create", "Crear", &empty, &[], None)))
 }
 
 #[post("/books/create", data = "<form_data>")]
-pub async fn books_create(pool: &State<Db>, form_data: Form<BookForm>) -> Redirect {
+pub async fn books_create(_editor: Editor, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>, form_data: Form<BookForm>) -> FormResult {
+    let failed = |status: Status, errors: &[FieldError], general: Option<&str>| {
+        let body = book_form_body("Crear Libro", "/books/create", "Crear", &form_data, errors, general);
+        status::Custom(status, RawHtml(render_page("Nuevo Libro", &body)))
+    };
     let payload = CreateBook {
         title: form_data.title.clone(),
         summary: form_data.summary.clone(),
         publication_date: form_data.publication_date.clone(),
         author_id: form_data.author_id,
     };
-    let _ = books_repo::create_book(&pool.0, &payload).await; // 👈 repository
-    Redirect::to("/books")
+    match validation::check(&pool.0, &payload).await {
+        Ok(errors) if errors.is_empty() => {}
+        Ok(errors) => return Err(failed(Status::UnprocessableEntity, &errors, None)),
+        Err(e) => {
+            error!(error = %e, "Error validando libro");
+            return Err(failed(Status::InternalServerError, &[], Some("No se pudo crear el libro")));
+        }
+    }
+
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::CreateBook { author_id: payload.author_id }).await;
+    match books_repo::create_book(&pool.0, &payload).await { // 👈 repository
+        Ok(id) => {
+            plan.purge(cache).await;
+            indexer.enqueue(IndexOp::Book(id));
+            Ok(Redirect::to("/books"))
+        }
+        Err(e) => {
+            error!(error = %e, "Error creando libro");
+            Err(failed(Status::InternalServerError, &[], Some("No se pudo crear el libro")))
+        }
+    }
 }
 
 #[get("/books/<id>/edit")]
-pub async fn edit_book_form(id: i32, pool: &State<Db>) -> RawHtml<String> {
+pub async fn edit_book_form(id: i32, _editor: Editor, pool: &State<Db>) -> RawHtml<String> {
     let existing = books_repo::get_book_by_id(&pool.0, id).await.ok().flatten(); // 👈 repository
-    let (title, summary, date, author_id) = if let Some(b) = existing {
-        (
-            b.title,
-            b.summary.unwrap_or_default(),
-            b.publication_date,
-            b.author.id.unwrap_or(1),
-        )
+    let form = if let Some(b) = existing {
+        BookForm {
+            title: b.title,
+            summary: b.summary,
+            publication_date: b.publication_date,
+            author_id: b.author.id.unwrap_or(1),
+            version: Some(b.version),
+        }
     } else {
-        ("".to_string(), "".to_string(), "".to_string(), 1)
+        BookForm { title: String::new(), summary: None, publication_date: String::new(), author_id: 1, version: None }
     };
 
-    let body = format!(r#"
-        <h2>Editar Libro #{id}</h2>
-        <form method="post" action="/books/{id}/update">
-            <label>Título: <input type="text" name="title" value="{title}" required></label><br>
-            <label>Resumen: <textarea name="summary">{summary}</textarea></label><br>
-            <label>Fecha publicación: <input type="date" name="publication_date" value="{date}"></label><br>
-            <label>Autor ID: <input type="number" name="author_id" value="{author_id}"></label><br>
-            <button type="submit">Guardar Cambios</button>
-        </form>
-        <p><a href="/books">Volver</a></p>
-    "#);
+    let body = book_form_body(&format!("Editar Libro #{id}"), &format!("/books/{id}/update"), "Guardar Cambios", &form, &[], None);
     RawHtml(render_page("Editar Libro", &body))
 }
 
 #[post("/books/<id>/update", data = "<form_data>")]
-pub async fn books_update(id: i32, pool: &State<Db>, form_data: Form<BookForm>) -> Redirect {
+pub async fn books_update(id: i32, _editor: Editor, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>, form_data: Form<BookForm>) -> FormResult {
+    let failed = |status: Status, errors: &[FieldError], general: Option<&str>| {
+        let body = book_form_body(&format!("Editar Libro #{id}"), &format!("/books/{id}/update"), "Guardar Cambios", &form_data, errors, general);
+        status::Custom(status, RawHtml(render_page("Editar Libro", &body)))
+    };
     let payload = UpdateBook {
         title: Some(form_data.title.clone()),
         summary: form_data.summary.clone(),
         publication_date: Some(form_data.publication_date.clone()),
         author_id: Some(form_data.author_id),
+        version: form_data.version,
     };
-    let _ = books_repo::update_book(&pool.0, id, &payload).await; // 👈 repository
-    Redirect::to("/books")
-}
\ No newline at end of file
+    match validation::check(&pool.0, &payload).await {
+        Ok(errors) if errors.is_empty() => {}
+        Ok(errors) => return Err(failed(Status::UnprocessableEntity, &errors, None)),
+        Err(e) => {
+            error!(book_id = id, error = %e, "Error validando libro");
+            return Err(failed(Status::InternalServerError, &[], Some("No se pudo guardar el libro")));
+        }
+    }
+
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateBook { id, new_author_id: payload.author_id }).await;
+    match books_repo::update_book(&pool.0, id, &payload).await { // 👈 repository
+        Ok(UpdateOutcome::Updated(_)) => {
+            plan.purge(cache).await;
+            indexer.enqueue(IndexOp::Book(id));
+            Ok(Redirect::to("/books"))
+        }
+        Ok(UpdateOutcome::NotFound) => Err(failed(Status::NotFound, &[], Some("El libro no existe"))),
+        // Se vuelve a mostrar lo que cargó el usuario con la versión actual: guardar de nuevo pisa el otro cambio a sabiendas
+        Ok(UpdateOutcome::Conflict { current }) => {
+            let retry = BookForm {
+                title: form_data.title.clone(),
+                summary: form_data.summary.clone(),
+                publication_date: form_data.publication_date.clone(),
+                author_id: form_data.author_id,
+                version: Some(current),
+            };
+            let message = "Otro usuario modificó este libro mientras lo editabas. Revisá el libro y volvé a guardar si querés reemplazar sus cambios";
+            let body = book_form_body(&format!("Editar Libro #{id}"), &format!("/books/{id}/update"), "Guardar Cambios", &retry, &[], Some(message));
+            Err(status::Custom(Status::PreconditionFailed, RawHtml(render_page("Editar Libro", &body))))
+        }
+        Err(e) => {
+            error!(book_id = id, error = %e, "Error actualizando libro");
+            Err(failed(Status::InternalServerError, &[], Some("No se pudo guardar el libro")))
+        }
+    }
+}
@@ -17,7 +17,16 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
         <button id="btn-delete">Eliminar libro</button>
       </div>
 
+      <form id="cover-form" style="margin:12px 0;">
+        <label>Portada (JPEG, PNG, GIF o WebP, máx. 5 MiB):
+          <input type="file" name="file" accept="image/jpeg,image/png,image/gif,image/webp" required>
+        </label>
+        <button type="submit">Subir portada</button>
+        <span id="cover-msg"></span>
+      </form>
+
       <h2>Reseñas</h2>
+      <p id="moderation-msg" style="color:#b60;"></p>
       <div style="margin:8px 0;">
         <button id="btn-new-review">Crear review</button>
       </div>
@@ -26,13 +35,14 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
         <thead>
           <tr>
             <th>Descripción</th>
+            <th>Autor</th>
             <th>Puntaje</th>
             <th>Up-votes</th>
             <th>Acciones</th>
           </tr>
         </thead>
         <tbody id="reviews-body">
-          <tr><td colspan="4" class="loading">Cargando reseñas...</td></tr>
+          <tr><td colspan="5" class="loading">Cargando reseñas...</td></tr>
         </tbody>
       </table>
 
@@ -47,6 +57,14 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
         document.getElementById('btn-delete').onclick = deleteBook;
         document.getElementById('btn-new-review').onclick = () => location.href = `/books/${bookId}/reviews/new`;
 
+        // Vuelta del formulario de reseña cuando la moderación no la publicó
+        const moderation = new URLSearchParams(window.location.search).get('moderation');
+        if (moderation === 'pending') {
+          document.getElementById('moderation-msg').textContent = 'Tu reseña quedó pendiente de moderación; se publicará cuando un administrador la apruebe.';
+        } else if (moderation === 'rejected') {
+          document.getElementById('moderation-msg').textContent = 'Tu reseña fue rechazada por la moderación y no se publicará.';
+        }
+
         async function loadBook() {
           try {
             const res = await fetch(`/api/books/${bookId}`);
@@ -56,7 +74,11 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
             const book = wrapper.data;
 
             const authorName = (book.author && book.author.name) || book.author_name || 'Desconocido';
+            const cover = book.cover
+              ? `<p><a href="${book.cover.url}"><img src="${book.cover.thumbnail_url}" alt="Portada" style="max-width:160px;"></a></p>`
+              : '';
             document.getElementById('book-details').innerHTML = `
+              ${cover}
               <p><b>Título:</b> ${book.title}</p>
               <p><b>Autor:</b> ${authorName}</p>
               <p><b>Resumen:</b> ${book.summary ?? ''}</p>
@@ -80,7 +102,7 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
             tbody.innerHTML = '';
 
             if (!Array.isArray(reviews) || reviews.length === 0) {
-              tbody.innerHTML = '<tr><td colspan="4">No hay reseñas</td></tr>';
+              tbody.innerHTML = '<tr><td colspan="5">No hay reseñas</td></tr>';
               return;
             }
 
@@ -88,9 +110,11 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
               const tr = document.createElement('tr');
               tr.innerHTML = `
                 <td>${r.review_text || ''}</td>
+                <td>${r.username || '—'}</td>
                 <td>${r.rating || 0}</td>
                 <td>${r.positive_votes ?? 0}</td>
                 <td>
+                  <button onclick="voteReview(${r.id})">👍</button>
                   <button onclick="location.href='/books/${bookId}/reviews/${r.id}/edit'">Editar</button>
                   <button onclick="deleteReview(${r.id})">Eliminar</button>
                 </td>
@@ -99,7 +123,7 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
             }
           } catch (err) {
             document.getElementById('reviews-body').innerHTML =
-              '<tr><td colspan="4">Error cargando reseñas: ' + err + '</td></tr>';
+              '<tr><td colspan="5">Error cargando reseñas: ' + err + '</td></tr>';
           }
         }
 
@@ -118,6 +142,38 @@ pub async fn books_show(_id: i32, _pool: &State<Db>) -> RawHtml<String> {
           }
         }
 
+        // Portada: multipart contra la API (requiere rol editor)
+        document.getElementById('cover-form').onsubmit = async (e) => {
+          e.preventDefault();
+          const msg = document.getElementById('cover-msg');
+          msg.textContent = 'Subiendo…';
+          try {
+            const res = await fetch(`/api/books/${bookId}/cover`, { method: 'POST', body: new FormData(e.target) });
+            const data = await res.json().catch(() => ({}));
+            if (!res.ok || (data && data.success === false)) {
+              throw new Error((data && data.message) || res.statusText);
+            }
+            msg.textContent = '';
+            e.target.reset();
+            loadBook();
+          } catch (err) {
+            msg.textContent = 'Error subiendo portada: ' + err;
+          }
+        };
+
+        async function voteReview(rid) {
+          try {
+            const res = await fetch(`/api/reviews/${rid}/vote`, { method: 'POST' });
+            const data = await res.json().catch(() => ({}));
+            if (!res.ok || (data && data.success === false)) {
+              throw new Error((data && data.message) || res.statusText);
+            }
+            loadReviews();
+          } catch (err) {
+            alert('Error votando reseña: ' + err);
+          }
+        }
+
         async function deleteReview(rid) {
           if (!confirm('¿Seguro que deseas eliminar esta reseña?')) return;
           try {
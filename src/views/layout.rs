@@ -1,9 +1,12 @@
+use crate::validation::{self, FieldError};
+
 pub fn bottom_nav_html() -> String {
     r#"
     <nav style="position:fixed;bottom:0;left:0;right:0;height:56px;background:#222;color:#fff;display:flex;justify-content:space-around;align-items:center;">
         <a href="/authors" style="color:#fff;text-decoration:none;padding:8px 16px;">Autores</a>
         <a href="/books"   style="color:#fff;text-decoration:none;padding:8px 16px;">Libros</a>
         <a href="/tables"  style="color:#fff;text-decoration:none;padding:8px 16px;">Tablas</a>
+        <a href="/login"   style="color:#fff;text-decoration:none;padding:8px 16px;">Cuenta</a>
     </nav>
     "#
     .to_string()
@@ -38,3 +41,26 @@ pub fn render_page(title: &str, body: &str) -> String {
         bottom_nav_html()
     )
 }
+
+// Escapa texto de la BD antes de interpolarlo en HTML renderizado en el servidor
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+// Mensaje de error junto a un campo de formulario (vacío si el campo es válido)
+pub fn field_error_html(errors: &[FieldError], field: &str) -> String {
+    validation::message_for(errors, field)
+        .map(|message| format!(r#" <small style="color:#b00;">{}</small>"#, escape_html(message)))
+        .unwrap_or_default()
+}
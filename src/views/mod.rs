@@ -7,4 +7,5 @@ pub mod books_form;
 pub mod books_show;
 pub mod reviews_form;
 pub mod books_search;
-pub mod sales;
\ No newline at end of file
+pub mod sales;
+pub mod auth;
\ No newline at end of file
@@ -1,97 +1,236 @@
 // src/views/reviews_form.rs
 use rocket::form::{Form, FromForm};
-use rocket::response::{content::RawHtml, Redirect};
+use rocket::response::{content::RawHtml, status, Redirect};
+use rocket::http::Status;
 use rocket::State;
 use crate::Db;
-use super::layout::render_page;
+use crate::auth::{self, Reader};
+use crate::cache::Cache;
+use crate::invalidation::{InvalidationPlan, Mutation};
+use crate::indexer::{IndexOp, Indexer};
+use crate::moderation::Moderation;
+use crate::validation::{self, FieldError, Validate};
+use super::layout::{escape_html, field_error_html, render_page};
 
-use crate::models::{CreateReview, UpdateReview, ReviewWithBook};
+use rocket_db_pools::sqlx::SqlitePool;
+use crate::models::{CreateReview, ModerationResult, ReviewStatus, UpdateOutcome, UpdateReview};
 use crate::repository::reviews as reviews_repo;
+use tracing::error;
 
 #[derive(FromForm)]
 pub struct ReviewForm {
     pub review_text: String,
-    pub rating: i32,                 // 1..=5
-    pub positive_votes: Option<i32>, // solo se usa en editar
+    pub rating: i32, // 1..=5
+    // Versión de la reseña al abrir la edición (campo oculto); None en el alta
+    pub version: Option<i32>,
 }
 
-#[get("/books/<book_id>/reviews/new")]
-pub async fn new_review_form(book_id: i32) -> RawHtml<String> {
-    // Form simple para crear
-    let body = format!(r#"
-        <h2>Crear Review para Libro #{book_id}</h2>
-        <form method="post" action="/books/{book_id}/reviews/create">
+// Respuesta de los POST: redirección si se guardó, el formulario con los errores si no
+type FormResult = Result<Redirect, status::Custom<RawHtml<String>>>;
+
+// Formulario de alta/edición; `extra` va antes del botón (p.ej. los votos en la edición)
+fn review_form_body(heading: &str, action: &str, submit: &str, book_id: i32, form: &ReviewForm, extra: &str, errors: &[FieldError]) -> String {
+    let version_html = form.version
+        .map(|v| format!(r#"<input type="hidden" name="version" value="{v}">"#))
+        .unwrap_or_default();
+    format!(r#"
+        <h2>{heading}</h2>
+        <form method="post" action="{action}">
+            {version_html}
             <label>Descripción:<br>
-              <textarea name="review_text" required rows="4" cols="50"></textarea>
-            </label><br><br>
+              <textarea name="review_text" required maxlength="5000" rows="4" cols="50">{review_text}</textarea>
+            </label>{text_error}<br><br>
 
             <label>Puntaje (1 a 5):
-              <input type="number" name="rating" min="1" max="5" value="5" required>
-            </label><br><br>
+              <input type="number" name="rating" min="1" max="5" value="{rating}" required>
+            </label>{rating_error}<br><br>
+
+            {extra}
 
-            <button type="submit">Crear</button>
+            <button type="submit">{submit}</button>
             <a href="/books/{book_id}"><button type="button">Volver</button></a>
         </form>
-    "#);
+    "#,
+        review_text = escape_html(&form.review_text),
+        rating = form.rating,
+        text_error = field_error_html(errors, "review_text"),
+        rating_error = field_error_html(errors, "rating"),
+    )
+}
+
+// Errores de validación => 422 con el formulario; errores de la BD => 500
+async fn check_form<T: Validate>(pool: &SqlitePool, payload: &T) -> Result<(), (Status, Vec<FieldError>)> {
+    match validation::check(pool, payload).await {
+        Ok(errors) if errors.is_empty() => Ok(()),
+        Ok(errors) => Err((Status::UnprocessableEntity, errors)),
+        Err(e) => {
+            error!(error = %e, "Error validando reseña");
+            Err((Status::InternalServerError, Vec::new()))
+        }
+    }
+}
+
+#[get("/books/<book_id>/reviews/new")]
+pub async fn new_review_form(book_id: i32, _reader: Reader) -> RawHtml<String> {
+    // Form simple para crear
+    let empty = ReviewForm { review_text: String::new(), rating: 5, version: None };
+    let body = review_form_body(&format!("Crear Review para Libro #{book_id}"), &format!("/books/{book_id}/reviews/create"), "Crear", book_id, &empty, "", &[]);
     RawHtml(render_page("Crear Review", &body))
 }
 
 #[post("/books/<book_id>/reviews/create", data = "<form_data>")]
-pub async fn reviews_create(book_id: i32, pool: &State<Db>, form_data: Form<ReviewForm>) -> Redirect {
+#[allow(clippy::too_many_arguments)]
+pub async fn reviews_create(book_id: i32, reader: Reader, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>, moderation: &State<Moderation>, form_data: Form<ReviewForm>) -> FormResult {
+    let failed = |status: Status, errors: &[FieldError]| {
+        let body = review_form_body(&format!("Crear Review para Libro #{book_id}"), &format!("/books/{book_id}/reviews/create"), "Crear", book_id, &form_data, "", errors);
+        status::Custom(status, RawHtml(render_page("Crear Review", &body)))
+    };
     let payload = CreateReview {
         book_id,
         review_text: form_data.review_text.clone(),
         rating: form_data.rating,
     };
-    let _ = reviews_repo::create_review(&pool.0, &payload).await;
-    Redirect::to(format!("/books/{book_id}"))
+    check_form(&pool.0, &payload).await.map_err(|(status, errors)| failed(status, &errors))?;
+    let verdict = moderation.review(&pool.0, book_id, None, &payload.review_text).await.map_err(|e| {
+        error!(error = %e, "Error moderando reseña");
+        failed(Status::InternalServerError, &[])
+    })?;
+
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::CreateReview { book_id }).await;
+    match reviews_repo::create_review(&pool.0, &payload, Some(reader.0.id), &verdict).await {
+        Ok(review_id) => {
+            plan.purge(cache).await;
+            indexer.enqueue(IndexOp::Review(review_id));
+            Ok(redirect_to_book(book_id, &verdict))
+        }
+        Err(e) => {
+            error!(error = %e, "Error creando reseña");
+            Err(failed(Status::InternalServerError, &[]))
+        }
+    }
 }
 
 #[get("/books/<book_id>/reviews/<id>/edit")]
-pub async fn edit_review_form(book_id: i32, id: i32, pool: &State<Db>) -> RawHtml<String> {
-    // Traemos reviews del libro y buscamos la que queremos editar
-    let existing: Option<ReviewWithBook> = reviews_repo::get_reviews_by_book(&pool.0, book_id)
-        .await
-        .ok()
-        .and_then(|list| list.into_iter().find(|r| r.id == Some(id)));
+pub async fn edit_review_form(book_id: i32, id: i32, reader: Reader, pool: &State<Db>) -> Result<RawHtml<String>, Status> {
+    check_review_owner(&pool.0, &reader, id).await?;
+
+    // En cualquier estado de moderación: el autor puede corregir una reseña pendiente o rechazada
+    let existing = reviews_repo::get_moderated_review(&pool.0, id).await.ok().flatten();
 
-    let (review_text, rating, positive_votes) = if let Some(r) = existing {
-        (r.review_text, r.rating, r.positive_votes)
+    let (form, info) = if let Some(r) = existing {
+        let info = review_info_html(r.positive_votes, r.status, r.moderation_reason.as_deref());
+        (ReviewForm { review_text: r.review_text, rating: r.rating, version: Some(r.version) }, info)
     } else {
-        ("".to_string(), 5, 0)
+        (ReviewForm { review_text: String::new(), rating: 5, version: None }, String::new())
     };
 
-    let body = format!(r#"
-        <h2>Editar Review #{id} (Libro #{book_id})</h2>
-        <form method="post" action="/books/{book_id}/reviews/{id}/update">
-            <label>Descripción:<br>
-              <textarea name="review_text" required rows="4" cols="50">{review_text}</textarea>
-            </label><br><br>
-
-            <label>Puntaje (1 a 5):
-              <input type="number" name="rating" min="1" max="5" value="{rating}" required>
-            </label><br><br>
-
-            <label>Up-votes:
-              <input type="number" name="positive_votes" min="0" value="{positive_votes}">
-            </label><br><br>
-
-            <button type="submit">Guardar</button>
-            <a href="/books/{book_id}"><button type="button">Volver</button></a>
-        </form>
-    "#);
-
-    RawHtml(render_page("Editar Review", &body))
+    let body = review_form_body(
+        &format!("Editar Review #{id} (Libro #{book_id})"),
+        &format!("/books/{book_id}/reviews/{id}/update"),
+        "Guardar",
+        book_id,
+        &form,
+        &info,
+        &[],
+    );
+    Ok(RawHtml(render_page("Editar Review", &body)))
 }
 
+// El Status externo es el del control de permisos (lo atienden los catchers HTML)
 #[post("/books/<book_id>/reviews/<id>/update", data = "<form_data>")]
-pub async fn reviews_update(book_id: i32, id: i32, pool: &State<Db>, form_data: Form<ReviewForm>) -> Redirect {
+#[allow(clippy::too_many_arguments)]
+pub async fn reviews_update(book_id: i32, id: i32, reader: Reader, pool: &State<Db>, cache: &Cache, indexer: &State<Indexer>, moderation: &State<Moderation>, form_data: Form<ReviewForm>) -> Result<FormResult, Status> {
+    check_review_owner(&pool.0, &reader, id).await?;
+    let failed = |status: Status, errors: &[FieldError]| {
+        let body = review_form_body(
+            &format!("Editar Review #{id} (Libro #{book_id})"),
+            &format!("/books/{book_id}/reviews/{id}/update"),
+            "Guardar",
+            book_id,
+            &form_data,
+            "",
+            errors,
+        );
+        status::Custom(status, RawHtml(render_page("Editar Review", &body)))
+    };
     let payload = UpdateReview {
-        book_id: Some(book_id),
         review_text: Some(form_data.review_text.clone()),
         rating: Some(form_data.rating),
-        positive_votes: form_data.positive_votes, // puede ser None
+        version: form_data.version,
+    };
+    if let Err((status, errors)) = check_form(&pool.0, &payload).await {
+        return Ok(Err(failed(status, &errors)));
+    }
+    let verdict = match moderation.review(&pool.0, book_id, Some(id), &form_data.review_text).await {
+        Ok(verdict) => verdict,
+        Err(e) => {
+            error!(review_id = id, error = %e, "Error moderando reseña");
+            return Ok(Err(failed(Status::InternalServerError, &[])));
+        }
     };
-    let _ = reviews_repo::update_review(&pool.0, id, &payload).await;
-    Redirect::to(format!("/books/{book_id}"))
-}
\ No newline at end of file
+
+    let plan = InvalidationPlan::prepare(&pool.0, Mutation::UpdateReview(id)).await;
+    match reviews_repo::update_review(&pool.0, id, &payload, Some(&verdict)).await {
+        Ok(UpdateOutcome::Updated(_)) => {
+            plan.purge(cache).await;
+            indexer.enqueue(IndexOp::Review(id));
+            Ok(Ok(redirect_to_book(book_id, &verdict)))
+        }
+        Ok(UpdateOutcome::NotFound) => Err(Status::NotFound),
+        // Se vuelve a mostrar lo que cargó el usuario con la versión actual: guardar de nuevo pisa el otro cambio a sabiendas
+        Ok(UpdateOutcome::Conflict { current }) => {
+            let retry = ReviewForm { review_text: form_data.review_text.clone(), rating: form_data.rating, version: Some(current) };
+            let notice = r#"<p style="color:#b00;">Otro usuario modificó esta reseña mientras la editabas. Revisala y volvé a guardar si querés reemplazar sus cambios</p>"#;
+            let body = review_form_body(
+                &format!("Editar Review #{id} (Libro #{book_id})"),
+                &format!("/books/{book_id}/reviews/{id}/update"),
+                "Guardar",
+                book_id,
+                &retry,
+                notice,
+                &[],
+            );
+            Ok(Err(status::Custom(Status::PreconditionFailed, RawHtml(render_page("Editar Review", &body)))))
+        }
+        Err(e) => {
+            error!(review_id = id, error = %e, "Error actualizando reseña");
+            Ok(Err(failed(Status::InternalServerError, &[])))
+        }
+    }
+}
+
+fn review_info_html(positive_votes: i32, status: ReviewStatus, reason: Option<&str>) -> String {
+    let moderation = match status {
+        ReviewStatus::Approved => String::new(),
+        _ => format!(
+            "<p>Estado: <strong>{}</strong> <small>{}</small></p>",
+            status.as_str(),
+            escape_html(reason.unwrap_or_default())
+        ),
+    };
+    format!("{moderation}<p>Up-votes: {positive_votes} <small>(los votos se dan desde la página del libro)</small></p>")
+}
+
+// La página del libro avisa (`?moderation=`) si la reseña no quedó publicada
+fn redirect_to_book(book_id: i32, verdict: &ModerationResult) -> Redirect {
+    match verdict.status {
+        ReviewStatus::Approved => Redirect::to(format!("/books/{book_id}")),
+        status => Redirect::to(format!("/books/{book_id}?moderation={}", status.as_str())),
+    }
+}
+
+// Solo el autor de la reseña o un Editor/Admin (el catcher 403 muestra la página de error)
+async fn check_review_owner(pool: &SqlitePool, reader: &Reader, id: i32) -> Result<(), Status> {
+    let owner = reviews_repo::get_review_owner(pool, id)
+        .await
+        .map_err(|e| {
+            error!(review_id = id, error = %e, "Error obteniendo reseña");
+            Status::InternalServerError
+        })?
+        .ok_or(Status::NotFound)?;
+    if auth::can_manage_review(&reader.0, owner) {
+        Ok(())
+    } else {
+        Err(Status::Forbidden)
+    }
+}
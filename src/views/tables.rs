@@ -1,32 +1,182 @@
 use rocket::response::content::RawHtml;
 use rocket::State;
 use crate::Db;
-use super::layout::render_page;
+use crate::cache::Cache;
+use crate::api::stats::{load_author_stats, load_top_rated, load_top_selling};
+use crate::repository;
+use super::layout::{escape_html, render_page};
+
+const TOP_RATED: i64 = 10;
+const TOP_SELLING: i64 = 50;
+
+fn truncate(text: &str, len: usize) -> String {
+    if text.chars().count() > len {
+        let cut: String = text.chars().take(len - 1).collect();
+        format!("{cut}…")
+    } else {
+        text.to_string()
+    }
+}
+
+fn thousands(n: i64) -> String {
+    let digits = n.abs().to_string();
+    let mut out = String::new();
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push('.');
+        }
+        out.push(ch);
+    }
+    if n < 0 { format!("-{out}") } else { out }
+}
 
+// Las tres tablas se calculan en SQL (repository::stats, cacheadas en Redis) y se
+// renderizan en el servidor: la página llega completa en un solo round-trip.
 #[get("/tables")]
-pub async fn tables_index(_pool: &State<Db>) -> RawHtml<String> {
-    let body = r#"
+pub async fn tables_index(pool: &State<Db>, cache: &Cache) -> RawHtml<String> {
+    let (dashboard, authors, top_rated, top_selling) = tokio::join!(
+        load_dashboard(&pool.0, cache),
+        load_author_stats(&pool.0, cache),
+        load_top_rated(&pool.0, cache, TOP_RATED),
+        load_top_selling(&pool.0, cache, TOP_SELLING),
+    );
+
+    // ===== index =====
+    let dash_html = match dashboard {
+        Ok(d) => format!(
+            r#"<li><strong>average_rating:</strong> {:.6}</li>
+        <li><strong>total_authors:</strong> {}</li>
+        <li><strong>total_books:</strong> {}</li>
+        <li><strong>total_reviews:</strong> {}</li>
+        <li><strong>total_sales:</strong> {}</li>"#,
+            d["average_rating"].as_f64().unwrap_or(0.0),
+            d["total_authors"].as_i64().unwrap_or(0),
+            d["total_books"].as_i64().unwrap_or(0),
+            d["total_reviews"].as_i64().unwrap_or(0),
+            d["total_sales"].as_i64().unwrap_or(0),
+        ),
+        Err(e) => format!("<li>Error cargando métricas: {}</li>", escape_html(&e.to_string())),
+    };
+
+    // ===== authors agg =====
+    let authors_html = match authors {
+        Ok(rows) if rows.is_empty() => r#"<tr><td colspan="4" class="muted">No hay datos.</td></tr>"#.to_string(),
+        Ok(rows) => rows.iter().map(|r| {
+            let avg = r.avg_rating.unwrap_or(0.0);
+            format!(
+                r#"<tr data-author="{name}" data-books="{books}" data-avg="{avg}" data-sales="{sales}">
+              <td>{name}</td>
+              <td class="center">{books}</td>
+              <td class="center">{avg:.2}</td>
+              <td class="right">{sales_fmt}</td>
+            </tr>"#,
+                name = escape_html(&r.author_name),
+                books = r.books,
+                avg = avg,
+                sales = r.total_sales,
+                sales_fmt = thousands(r.total_sales),
+            )
+        }).collect(),
+        Err(e) => format!(r#"<tr><td colspan="4">Error calculando tabla de autores: {}</td></tr>"#, escape_html(&e.to_string())),
+    };
+
+    // ===== top 10 =====
+    let top_rated_html = match top_rated {
+        Ok(rows) if rows.is_empty() => r#"<tr><td colspan="5" class="muted">No hay datos suficientes de reviews.</td></tr>"#.to_string(),
+        Ok(rows) => rows.iter().map(|item| {
+            let review_cell = |r: &Option<crate::models::ReviewSnippet>| match r {
+                Some(r) => format!(
+                    r#"<div><strong>Rating:</strong> {} <span class="muted">({} up-votes)</span></div>
+                  <div>{}</div>"#,
+                    r.rating, r.positive_votes, escape_html(&truncate(&r.review_text, 240))
+                ),
+                None => String::new(),
+            };
+            format!(
+                r#"<tr>
+                <td>
+                  <div><strong>{title}</strong></div>
+                  <div class="muted">ID: {id}</div>
+                </td>
+                <td class="nowrap">{avg:.2}</td>
+                <td>{highest}</td>
+                <td>{lowest}</td>
+                <td class="nowrap">
+                  <a href="/books/{id}"><button class="btn">Ver libro</button></a>
+                </td>
+              </tr>"#,
+                title = escape_html(&truncate(&item.title, 80)),
+                id = item.book_id,
+                avg = item.avg_rating,
+                highest = review_cell(&item.highest),
+                lowest = review_cell(&item.lowest),
+            )
+        }).collect(),
+        Err(e) => format!(r#"<tr><td colspan="5">Error calculando top 10: {}</td></tr>"#, escape_html(&e.to_string())),
+    };
+
+    // ===== 50 sales =====
+    let top_selling_html = match top_selling {
+        Ok(rows) if rows.is_empty() => r#"<tr><td colspan="5" class="muted">No hay datos de ventas.</td></tr>"#.to_string(),
+        Ok(rows) => rows.iter().map(|b| {
+            let year = b.publication_year.map(|y| y.to_string()).unwrap_or_else(|| "N/A".to_string());
+            format!(
+                r#"<tr>
+                <td>
+                  <div><strong>{title}</strong></div>
+                  <div class="muted">ID: {id} · Autor: {author} · Año: {year}</div>
+                </td>
+                <td class="nowrap">{sales}</td>
+                <td class="nowrap">{author_sales}</td>
+                <td class="nowrap">
+                  <span class="pill {pill}">{top5}</span>
+                </td>
+                <td class="nowrap">
+                  <a href="/books/{id}"><button class="btn">Ver libro</button></a>
+                </td>
+              </tr>"#,
+                title = escape_html(&truncate(&b.title, 80)),
+                id = b.book_id,
+                author = escape_html(&b.author_name),
+                year = year,
+                sales = thousands(b.sales_count),
+                author_sales = thousands(b.author_total_sales),
+                pill = if b.top5_in_year { "ok" } else { "no" },
+                top5 = if b.top5_in_year { "Sí" } else { "No" },
+            )
+        }).collect(),
+        Err(e) => format!(r#"<tr><td colspan="5">Error calculando top 50: {}</td></tr>"#, escape_html(&e.to_string())),
+    };
+
+    let body = format!(r#"
       <style>
-        table { border-collapse: collapse; width: 100%; margin-top: 1rem; }
-        th, td { border: 1px solid #ddd; padding: 8px; vertical-align: top; }
-        th { background: #f7f7f7; text-align: left; cursor: default; }
-        .th-sortable { cursor: pointer; user-select: none; }
-        .muted { color: #666; font-size: 0.9em; }
-        .nowrap { white-space: nowrap; }
-        .pill { display:inline-block; padding:2px 8px; border-radius: 999px; border:1px solid #ddd; font-size:.85em; }
-        .pill.ok { background:#e6ffed; border-color:#b7f5c7; }
-        .pill.no { background:#ffecec; border-color:#f5c1c1; }
-        .btn { padding:6px 10px; }
-        .filters input { width: 100%; box-sizing: border-box; padding: 4px; }
-        .right { text-align: right; }
-        .center { text-align: center; }
+        table {{ border-collapse: collapse; width: 100%; margin-top: 1rem; }}
+        th, td {{ border: 1px solid #ddd; padding: 8px; vertical-align: top; }}
+        th {{ background: #f7f7f7; text-align: left; cursor: default; }}
+        .th-sortable {{ cursor: pointer; user-select: none; }}
+        .muted {{ color: #666; font-size: 0.9em; }}
+        .nowrap {{ white-space: nowrap; }}
+        .pill {{ display:inline-block; padding:2px 8px; border-radius: 999px; border:1px solid #ddd; font-size:.85em; }}
+        .pill.ok {{ background:#e6ffed; border-color:#b7f5c7; }}
+        .pill.no {{ background:#ffecec; border-color:#f5c1c1; }}
+        .btn {{ padding:6px 10px; }}
+        .filters input {{ width: 100%; box-sizing: border-box; padding: 4px; }}
+        .right {{ text-align: right; }}
+        .center {{ text-align: center; }}
       </style>
 
       <!-- ===== index ===== -->
       <h2>Resumen</h2>
       <ul id="dash-list" class="muted">
-        <li>Cargando métricas…</li>
+        {dash_html}
       </ul>
+      <p class="muted">
+        Descargar (Excel):
+        <a href="/api/export/authors?format=excel">autores</a> ·
+        <a href="/api/export/catalog?format=excel">libros con autor, rating y ventas</a> ·
+        <a href="/api/export/reviews?format=excel">reseñas</a> ·
+        <a href="/api/export/sales?format=excel">ventas por año</a>
+      </p>
 
       <!-- ===== authors agg ===== -->
       <h2>Autores — libros, puntaje promedio y ventas totales</h2>
@@ -46,7 +196,7 @@ pub async fn tables_index(_pool: &State<Db>) -> RawHtml<String> {
           </tr>
         </thead>
         <tbody id="authors-body">
-          <tr><td colspan="4" class="muted">Calculando…</td></tr>
+          {authors_html}
         </tbody>
       </table>
 
@@ -63,7 +213,7 @@ pub async fn tables_index(_pool: &State<Db>) -> RawHtml<String> {
           </tr>
         </thead>
         <tbody id="top-books-body">
-          <tr><td colspan="5" class="muted">Calculando…</td></tr>
+          {top_rated_html}
         </tbody>
       </table>
 
@@ -80,334 +230,65 @@ pub async fn tables_index(_pool: &State<Db>) -> RawHtml<String> {
           </tr>
         </thead>
         <tbody id="top-sales-body">
-          <tr><td colspan="5" class="muted">Calculando…</td></tr>
+          {top_selling_html}
         </tbody>
       </table>
 
       <script>
-        function truncate(text, len = 140) {
-          if (!text) return '';
-          text = String(text);
-          return text.length > len ? text.slice(0, len - 1) + '…' : text;
-        }
-        function num(x){ const n = Number(x); return Number.isFinite(n) ? n : 0; }
-
-        // ===== index =====
-        async function loadDashboard() {
-          const ul = document.getElementById('dash-list');
-          try {
-            const res = await fetch('/api/dashboard');
-            if (!res.ok) throw new Error(res.statusText);
-            const wrapper = await res.json();
-            if (!wrapper || !wrapper.success) throw new Error(wrapper?.message || 'Respuesta inválida');
-            const d = wrapper.data || wrapper;
-
-            ul.innerHTML = `
-              <li><strong>average_rating:</strong> ${Number(d.average_rating ?? 0).toFixed(6)}</li>
-              <li><strong>total_authors:</strong> ${d.total_authors ?? 0}</li>
-              <li><strong>total_books:</strong> ${d.total_books ?? 0}</li>
-              <li><strong>total_reviews:</strong> ${d.total_reviews ?? 0}</li>
-              <li><strong>total_sales:</strong> ${d.total_sales ?? 0}</li>
-            `;
-          } catch (err) {
-            ul.innerHTML = `<li>Error cargando métricas: ${err}</li>`;
-          }
-        }
-
-        // ===== authors agg =====
-        const authorsAggState = {
-          rows: [],
-          sortKey: 'sales',
-          sortDir: 'desc' // 'asc'|'desc'
-        };
-
-        async function loadAuthorsAgg() {
-          const tbody = document.getElementById('authors-body');
-          try {
-            // Traemos libros (traen autor embebido + sales_count)
-            const booksRes = await fetch('/api/books');
-            if (!booksRes.ok) throw new Error(booksRes.statusText);
-            const booksWrapper = await booksRes.json();
-            if (!booksWrapper.success) throw new Error(booksWrapper.message);
-            const books = Array.isArray(booksWrapper.data) ? booksWrapper.data : [];
-
-            // Mapa por autor
-            const byAuthor = new Map(); // author_id -> { author_id, author_name, books:[], salesTotal, ratings:[] }
-            for (const b of books) {
-              const aid = b.author?.id ?? b.author_id;
-              const aname = b.author?.name ?? 'Desconocido';
-              if (!byAuthor.has(aid)) {
-                byAuthor.set(aid, { author_id: aid, author_name: aname, books: [], salesTotal: 0, ratings: [] });
-              }
-              const entry = byAuthor.get(aid);
-              entry.books.push(b);
-              entry.salesTotal += num(b.sales_count);
-            }
+        // Orden y filtros de la tabla de autores sobre las filas ya renderizadas (sin pedir datos)
+        const authorsBody = document.getElementById('authors-body');
+        const rows = Array.from(authorsBody.querySelectorAll('tr[data-author]'));
+        const state = {{ sortKey: 'sales', sortDir: 'desc' }};
 
-            // Para calcular rating promedio del autor, juntamos todas las reviews de todos sus libros
-            // (simple pero potencialmente costoso. Para datasets medianos funciona bien)
-            const rows = [];
-            for (const entry of byAuthor.values()) {
-              let ratings = [];
-              for (const bk of entry.books) {
-                try {
-                  const rRes = await fetch(`/api/books/${bk.id}/reviews`);
-                  if (!rRes.ok) throw new Error(rRes.statusText);
-                  const rWrap = await rRes.json();
-                  if (!rWrap.success) throw new Error(rWrap.message);
-                  const reviews = Array.isArray(rWrap.data) ? rWrap.data : [];
-                  ratings.push(...reviews.map(r => num(r.rating)));
-                } catch (e) {
-                  console.warn('No se pudieron leer reviews de libro', bk?.id, e);
-                }
-              }
-              const avg = ratings.length ? (ratings.reduce((a, b) => a + b, 0) / ratings.length) : 0;
-              rows.push({
-                author_id: entry.author_id,
-                author_name: entry.author_name,
-                books: entry.books.length,
-                avg,
-                sales: entry.salesTotal
-              });
-            }
-
-            authorsAggState.rows = rows;
-            renderAuthorsAgg();
-            wireAuthorsSortAndFilters();
-          } catch (err) {
-            tbody.innerHTML = `<tr><td colspan="4">Error calculando tabla de autores: ${err}</td></tr>`;
-          }
-        }
-
-        function applyAuthorsFilters(data) {
+        function renderAuthorsAgg() {{
           const fa = document.getElementById('f-author').value.trim().toLowerCase();
-          const fb = document.getElementById('f-books').value.trim();
-          const favg = document.getElementById('f-avg').value.trim();
-          const fs = document.getElementById('f-sales').value.trim();
-
-          return data.filter(r => {
-            if (fa && !String(r.author_name).toLowerCase().includes(fa)) return false;
-            if (fb && !(r.books >= Number(fb))) return false;
-            if (favg && !(r.avg >= Number(favg))) return false;
-            if (fs && !(r.sales >= Number(fs))) return false;
-            return true;
-          });
-        }
-
-        function sortByKey(data, key, dir) {
-          const mul = dir === 'desc' ? -1 : 1;
-          return [...data].sort((a, b) => {
-            let va = a[key], vb = b[key];
-            if (typeof va === 'string') { va = va.toLowerCase(); vb = String(vb).toLowerCase(); }
-            if (va < vb) return -1 * mul;
-            if (va > vb) return 1 * mul;
-            return 0;
-          });
-        }
-
-        function renderAuthorsAgg() {
-          const tbody = document.getElementById('authors-body');
-          const state = authorsAggState;
-
-          let rows = applyAuthorsFilters(state.rows);
-          rows = sortByKey(rows, state.sortKey, state.sortDir);
-
-          tbody.innerHTML = '';
-          if (rows.length === 0) {
-            tbody.innerHTML = '<tr><td colspan="4" class="muted">No hay datos.</td></tr>';
-            return;
-          }
-
-          for (const r of rows) {
-            const tr = document.createElement('tr');
-            tr.innerHTML = `
-              <td>${r.author_name}</td>
-              <td class="center">${r.books}</td>
-              <td class="center">${r.avg.toFixed(2)}</td>
-              <td class="right">${Number(r.sales).toLocaleString()}</td>
-            `;
-            tbody.appendChild(tr);
-          }
-        }
-
-        function wireAuthorsSortAndFilters() {
-          // Sorting
-          document.querySelectorAll('th.th-sortable').forEach(th => {
-            th.addEventListener('click', () => {
-              const key = th.dataset.sortKey;
-              if (!key) return;
-              if (authorsAggState.sortKey === key) {
-                authorsAggState.sortDir = authorsAggState.sortDir === 'asc' ? 'desc' : 'asc';
-              } else {
-                authorsAggState.sortKey = key;
-                authorsAggState.sortDir = key === 'author' ? 'asc' : 'desc';
-              }
-              renderAuthorsAgg();
-            });
-          });
-          // Filters
-          ['f-author','f-books','f-avg','f-sales'].forEach(id => {
-            const el = document.getElementById(id);
-            el.addEventListener('input', () => renderAuthorsAgg());
-          });
-        }
-
-        // ===== top 10 =====
-        async function loadTopBooksByRating() {
-          const tbody = document.getElementById('top-books-body');
-          try {
-            const booksRes = await fetch('/api/books');
-            if (!booksRes.ok) throw new Error(booksRes.statusText);
-            const booksWrapper = await booksRes.json();
-            if (!booksWrapper.success) throw new Error(booksWrapper.message);
-            const books = Array.isArray(booksWrapper.data) ? booksWrapper.data : [];
-
-            const withStats = [];
-            for (const b of books) {
-              try {
-                const rRes = await fetch(`/api/books/${b.id}/reviews`);
-                if (!rRes.ok) throw new Error(rRes.statusText);
-                const rWrap = await rRes.json();
-                if (!rWrap.success) throw new Error(rWrap.message);
-                const reviews = Array.isArray(rWrap.data) ? rWrap.data : [];
-                if (reviews.length === 0) continue;
-
-                const avg = reviews.reduce((acc, r) => acc + (r.rating || 0), 0) / reviews.length;
-
-                const maxRating = Math.max(...reviews.map(r => r.rating || 0));
-                const highest = reviews.filter(r => (r.rating || 0) === maxRating)
-                  .sort((a, b) => (b.positive_votes || 0) - (a.positive_votes || 0))[0];
-
-                const minRating = Math.min(...reviews.map(r => r.rating || 0));
-                const lowest = reviews.filter(r => (r.rating || 0) === minRating)
-                  .sort((a, b) => (b.positive_votes || 0) - (a.positive_votes || 0))[0];
-
-                withStats.push({ id: b.id, title: b.title, avgRating: avg, highest, lowest });
-              } catch (e) {
-                console.warn('No se pudieron calcular reviews para libro', b?.id, e);
-              }
-            }
-
-            withStats.sort((a, b) => b.avgRating - a.avgRating);
-            const top10 = withStats.slice(0, 10);
-
-            tbody.innerHTML = '';
-            if (top10.length === 0) {
-              tbody.innerHTML = '<tr><td colspan="5" class="muted">No hay datos suficientes de reviews.</td></tr>';
-              return;
-            }
-
-            for (const item of top10) {
-              const h = item.highest || {};
-              const l = item.lowest || {};
-              const tr = document.createElement('tr');
-              tr.innerHTML = `
-                <td>
-                  <div><strong>${truncate(item.title, 80)}</strong></div>
-                  <div class="muted">ID: ${item.id}</div>
-                </td>
-                <td class="nowrap">${item.avgRating.toFixed(2)}</td>
-                <td>
-                  <div><strong>Rating:</strong> ${h.rating ?? ''} <span class="muted">(${h.positive_votes ?? 0} up-votes)</span></div>
-                  <div>${truncate(h.review_text, 240)}</div>
-                </td>
-                <td>
-                  <div><strong>Rating:</strong> ${l.rating ?? ''} <span class="muted">(${l.positive_votes ?? 0} up-votes)</span></div>
-                  <div>${truncate(l.review_text, 240)}</div>
-                </td>
-                <td class="nowrap">
-                  <a href="/books/${item.id}"><button class="btn">Ver libro</button></a>
-                </td>
-              `;
-              tbody.appendChild(tr);
-            }
-          } catch (err) {
-            tbody.innerHTML = `<tr><td colspan="5">Error calculando top 10: ${err}</td></tr>`;
-          }
-        }
-
-        // ===== 50 sales =====
-        async function loadTop50Sales() {
-          const tbody = document.getElementById('top-sales-body');
-          try {
-            const booksRes = await fetch('/api/books');
-            if (!booksRes.ok) throw new Error(booksRes.statusText);
-            const booksWrapper = await booksRes.json();
-            if (!booksWrapper.success) throw new Error(booksWrapper.message);
-            const books = Array.isArray(booksWrapper.data) ? booksWrapper.data : [];
-
-            // Totales por autor
-            const authorTotals = new Map(); // author_id -> total_sales
-            for (const b of books) {
-              const aid = b.author?.id ?? b.author_id;
-              const sales = num(b.sales_count);
-              if (aid != null) authorTotals.set(aid, (authorTotals.get(aid) || 0) + sales);
-            }
-
-            // Agrupar por año de publicación para calcular si está en top 5 de su año
-            const byYear = new Map(); // year -> books[]
-            for (const b of books) {
-              const year = (b.publication_date || '').slice(0, 4) || 'N/A';
-              if (!byYear.has(year)) byYear.set(year, []);
-              byYear.get(year).push(b);
-            }
-
-            // Precalcular rankings por año (orden por ventas del libro)
-            const top5PerYear = new Map(); // key: `${year}:${book_id}` -> boolean
-            for (const [year, list] of byYear.entries()) {
-              const sorted = [...list].sort((a, b) => num(b.sales_count) - num(a.sales_count));
-              const top5Ids = new Set(sorted.slice(0, 5).map(x => x.id));
-              for (const x of list) {
-                top5PerYear.set(`${year}:${x.id}`, top5Ids.has(x.id));
-              }
-            }
-
-            // Top 50 por ventas del libro (históricas)
-            const top50 = [...books]
-              .sort((a, b) => num(b.sales_count) - num(a.sales_count))
-              .slice(0, 50);
-
-            tbody.innerHTML = '';
-            if (top50.length === 0) {
-              tbody.innerHTML = '<tr><td colspan="5" class="muted">No hay datos de ventas.</td></tr>';
-              return;
-            }
-
-            for (const b of top50) {
-              const aid = b.author?.id ?? b.author_id;
-              const aTotal = aid != null ? (authorTotals.get(aid) || 0) : 0;
-              const year = (b.publication_date || '').slice(0, 4) || 'N/A';
-              const isTop5Year = top5PerYear.get(`${year}:${b.id}`) === true;
-
-              const tr = document.createElement('tr');
-              tr.innerHTML = `
-                <td>
-                  <div><strong>${truncate(b.title, 80)}</strong></div>
-                  <div class="muted">ID: ${b.id} · Autor: ${b.author?.name ?? 'Desconocido'} · Año: ${year}</div>
-                </td>
-                <td class="nowrap">${num(b.sales_count).toLocaleString()}</td>
-                <td class="nowrap">${num(aTotal).toLocaleString()}</td>
-                <td class="nowrap">
-                  <span class="pill ${isTop5Year ? 'ok' : 'no'}">${isTop5Year ? 'Sí' : 'No'}</span>
-                </td>
-                <td class="nowrap">
-                  <a href="/books/${b.id}"><button class="btn">Ver libro</button></a>
-                </td>
-              `;
-              tbody.appendChild(tr);
-            }
-          } catch (err) {
-            tbody.innerHTML = `<tr><td colspan="5">Error calculando top 50: ${err}</td></tr>`;
-          }
-        }
-
-        // Cargas en paralelo
-        loadDashboard();
-        loadAuthorsAgg();
-        loadTopBooksByRating();
-        loadTop50Sales();
+          const fb = Number(document.getElementById('f-books').value.trim() || 0);
+          const favg = Number(document.getElementById('f-avg').value.trim() || 0);
+          const fs = Number(document.getElementById('f-sales').value.trim() || 0);
+          const mul = state.sortDir === 'desc' ? -1 : 1;
+
+          const value = (tr, key) => key === 'author' ? tr.dataset.author.toLowerCase() : Number(tr.dataset[key]);
+          const sorted = [...rows].sort((a, b) => {{
+            const va = value(a, state.sortKey), vb = value(b, state.sortKey);
+            return va < vb ? -1 * mul : va > vb ? 1 * mul : 0;
+          }});
+          for (const tr of sorted) {{
+            const visible = (!fa || tr.dataset.author.toLowerCase().includes(fa))
+              && Number(tr.dataset.books) >= fb
+              && Number(tr.dataset.avg) >= favg
+              && Number(tr.dataset.sales) >= fs;
+            tr.style.display = visible ? '' : 'none';
+            authorsBody.appendChild(tr);
+          }}
+        }}
+
+        document.querySelectorAll('th.th-sortable').forEach(th => {{
+          th.addEventListener('click', () => {{
+            const key = th.dataset.sortKey;
+            if (state.sortKey === key) {{
+              state.sortDir = state.sortDir === 'asc' ? 'desc' : 'asc';
+            }} else {{
+              state.sortKey = key;
+              state.sortDir = key === 'author' ? 'asc' : 'desc';
+            }}
+            renderAuthorsAgg();
+          }});
+        }});
+        ['f-author','f-books','f-avg','f-sales'].forEach(id => {{
+          document.getElementById(id).addEventListener('input', renderAuthorsAgg);
+        }});
       </script>
-    "#;
-
-    RawHtml(render_page("Tablas", body))
-}
\ No newline at end of file
+    "#);
+
+    RawHtml(render_page("Tablas", &body))
+}
+
+// Mismo caché que GET /api/dashboard
+async fn load_dashboard(pool: &rocket_db_pools::sqlx::SqlitePool, cache: &Cache) -> Result<serde_json::Value, rocket_db_pools::sqlx::Error> {
+    let pool = pool.clone();
+    cache
+        .get_or_compute(Cache::KEY_DASHBOARD_STATS, Cache::TTL_5_MIN, |_| Vec::new(), move || async move {
+            repository::get_dashboard_stats(&pool).await
+        })
+        .await
+}